@@ -0,0 +1,24 @@
+//! The clock trait, used to measure the time between polls. This is
+//! injectable so that tests can simulate things like the process
+//! being paused (`SIGSTOP`) without actually waiting.
+
+use std::time::Instant;
+
+/// A source of monotonic time. Implementations must never be
+/// affected by the system clock being stepped (NTP, the user changing
+/// the time, etc), which is why this returns an `Instant` and not a
+/// `SystemTime`.
+pub trait Clock {
+    /// Return the current point in time
+    fn now(&self) -> Instant;
+}
+
+/// The default clock, which uses the operating system's monotonic
+/// clock
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MonotonicClock;
+impl Clock for MonotonicClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
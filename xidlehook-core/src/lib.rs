@@ -29,9 +29,16 @@
 //! This library lets you create your own xidlehook front-end using a
 //! powerful timer and module system.
 
-use std::{cmp, convert::TryInto, fmt, ptr, time::Duration};
+use std::{
+    cmp,
+    convert::TryInto,
+    fmt,
+    future::Future,
+    mem, ptr,
+    time::{Duration, Instant},
+};
 
-use log::trace;
+use log::{debug, log_enabled, trace, Level};
 use nix::libc;
 
 /// The default error type for xidlehook. Unfortunately, it's a
@@ -40,14 +47,28 @@ pub type Error = Box<dyn std::error::Error>;
 /// An alias to Result which overrides the default Error type.
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+pub mod clock;
 pub mod modules;
 pub mod timers;
 
 pub use self::{
+    clock::Clock,
     modules::{Module, Progress},
     timers::Timer,
 };
 
+/// How much more time may pass between two polls than the idle time
+/// increased, before xidlehook assumes the user was active in between
+/// (see `Xidlehook::poll`).
+const CLOCK_TOLERANCE: Duration = Duration::from_secs(1);
+
+/// The log target of a structured trace of the decisions made while
+/// stepping: The idle time and resulting action of every step, and
+/// the `Progress` each `modules::Toggle` returned. The records are
+/// logged at the trace level, with their details as key-value pairs.
+/// Nothing is computed unless this target is enabled.
+pub const DECISION_TARGET: &str = "xidlehook_core::decision";
+
 /// An identifier for a timer, based on the index in the timer list
 /// and its length.
 #[derive(Clone, Copy, Debug)]
@@ -56,6 +77,121 @@ pub struct TimerInfo {
     pub index: usize,
     /// The length of the timer list
     pub length: usize,
+    /// How far the chain got: The index of the timer that was
+    /// activated last, unless none was. When a timer is deactivated,
+    /// this is the timer that took over from it, otherwise it's this
+    /// timer once it's activated.
+    pub reached: Option<usize>,
+    /// Whether no enabled timer comes after this one, so that the
+    /// chain ends with it, see `Timer::disabled`
+    pub last: bool,
+}
+
+/// What a single `Xidlehook::step` did
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// No timer was activated. Sleep for at most this long before
+    /// stepping again.
+    Sleep(Duration),
+    /// The timer with this index was activated. Sleep for at most
+    /// `sleep` before stepping again.
+    Activated {
+        /// The index of the activated timer
+        index: usize,
+        /// The max amount of time to sleep for
+        sleep: Duration,
+    },
+    /// A module aborted the chain just as the timer with this index
+    /// was about to be activated. Sleep for at most `sleep` before
+    /// stepping again.
+    Aborted {
+        /// The index of the timer that wasn't activated
+        index: usize,
+        /// The max amount of time to sleep for
+        sleep: Duration,
+    },
+    /// A module halted the chain just as the timer with this index
+    /// was about to be activated, see `Progress::Halt`. Sleep for at
+    /// most `sleep` before stepping again.
+    Halted {
+        /// The index of the timer that wasn't activated
+        index: usize,
+        /// The max amount of time to sleep for
+        sleep: Duration,
+    },
+    /// A module requested that xidlehook stops
+    Stop,
+}
+impl Action {
+    /// The max amount of time to sleep before stepping again, or
+    /// `None` if xidlehook should stop
+    pub fn sleep(self) -> Option<Duration> {
+        match self {
+            Self::Sleep(sleep)
+            | Self::Activated { sleep, .. }
+            | Self::Aborted { sleep, .. }
+            | Self::Halted { sleep, .. } => Some(sleep),
+            Self::Stop => None,
+        }
+    }
+
+    /// The same action, but sleeping for at most `max`
+    fn cap_sleep(self, max: Duration) -> Self {
+        match self {
+            Self::Sleep(sleep) => Self::Sleep(sleep.min(max)),
+            Self::Activated { index, sleep } => Self::Activated {
+                index,
+                sleep: sleep.min(max),
+            },
+            Self::Aborted { index, sleep } => Self::Aborted {
+                index,
+                sleep: sleep.min(max),
+            },
+            Self::Halted { index, sleep } => Self::Halted {
+                index,
+                sleep: sleep.min(max),
+            },
+            Self::Stop => Self::Stop,
+        }
+    }
+}
+
+/// Where a timer is in the current chain, see `Xidlehook::timer_state`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimerState {
+    /// Waiting for its turn
+    Armed,
+    /// Activated, and not deactivated or aborted (yet)
+    Active,
+    /// Activated, and then deactivated as the next timer took over
+    Deactivated,
+    /// Activated, and then aborted as the user became active or a
+    /// module aborted the chain
+    Aborted,
+    /// Won't run until the user is active again, as the chain was
+    /// aborted or halted before reaching it
+    Skipped,
+}
+
+/// How far the chain got in the current idle episode, see
+/// `Xidlehook::episode`. Together with the idle time, this is enough
+/// to pick up where a previous instance left off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Episode {
+    /// The index of the timer that's up next. The last timer before
+    /// it is active.
+    pub next_index: usize,
+    /// The absolute idle time when the last timer was activated
+    pub base_idle_time: Duration,
+}
+
+/// Whether the timers run, see `Xidlehook::set_paused`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pause {
+    Running,
+    Paused,
+    /// Just resumed, so the next step counts the idle time from there
+    Resumed,
 }
 
 /// The main xidlehook instance that allows you to schedule things
@@ -77,6 +213,24 @@ where
     /// If a chain is aborted during the process, store this here as
     /// to not make any more attempts to continue it.
     aborted: bool,
+    /// Timers that a module left active by vetoing a deactivation, see
+    /// `Module::post_timer`. They're aborted, latest first, once the
+    /// user is active again.
+    vetoed: Vec<usize>,
+    /// If a module halted the chain, store this here as to not run
+    /// any more timers until the user is active again. Unlike
+    /// `aborted`, the previous timer is still aborted then.
+    halted: bool,
+    /// The state of each timer that isn't `TimerState::Armed`, by
+    /// index. Timers past the end are armed.
+    states: Vec<TimerState>,
+    /// The clock used to measure the time between polls
+    clock: Box<dyn Clock>,
+    /// When the last poll happened, according to `clock`
+    last_poll: Option<Instant>,
+    /// The longest sleep ever returned, see `with_max_sleep`
+    max_sleep: Option<Duration>,
+    pause: Pause,
 }
 impl<T: Timer> Xidlehook<T, ()> {
     /// An empty instance without any modules
@@ -89,6 +243,13 @@ impl<T: Timer> Xidlehook<T, ()> {
             base_idle_time: Duration::default(),
             previous_idle_time: Duration::default(),
             aborted: false,
+            vetoed: Vec::new(),
+            halted: false,
+            states: Vec::new(),
+            clock: Box::new(clock::MonotonicClock),
+            last_poll: None,
+            max_sleep: None,
+            pause: Pause::Running,
         }
     }
 }
@@ -102,6 +263,13 @@ macro_rules! with_module {
             base_idle_time: $self.base_idle_time,
             previous_idle_time: $self.previous_idle_time,
             aborted: $self.aborted,
+            vetoed: $self.vetoed,
+            halted: $self.halted,
+            states: $self.states,
+            clock: $self.clock,
+            last_poll: $self.last_poll,
+            max_sleep: $self.max_sleep,
+            pause: $self.pause,
         }
     };
 }
@@ -127,18 +295,157 @@ where
         with_module!(self, (self.module, other))
     }
 
+    /// Return this xidlehook instance but with a different clock. The default is
+    /// `clock::MonotonicClock`, which is what you want unless you're writing tests.
+    #[must_use]
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Return this xidlehook instance but never sleeping longer than `max` between steps, so that
+    /// a sleep which outlasts a suspend of the system is cut short. Without a cap, which is the
+    /// default, it sleeps right until the next timer is due.
+    #[must_use]
+    pub fn with_max_sleep(mut self, max: Duration) -> Self {
+        self.max_sleep = Some(max);
+        self
+    }
+
     /// Returns an immutable list of all timers
     pub fn timers(&self) -> &Vec<T> {
         &self.timers
     }
 
+    /// Returns the registered module(s)
+    pub fn module(&self) -> &M {
+        &self.module
+    }
+
+    /// Returns the registered module(s) mutably, such as to change their settings at runtime
+    pub fn module_mut(&mut self) -> &mut M {
+        &mut self.module
+    }
+
     /// Returns a mutable list of all timers. Use this to add or remove timers as you wish. This
     /// will abort the idle chain as that may otherwise panic.
     pub fn timers_mut(&mut self) -> Result<&mut Vec<T>> {
         self.abort()?;
+        // The indices may no longer match, and nothing is active anymore anyway
+        self.states.clear();
         Ok(&mut self.timers)
     }
 
+    /// Replace all timers, such as when an application reloads its configuration, without
+    /// disrupting the current chain if it can be helped. `unchanged` tells whether a new timer is
+    /// the same as an old one, such as by comparing their names or definitions. If every timer
+    /// that already went off in this chain is unchanged and still at the same index, the chain
+    /// carries on with those timers, which keep their state, and any timers after them are free
+    /// to be added, removed or changed. Otherwise, this aborts the chain just like `timers_mut`.
+    /// Returns whether the chain was kept.
+    ///
+    /// # Errors
+    ///
+    /// If aborting the chain fails, such as when a module does
+    pub fn replace_timers<F>(&mut self, timers: Vec<T>, mut unchanged: F) -> Result<bool>
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let ran = self.next_index;
+        let kept = timers.len() >= ran
+            && self
+                .timers
+                .iter()
+                .zip(&timers)
+                .take(ran)
+                .all(|(old, new)| unchanged(old, new));
+
+        if kept {
+            self.states.truncate(ran);
+            let old = mem::replace(&mut self.timers, timers);
+            for (timer, old) in self.timers.iter_mut().zip(old).take(ran) {
+                *timer = old;
+            }
+        } else {
+            *self.timers_mut()? = timers;
+        }
+        Ok(kept)
+    }
+
+    /// Insert a timer at this index, such as one added at runtime. If it goes after every timer
+    /// that already went off in this chain, the chain carries on, and the new timer goes off once
+    /// it's due. That's on the next poll if the idle time is already past it. Otherwise, this
+    /// aborts the chain just like `timers_mut`. Returns whether the chain was kept.
+    ///
+    /// # Errors
+    ///
+    /// If aborting the chain fails, such as when a module does
+    pub fn insert_timer(&mut self, index: usize, timer: T) -> Result<bool> {
+        if index > self.timers.len() {
+            return Err(format!(
+                "index {} is out of range, there are only {} timers",
+                index,
+                self.timers.len()
+            )
+            .into());
+        }
+        if index < self.next_index {
+            self.timers_mut()?.insert(index, timer);
+            return Ok(false);
+        }
+        self.timers.insert(index, timer);
+        if index < self.states.len() {
+            self.states.insert(index, TimerState::Armed);
+        }
+        Ok(true)
+    }
+
+    /// Remove the timer with this index, such as one removed at runtime. If it hasn't gone off
+    /// yet in this chain, the chain carries on without it. Otherwise, such as when it's the one
+    /// that's active, the chain is aborted just like with `timers_mut`, which runs its abortion
+    /// first. Returns the timer, and whether the chain was kept.
+    ///
+    /// # Errors
+    ///
+    /// If aborting the chain fails, such as when a module does
+    pub fn remove_timer(&mut self, index: usize) -> Result<(T, bool)> {
+        if index >= self.timers.len() {
+            return Err(format!(
+                "timer {} does not exist, there are only {} timers",
+                index,
+                self.timers.len()
+            )
+            .into());
+        }
+        if index < self.next_index {
+            return Ok((self.timers_mut()?.remove(index), false));
+        }
+        if index < self.states.len() {
+            self.states.remove(index);
+        }
+        Ok((self.timers.remove(index), true))
+    }
+
+    /// Returns where the timer with this index is in the current chain. Whether it's disabled
+    /// isn't taken into account, see `Timer::disabled` for that.
+    pub fn timer_state(&self, index: usize) -> TimerState {
+        match self.states.get(index) {
+            Some(&state) if state != TimerState::Armed => state,
+            _ if self.aborted || self.halted => TimerState::Skipped,
+            _ => TimerState::Armed,
+        }
+    }
+
+    fn set_state(&mut self, index: usize, state: TimerState) {
+        if self.states.len() <= index {
+            self.states.resize(
+                self.timers.len().max(index.saturating_add(1)),
+                TimerState::Armed,
+            );
+        }
+        self.states[index] = state;
+    }
+
     /// Returns the previous timer that was activated (but not deactivated)
     fn previous(&mut self) -> Option<&mut T> {
         self.next_index
@@ -149,31 +456,58 @@ where
     /// Calls the abortion function on the current timer and stops pursuing the chain
     fn abort(&mut self) -> Result<()> {
         if self.aborted {
+            for index in mem::take(&mut self.vetoed).into_iter().rev() {
+                self.abort_timer(index)?;
+            }
             return Ok(());
         }
 
         self.aborted = true;
-        if let Some(prev) = self.previous() {
-            prev.abort()?;
+        if let Some(index) = self.next_index.checked_sub(1) {
+            self.abort_timer(index)?;
         }
         Ok(())
     }
 
+    /// Calls the abortion function of this timer
+    fn abort_timer(&mut self, index: usize) -> Result<()> {
+        let info = self.timer_info(index, Some(index));
+        let timer = &mut self.timers[index];
+        timer.prepare(info);
+        timer.abort()?;
+        debug!(
+            timer = index, event = "aborted";
+            "Timer {} aborted", display_name(index, timer)
+        );
+        self.set_state(index, TimerState::Aborted);
+        Ok(())
+    }
+
     /// Calls the abortion functions on the current timer and restarts from index zero. Just like
     /// `poll` is continued usage after an error discouraged.
     fn reset(&mut self) -> Result<()> {
         self.abort()?;
+        for timer in &mut self.timers {
+            timer.reset();
+        }
 
         if self.next_index > 0 {
             if let Err(err) = self.module.reset() {
                 self.module.warning(&err)?;
             }
+            for index in 0..self.timers.len() {
+                if let Some(Err(err)) = self.timers[index].module().map(Module::reset) {
+                    self.module.warning(&err)?;
+                }
+            }
             self.next_index = 0;
         }
 
         self.base_idle_time = Duration::default();
         self.previous_idle_time = Duration::default();
         self.aborted = false;
+        self.halted = false;
+        self.states.clear();
 
         Ok(())
     }
@@ -190,6 +524,212 @@ where
         Some(index)
     }
 
+    /// Describe the timer at this index to itself and to the modules
+    fn timer_info(&mut self, index: usize, reached: Option<usize>) -> TimerInfo {
+        TimerInfo {
+            index,
+            length: self.timers.len(),
+            reached,
+            last: self.next_enabled(index.saturating_add(1)).is_none(),
+        }
+    }
+
+    /// Treat this as user activity, even though the idle time hasn't reset (yet), such as when a
+    /// blocking lock command exits because the user unlocked the screen. The current timer is
+    /// aborted and the chain starts over, just like on activity, but it counts from
+    /// `absolute_time` instead of from zero, so it doesn't matter how long the idle time takes to
+    /// catch up.
+    ///
+    /// # Errors
+    ///
+    /// If aborting the current timer fails, such as when a module does
+    pub fn reset_activity(&mut self, absolute_time: Duration) -> Result<()> {
+        self.reset()?;
+        self.base_idle_time = absolute_time;
+        self.previous_idle_time = absolute_time;
+        // Time passed without any polls (such as while the lock command ran), which would
+        // otherwise also count as activity and reset the base idle time back to zero
+        self.last_poll = Some(self.clock.now());
+        Ok(())
+    }
+
+    /// Returns how far the chain got since the user was last active, or `None` if no timer is
+    /// active, or the chain was aborted or halted.
+    pub fn episode(&self) -> Option<Episode> {
+        let last = self.next_index.checked_sub(1)?;
+        if self.aborted || self.halted || self.timer_state(last) != TimerState::Active {
+            return None;
+        }
+        Some(Episode {
+            next_index: self.next_index,
+            base_idle_time: self.base_idle_time,
+        })
+    }
+
+    /// Continue an episode returned by `episode`, such as one of a previous instance, as if the
+    /// timers before `next_index` had activated. They aren't run again, but the last one is
+    /// aborted as usual once the user is active. The caller must make sure the user wasn't active
+    /// since, and that the timers are the same. Returns false if the episode doesn't fit these
+    /// timers.
+    ///
+    /// # Errors
+    ///
+    /// If a module fails to reset
+    pub fn resume_episode(&mut self, episode: Episode, absolute_time: Duration) -> Result<bool> {
+        let last = match episode.next_index.checked_sub(1) {
+            Some(last) if last < self.timers.len() => last,
+            _ => return Ok(false),
+        };
+        self.reset()?;
+        for index in 0..last {
+            if !self.timers[index].disabled() {
+                self.set_state(index, TimerState::Deactivated);
+            }
+        }
+        self.set_state(last, TimerState::Active);
+        self.next_index = episode.next_index;
+        self.base_idle_time = episode.base_idle_time;
+        self.previous_idle_time = absolute_time;
+        self.last_poll = Some(self.clock.now());
+        Ok(true)
+    }
+
+    /// Pause or resume all timers, such as while an application that
+    /// the embedding program detects itself is fullscreen. While
+    /// paused, the user is treated as active: Pausing aborts the chain
+    /// like activity would, steps never activate a timer, and once
+    /// resumed, the idle time counts from the next step on, as if the
+    /// user had just been active. Steps still happen while paused, so
+    /// a loop such as `main_async` keeps running, and picks up the
+    /// resume on its next step. Unlike disabling every timer, this
+    /// leaves the timers alone.
+    ///
+    /// # Errors
+    ///
+    /// If aborting the chain fails, such as when a module does
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        if paused == self.is_paused() {
+            return Ok(());
+        }
+        if paused {
+            debug!("Pausing the timers");
+            self.reset()?;
+            self.pause = Pause::Paused;
+        } else {
+            debug!("Resuming the timers");
+            self.pause = Pause::Resumed;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the timers are paused, see `set_paused`
+    pub fn is_paused(&self) -> bool {
+        self.pause == Pause::Paused
+    }
+
+    /// Push back the timer that's up next by this much, such as for a "5 more minutes" button on
+    /// a notification. This only lasts until that timer activates or the user is active, and
+    /// snoozing several times adds up. Returns false if there's no timer left to snooze, such as
+    /// when the chain was aborted.
+    pub fn snooze(&mut self, duration: Duration) -> bool {
+        if self.aborted || self.halted || self.next_enabled(self.next_index).is_none() {
+            return false;
+        }
+        // The pending timer counts from the base idle time, which is reset when it activates
+        self.base_idle_time = self.base_idle_time.saturating_add(duration);
+        true
+    }
+
+    /// Returns the index of the timer that's up next and how long until it activates, given the
+    /// current absolute idle time, such as for a "locking in 4:59" countdown. The modules are
+    /// asked whether they'd let it run right now with `Module::would_allow`, so this is `None`
+    /// if one of them would abort, halt or stop the chain. It's also `None` if no timer is left
+    /// in this chain, such as when it was aborted, until the user is active again. This doesn't
+    /// change anything, and `pre_timer` isn't called.
+    ///
+    /// # Errors
+    ///
+    /// If a module or timer fails to tell, just like `poll`
+    pub fn next_activation(
+        &mut self,
+        absolute_time: Duration,
+    ) -> Result<Option<(usize, Duration)>> {
+        // If the idle time has decreased, the next step starts over from the first timer
+        let start_over = absolute_time < self.previous_idle_time;
+        if self.is_paused() || (!start_over && (self.aborted || self.halted)) {
+            return Ok(None);
+        }
+        let (start, base_idle_time) = if start_over {
+            (0, Duration::default())
+        } else {
+            (self.next_index, self.base_idle_time)
+        };
+        // Just like the next step, count from now once resumed
+        let base_idle_time = if self.pause == Pause::Resumed {
+            absolute_time
+        } else {
+            base_idle_time
+        };
+        let Some(index) = self.next_enabled(start) else {
+            return Ok(None);
+        };
+
+        let relative_time = absolute_time
+            .checked_sub(base_idle_time)
+            .unwrap_or_default();
+        let remaining = self.timers[index]
+            .time_left(relative_time)?
+            .unwrap_or_default();
+
+        let timer_info = self.timer_info(index, self.next_index.checked_sub(1));
+        let progress = match self.would_allow(timer_info) {
+            Ok(progress) => progress,
+            Err(err) => {
+                // Just like when the timer is due, a failing module doesn't hold it back
+                self.module.warning(&err)?;
+                Progress::Continue
+            },
+        };
+        match progress {
+            Progress::Continue | Progress::Force => Ok(Some((index, remaining))),
+            Progress::Abort | Progress::Halt | Progress::Stop => Ok(None),
+        }
+    }
+
+    /// Ask the modules whether this timer should run: The global ones,
+    /// and then the timer's own, if it has any
+    fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let progress = self.module.pre_timer(timer)?;
+        match self.timers[timer.index].module() {
+            Some(module) if progress != Progress::Stop => {
+                Ok(progress.combine(module.pre_timer(timer)?))
+            },
+            _ => Ok(progress),
+        }
+    }
+
+    /// Like `pre_timer`, but without side effects, see `Module::would_allow`
+    fn would_allow(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let progress = self.module.would_allow(timer)?;
+        match self.timers[timer.index].module() {
+            Some(module) if progress != Progress::Stop => {
+                Ok(progress.combine(module.would_allow(timer)?))
+            },
+            _ => Ok(progress),
+        }
+    }
+
+    /// Like `pre_timer`, but after the timer ran
+    fn post_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let progress = self.module.post_timer(timer)?;
+        match self.timers[timer.index].module() {
+            Some(module) if progress != Progress::Stop => {
+                Ok(progress.combine(module.post_timer(timer)?))
+            },
+            _ => Ok(progress),
+        }
+    }
+
     /// Skip ahead to the selected timer. Timers leading up to this point will not be ran. If you
     /// pass `force`, modules will not even be able to prevent this from happening (all requests
     /// pre-timer would be ignored). Post-timer requests are fully complied with.
@@ -197,7 +737,8 @@ where
     /// Whatever the return value is, it's already been handled. If the return value is `Err(...)`,
     /// that means this function invoked the module's `warning` function and that still wanted to
     /// propagate the error. If the return value is `Ok(Progress::Abort)`, never mind it. The
-    /// `self.abort()` function has already been invoked - it's all cool.
+    /// `self.abort()` function has already been invoked - it's all cool. Likewise, if it's
+    /// `Ok(Progress::Halt)`, the chain has been halted before this timer.
     ///
     /// # Panics
     ///
@@ -210,22 +751,23 @@ where
     ) -> Result<Progress> {
         trace!("Activating timer {}", index);
 
-        let timer_info = TimerInfo {
-            index,
-            length: self.timers.len(),
-        };
+        let timer_info = self.timer_info(index, self.next_index.checked_sub(1));
 
-        let next = &mut self.timers[index];
-
-        match self.module.pre_timer(timer_info) {
+        match self.pre_timer(timer_info) {
             Ok(_) if force => (),
 
             Ok(Progress::Continue) => (),
+            Ok(Progress::Force) => trace!("Module forced timer."),
             Ok(Progress::Abort) => {
                 trace!("Module requested abort of chain.");
                 self.abort()?;
                 return Ok(Progress::Abort);
             },
+            Ok(Progress::Halt) => {
+                trace!("Module requested halt of chain.");
+                self.halted = true;
+                return Ok(Progress::Halt);
+            },
             Ok(Progress::Stop) => return Ok(Progress::Stop),
 
             Err(err) => {
@@ -233,41 +775,170 @@ where
             },
         }
 
+        let timer_info = TimerInfo {
+            reached: Some(index),
+            ..timer_info
+        };
+        let next = &mut self.timers[index];
+        next.prepare(timer_info);
         next.activate()?;
-        if let Some(previous) = self.previous() {
-            previous.deactivate()?;
-        }
+        debug!(
+            timer = index, event = "activated";
+            "Timer {} activated", display_name(index, next)
+        );
+        self.set_state(index, TimerState::Active);
 
         self.base_idle_time = absolute_time;
 
-        match self.module.post_timer(timer_info) {
-            Ok(Progress::Continue) => (),
-            Ok(Progress::Abort) => {
-                trace!("Module requested abort of chain.");
-                self.abort()?;
-                return Ok(Progress::Abort);
-            },
-            Ok(Progress::Stop) => return Ok(Progress::Stop),
-
+        // The previous timer is only deactivated once the modules have had their say, see
+        // `Module::post_timer`
+        let progress = match self.post_timer(timer_info) {
+            Ok(progress) => progress,
             Err(err) => {
                 self.module.warning(&err)?;
+                Progress::Continue
             },
+        };
+
+        if progress == Progress::Abort {
+            trace!("Module vetoed deactivation, aborting chain.");
+            // Not `self.abort()`, as that would cancel the previous timer right away. Both are
+            // left active until the user is back instead.
+            self.aborted = true;
+            self.vetoed = self
+                .next_index
+                .checked_sub(1)
+                .filter(|&previous| previous != index)
+                .into_iter()
+                .chain(Some(index))
+                .collect();
+            return Ok(Progress::Abort);
         }
 
-        Ok(Progress::Continue)
+        let previous_info = self
+            .next_index
+            .checked_sub(1)
+            .map(|previous| self.timer_info(previous, Some(index)));
+        if let (Some(previous), Some(info)) = (self.previous(), previous_info) {
+            previous.prepare(info);
+            previous.deactivate()?;
+            if let Some(previous) = self.next_index.checked_sub(1).filter(|&i| i != index) {
+                self.set_state(previous, TimerState::Deactivated);
+            }
+        }
+
+        match progress {
+            Progress::Stop => Ok(Progress::Stop),
+            Progress::Halt => {
+                trace!("Module requested halt of chain after this timer.");
+                self.halted = true;
+                Ok(Progress::Continue)
+            },
+            _ => Ok(Progress::Continue),
+        }
     }
 
     /// Polls the scheduler for any activated timers. On success, returns the max amount of time a
     /// program can sleep for. Only fatal errors cause this function to return, and at that point,
     /// the state of xidlehook is undefined so it should not be used.
+    ///
+    /// This is `step` for when you don't care what happened.
     pub fn poll(&mut self, absolute_time: Duration) -> Result<Option<Duration>> {
+        self.step(absolute_time).map(Action::sleep)
+    }
+
+    /// Advances the scheduler by one step, given the current absolute idle time. This evaluates
+    /// the timers and modules just once, activating a timer if it's due, and returns what it
+    /// did. This lets you drive xidlehook from your own loop, see `main_sync` for an example.
+    /// Just like `poll`, continued usage after an error is discouraged.
+    ///
+    /// If there are no timers, or they are all disabled, there's nothing to do: This returns a
+    /// very long sleep (unless capped with `with_max_sleep`), so that a caller which can add or
+    /// enable timers some other way (such as over a socket) can wake up the loop when it does.
+    ///
+    /// If more time has passed since the last poll (according to the monotonic clock) than the
+    /// idle time has increased, the user must have been active in between, and the chain is
+    /// reset. This happens when the process was paused (`SIGSTOP`) for a while, as the idle time
+    /// may then have both decreased and increased again without xidlehook noticing.
+    ///
+    /// Every step is traced to the `DECISION_TARGET` log target.
+    ///
+    /// # Errors
+    ///
+    /// If a module or timer fails, just like `poll`
+    pub fn step(&mut self, absolute_time: Duration) -> Result<Action> {
+        let mut action = self.decide(absolute_time)?;
+        if let Some(max) = self.max_sleep {
+            action = action.cap_sleep(max);
+        }
+        if log_enabled!(target: DECISION_TARGET, Level::Trace) {
+            let (event, timer) = match action {
+                Action::Sleep(_) => ("sleep", None),
+                Action::Activated { index, .. } => ("activated", Some(index)),
+                Action::Aborted { index, .. } => ("aborted", Some(index)),
+                Action::Halted { index, .. } => ("halted", Some(index)),
+                Action::Stop => ("stop", None),
+            };
+            trace!(
+                target: DECISION_TARGET,
+                idle_ms = absolute_time.as_millis(),
+                action = event,
+                timer = timer,
+                sleep_ms = action.sleep().map(|sleep| sleep.as_millis()),
+                next_timer = self.next_index,
+                aborted = self.aborted,
+                halted = self.halted;
+                "Idle for {absolute_time:?}, decided to {event}"
+            );
+        }
+        Ok(action)
+    }
+
+    /// Pass on the timers' warnings, and reset the chain if the idle
+    /// time shows the user was active since the last poll
+    fn observe(&mut self, absolute_time: Duration) -> Result<()> {
+        let warnings: Vec<Error> = self.timers.iter_mut().flat_map(T::take_warnings).collect();
+        for err in warnings {
+            self.module.warning(&err)?;
+        }
+
+        let now = self.clock.now();
+        let elapsed = self
+            .last_poll
+            .replace(now)
+            // An injected clock might go backwards, be defensive
+            .map(|last_poll| now.saturating_duration_since(last_poll));
+
         if absolute_time < self.previous_idle_time {
             // If the idle time has decreased, the only reasonable explanation is that the user
             // briefly wasn't idle.
             self.reset()?;
+        } else if let Some(elapsed) = elapsed {
+            let increase = absolute_time
+                .checked_sub(self.previous_idle_time)
+                .unwrap_or_default();
+            if elapsed
+                .checked_sub(increase)
+                .is_some_and(|unaccounted| unaccounted > CLOCK_TOLERANCE)
+            {
+                trace!(
+                    "{elapsed:?} passed since last poll, but idle only increased by {increase:?}"
+                );
+                self.reset()?;
+            }
         }
 
         self.previous_idle_time = absolute_time;
+        if self.pause == Pause::Resumed {
+            // Treated as active up until now, see `set_paused`
+            self.pause = Pause::Running;
+            self.base_idle_time = absolute_time;
+        }
+        Ok(())
+    }
+    /// The logic of `step`, without the tracing
+    fn decide(&mut self, absolute_time: Duration) -> Result<Action> {
+        self.observe(absolute_time)?;
 
         let first_index = match self.next_enabled(0) {
             Some(index) => index,
@@ -278,7 +949,7 @@ where
             // https://github.com/jD91mZM2/xidlehook/issues/35#issuecomment-579495447
             //
             // This isn't `u64::max_value()` because loads of things overflow and panic.
-            None => return Ok(Some(Duration::from_secs(u32::max_value().into()))),
+            None => return Ok(Action::Sleep(Duration::from_secs(u32::MAX.into()))),
         };
 
         let mut max_sleep = self.timers[first_index]
@@ -289,12 +960,16 @@ where
             max_sleep
         );
 
-        if self.aborted {
-            // This chain was aborted, so don't pursue it
-            return Ok(Some(max_sleep));
+        if self.aborted || self.halted || self.is_paused() {
+            // This chain was aborted or halted, or is paused, so don't pursue it
+            return Ok(Action::Sleep(max_sleep));
         }
 
-        let relative_time = absolute_time - self.base_idle_time;
+        // The base idle time can be larger than the current idle time if a timer was triggered
+        // manually with a newer idle time than was last polled
+        let relative_time = absolute_time
+            .checked_sub(self.base_idle_time)
+            .unwrap_or_default();
         trace!("Relative time: {:?}", relative_time);
 
         if self.next_index == 0 {
@@ -308,6 +983,8 @@ where
             self.next_index = first_index;
         }
 
+        let mut activated = None;
+
         // When there's a next timer available, get the time until that activates
         if let Some(next) = self.timers.get_mut(self.next_index) {
             if let Some(remaining) = next.time_left(relative_time)? {
@@ -316,13 +993,32 @@ where
             } else {
                 // Oh! It's already been activated - let's trigger it.
 
-                match self.trigger(self.next_index, absolute_time, false)? {
-                    Progress::Continue => (),
-                    Progress::Abort => return Ok(Some(max_sleep)),
-                    Progress::Stop => return Ok(None),
+                let index = self.next_index;
+                match self.trigger(index, absolute_time, false)? {
+                    // `trigger` never returns `Force`, it's already been applied
+                    Progress::Continue | Progress::Force => activated = Some(index),
+                    Progress::Abort => {
+                        return Ok(Action::Aborted {
+                            index,
+                            sleep: max_sleep,
+                        })
+                    },
+                    Progress::Halt => {
+                        return Ok(Action::Halted {
+                            index,
+                            sleep: max_sleep,
+                        })
+                    },
+                    Progress::Stop => return Ok(Action::Stop),
                 }
                 // From now on, `relative_time` is invalid. Don't use it.
 
+                // Some timers measure time differently and restart on activation (see
+                // `timers::SessionTimer`), so the first timer's time may have changed
+                max_sleep = self.timers[first_index]
+                    .time_left(Duration::default())?
+                    .unwrap_or_default();
+
                 // Thanks, clippy, but get_mut will fail far before this is even close to
                 // overflowing
                 #[allow(clippy::integer_arithmetic)]
@@ -332,8 +1028,9 @@ where
                         .unwrap_or_else(|| self.timers.len());
                 }
 
+                // `next_enabled` already skipped the disabled timers. Asking again could give a
+                // different answer, as it may depend on the outside world, see `Timer::disabled`.
                 if let Some(next) = self.timers.get_mut(self.next_index) {
-                    assert!(!next.disabled());
                     if let Some(remaining) = next.time_left(Duration::default())? {
                         trace!(
                             "Taking next-next timer into account. Remaining: {:?}",
@@ -357,7 +1054,13 @@ where
             }
         }
 
-        Ok(Some(max_sleep))
+        Ok(match activated {
+            Some(index) => Action::Activated {
+                index,
+                sleep: max_sleep,
+            },
+            None => Action::Sleep(max_sleep),
+        })
     }
 
     /// Runs a standard poll-sleep-repeat loop.
@@ -412,9 +1115,8 @@ where
     {
         loop {
             let idle = xcb.get_idle()?;
-            let delay = match self.poll(idle)? {
-                Some(delay) => delay,
-                None => break,
+            let Some(delay) = self.step(idle)?.sleep() else {
+                break;
             };
 
             trace!("Sleeping for {:?}", delay);
@@ -423,10 +1125,7 @@ where
             unsafe {
                 libc::nanosleep(
                     &libc::timespec {
-                        tv_sec: delay
-                            .as_secs()
-                            .try_into()
-                            .expect("woah that's one large number"),
+                        tv_sec: delay.as_secs().try_into().unwrap_or(libc::time_t::MAX),
                         tv_nsec: delay.subsec_nanos().into(),
                     },
                     ptr::null_mut(),
@@ -441,23 +1140,49 @@ where
         Ok(())
     }
 
-    /// Runs a standard poll-sleep-repeat loop... asynchronously.
+    /// Runs a standard poll-sleep-repeat loop... asynchronously, on the async-std runtime. See
+    /// `main_async_with` for other runtimes.
     #[cfg(feature = "async-std")]
     pub async fn main_async(&mut self, xcb: &self::modules::Xcb) -> Result<()> {
+        self.main_async_with(xcb, async_std::task::sleep).await
+    }
+
+    /// Like `main_async`, but on any runtime: `sleep` is the runtime's way of sleeping, such as
+    /// `tokio::time::sleep` for tokio. This is the only thing xidlehook needs from a runtime, so
+    /// it doesn't depend on one.
+    ///
+    /// # Errors
+    ///
+    /// If a module or timer fails, or the idle time can't be read
+    pub async fn main_async_with<S, F>(
+        &mut self,
+        xcb: &self::modules::Xcb,
+        mut sleep: S,
+    ) -> Result<()>
+    where
+        S: FnMut(Duration) -> F,
+        F: Future<Output = ()>,
+    {
         loop {
             let idle = xcb.get_idle()?;
-            let delay = match self.poll(idle)? {
-                Some(delay) => delay,
-                None => break,
+            let Some(delay) = self.step(idle)?.sleep() else {
+                break;
             };
 
             trace!("Sleeping for {:?}", delay);
-            async_std::task::sleep(delay).await;
+            sleep(delay).await;
         }
         Ok(())
     }
 }
 
+/// Returns the timer's name in quotes if it has one, otherwise its index
+fn display_name<T: Timer>(index: usize, timer: &T) -> String {
+    timer
+        .name()
+        .map_or_else(|| index.to_string(), |name| format!("'{name}'"))
+}
+
 impl<T, M> fmt::Debug for Xidlehook<T, M>
 where
     T: Timer,
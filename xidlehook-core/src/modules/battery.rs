@@ -0,0 +1,198 @@
+//! Refuses to let xidlehook run the next timer depending on the power
+//! state in `/sys/class/power_supply`, such as not suspending while
+//! plugged in, or not locking while the battery is low. What counts is
+//! up to a predicate, so the same module covers both. Whenever the
+//! decision flips, such as when the charger is plugged in, a callback
+//! can be told, so power widgets can show it. This is used to
+//! implement `--not-when-on-ac` and `--not-when-battery-below` in the
+//! xidlehook example application.
+
+use crate::{Module, Progress, Result, TimerInfo};
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+/// How long the power state is reused before reading it again. It
+/// changes slowly, and `pre_timer` may be called several times in quick
+/// succession.
+const CACHE_DURATION: Duration = Duration::from_secs(10);
+
+/// What the power supplies report
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Power {
+    /// Whether the system runs on mains power. Without any battery,
+    /// this is always the case.
+    pub on_ac: bool,
+    /// How charged the batteries are altogether, in percent, unless
+    /// there are none
+    pub battery: Option<f64>,
+}
+
+/// Read a sysfs attribute, without the trailing newline
+fn attribute(dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(dir.join(name))
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+/// Read a numeric sysfs attribute
+fn number(dir: &Path, name: &str) -> Option<f64> {
+    attribute(dir, name)?.parse().ok()
+}
+
+/// How much charge this battery has and can hold, in whatever unit it
+/// reports. The percentage is only a last resort, as it can't be summed
+/// up across batteries of different sizes.
+fn charge(dir: &Path) -> Option<(f64, f64)> {
+    let pair = |now, full| Some((number(dir, now)?, number(dir, full)?));
+    pair("energy_now", "energy_full")
+        .or_else(|| pair("charge_now", "charge_full"))
+        .or_else(|| Some((number(dir, "capacity")?, 100.0)))
+}
+
+/// See the module-level documentation
+pub struct NotWhenBattery {
+    predicate: Box<dyn FnMut(Power) -> bool>,
+    path: PathBuf,
+    cache: Option<(Instant, Power)>,
+    on_change: Option<Box<dyn FnMut(bool, Power)>>,
+    /// Whether the last decision was to abort, if there was any
+    aborting: Option<bool>,
+}
+impl NotWhenBattery {
+    /// Abort the chain whenever `predicate` returns true for the
+    /// current power state
+    pub fn new<F>(predicate: F) -> Self
+    where
+        F: FnMut(Power) -> bool + 'static,
+    {
+        Self {
+            predicate: Box::new(predicate),
+            path: PathBuf::from(POWER_SUPPLY),
+            cache: None,
+            on_change: None,
+            aborting: None,
+        }
+    }
+    /// Abort the chain while on mains power
+    pub fn on_ac() -> Self {
+        Self::new(|power| power.on_ac)
+    }
+    /// Abort the chain while the battery is charged less than `percent`
+    pub fn battery_below(percent: f64) -> Self {
+        Self::new(move |power| power.battery.is_some_and(|charge| charge < percent))
+    }
+    /// Abort the chain while the battery is charged more than `percent`
+    pub fn battery_above(percent: f64) -> Self {
+        Self::new(move |power| power.battery.is_some_and(|charge| charge > percent))
+    }
+
+    /// Look for power supplies in this directory instead of
+    /// `/sys/class/power_supply`. It must be laid out the same way.
+    #[must_use]
+    pub fn with_path(mut self, path: PathBuf) -> Self {
+        self.path = path;
+        self
+    }
+
+    /// Call this whenever the decision flips, with whether the chain is
+    /// aborted now and the power state that made it so. It's also
+    /// called for the first decision, so the state is known from the
+    /// start.
+    #[must_use]
+    pub fn on_change<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(bool, Power) + 'static,
+    {
+        self.on_change = Some(Box::new(callback));
+        self
+    }
+
+    /// The current power state
+    ///
+    /// # Errors
+    ///
+    /// If the power supplies can't be read
+    pub fn power(&mut self) -> Result<Power> {
+        if let Some((time, power)) = self.cache {
+            if time.elapsed() < CACHE_DURATION {
+                return Ok(power);
+            }
+        }
+
+        let mut mains = false;
+        let mut batteries = false;
+        let (mut now, mut full) = (0.0, 0.0);
+        for entry in fs::read_dir(&self.path)? {
+            let dir = entry?.path();
+            match attribute(&dir, "type").as_deref() {
+                Some("Mains" | "USB") => {
+                    mains = mains || attribute(&dir, "online").as_deref() == Some("1");
+                },
+                // Batteries of peripherals, such as a wireless mouse, don't power the system
+                Some("Battery") if attribute(&dir, "scope").as_deref() != Some("Device") => {
+                    batteries = true;
+                    if let Some((charge_now, charge_full)) = charge(&dir) {
+                        now += charge_now;
+                        full += charge_full;
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        let power = Power {
+            on_ac: mains || !batteries,
+            battery: if full > 0.0 {
+                Some(now / full * 100.0)
+            } else {
+                None
+            },
+        };
+        self.cache = Some((Instant::now(), power));
+        Ok(power)
+    }
+}
+impl fmt::Debug for NotWhenBattery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NotWhenBattery")
+    }
+}
+impl Module for NotWhenBattery {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        let power = self.power()?;
+        let aborting = (self.predicate)(power);
+        if self.aborting != Some(aborting) {
+            self.aborting = Some(aborting);
+            if let Some(ref mut on_change) = self.on_change {
+                on_change(aborting, power);
+            }
+        }
+        if aborting {
+            debug!("Aborting because of the power state: {power:?}");
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        let power = self.power()?;
+        if (self.predicate)(power) {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.cache = None;
+        Ok(())
+    }
+}
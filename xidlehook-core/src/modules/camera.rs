@@ -0,0 +1,126 @@
+//! Detects whether any process has a video device (such as a webcam)
+//! open, by scanning the file descriptors of all processes in
+//! `/proc`. If so, it refuses to let xidlehook run the next timer,
+//! since you're most likely in a video call. This is used to
+//! implement `--not-when-camera` in the xidlehook example
+//! application.
+
+use crate::{Module, Progress, Result, TimerInfo};
+
+use std::{
+    fmt, fs, io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+
+const VIDEO_DEVICE_PREFIX: &str = "/dev/video";
+
+/// How long a scan result is reused before scanning again. Scanning
+/// every file descriptor of every process isn't free, and `pre_timer`
+/// may be called several times in quick succession.
+const CACHE_DURATION: Duration = Duration::from_secs(1);
+
+/// See the module-level documentation
+#[derive(Clone, Copy, Default)]
+pub struct NotWhenCamera {
+    cache: Option<(Instant, bool)>,
+    warned: bool,
+}
+impl NotWhenCamera {
+    /// Create a new instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan all processes for an open video device
+    ///
+    /// # Errors
+    ///
+    /// If the list of processes can't be read
+    pub fn camera_in_use(&mut self) -> Result<bool> {
+        if let Some((time, in_use)) = self.cache {
+            if time.elapsed() < CACHE_DURATION {
+                return Ok(in_use);
+            }
+        }
+
+        let mut in_use = false;
+        for process in fs::read_dir("/proc")? {
+            let process = process?;
+            let is_pid = process
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.bytes().all(|b| b.is_ascii_digit()));
+            if !is_pid {
+                continue;
+            }
+
+            match Self::has_video_fd(&process.path().join("fd")) {
+                Ok(true) => {
+                    debug!(
+                        "Process {} is using a video device",
+                        process.file_name().to_string_lossy()
+                    );
+                    in_use = true;
+                    break;
+                },
+                Err(ref err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                    // Processes owned by other users can't be inspected. Let the user know once,
+                    // as a camera used by another user won't be detected.
+                    if !self.warned {
+                        warn!(
+                            "camera: can't inspect some processes ({err}), their video devices will \
+                             be ignored"
+                        );
+                        self.warned = true;
+                    }
+                },
+                // Either nothing was found, or the process exited while we were looking at it
+                Ok(false) | Err(_) => (),
+            }
+        }
+
+        self.cache = Some((Instant::now(), in_use));
+        Ok(in_use)
+    }
+
+    fn has_video_fd(dir: &Path) -> io::Result<bool> {
+        for fd in fs::read_dir(dir)? {
+            let Ok(target) = fs::read_link(fd?.path()) else {
+                continue;
+            };
+            if target.to_string_lossy().starts_with(VIDEO_DEVICE_PREFIX) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+impl fmt::Debug for NotWhenCamera {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NotWhenCamera")
+    }
+}
+impl Module for NotWhenCamera {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.camera_in_use()? {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.camera_in_use()? {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.cache = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+//! Leaves the decision of whether to run the next timer to an
+//! external command. Its exit code decides: 0 means continue, 1 means
+//! abort the chain, 2 means stop xidlehook, and 3 means halt the
+//! chain (see `Progress::Halt`). Any other exit code,
+//! or the command not finishing in time, is reported as a warning and
+//! doesn't stop the timer. This is used to implement `--exec-module`
+//! in the xidlehook example application.
+
+use crate::{Module, Progress, Result, TimerInfo};
+
+use std::{
+    convert::TryInto,
+    fmt,
+    io::{self, Read},
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::{self, Pid},
+};
+
+/// How long a decision is reused before running the command again, as
+/// `pre_timer` may be called several times in quick succession
+const CACHE_DURATION: Duration = Duration::from_secs(1);
+
+/// Runs the shell with the command and prints its exit code. The
+/// xidlehook application reaps every child process as soon as it
+/// exits, so the exit code can't be waited for directly. Instead, this
+/// shell waits for the command, which isn't a child of xidlehook
+/// itself.
+const WRAPPER: &str = "\"$@\" >&2; echo $?";
+
+/// See the module-level documentation
+pub struct ExecModule {
+    command: String,
+    shell: Vec<String>,
+    timeout: Duration,
+    cache: Option<(Instant, Progress)>,
+}
+impl ExecModule {
+    /// Decide with this command, which is passed through `/bin/sh -c`
+    /// unless another shell is set with `with_shell`. It's killed if it
+    /// runs for longer than `timeout`.
+    pub fn new(command: String, timeout: Duration) -> Self {
+        Self {
+            command,
+            shell: vec![String::from("/bin/sh")],
+            timeout,
+            cache: None,
+        }
+    }
+
+    /// Pass the command through this shell instead, which is the
+    /// program and any arguments to run before `-c`, such as
+    /// `["/usr/bin/env", "bash"]`
+    #[must_use]
+    pub fn with_shell(mut self, shell: Vec<String>) -> Self {
+        self.shell = shell;
+        self
+    }
+
+    /// Run the command and return its decision
+    ///
+    /// # Errors
+    ///
+    /// If the command can't be run, takes longer than the timeout, or
+    /// exits with a code that isn't a decision
+    pub fn decide(&mut self) -> Result<Progress> {
+        if let Some((time, progress)) = self.cache {
+            if time.elapsed() < CACHE_DURATION {
+                return Ok(progress);
+            }
+        }
+
+        let mut child = unsafe {
+            Command::new("/bin/sh")
+                .args(["-c", WRAPPER, "xidlehook-exec"])
+                .args(&self.shell)
+                .args(["-c", &self.command])
+                .stdout(Stdio::piped())
+                // In its own process group, so it can be killed along with the command
+                .pre_exec(|| {
+                    unistd::setpgid(Pid::from_raw(0), Pid::from_raw(0))
+                        .map_err(|_| io::Error::last_os_error())
+                })
+                .spawn()?
+        };
+        let pgid = Pid::from_raw(child.id().try_into()?);
+        let mut stdout = child.stdout.take().ok_or("exec: no stdout")?;
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut output = String::new();
+            let _ = tx.send(stdout.read_to_string(&mut output).map(|_| output));
+        });
+
+        let output = if let Ok(output) = rx.recv_timeout(self.timeout) {
+            output?
+        } else {
+            let _ = signal::killpg(pgid, Signal::SIGKILL);
+            return Err(format!(
+                "exec: {:?} took longer than {:?}",
+                self.command, self.timeout
+            )
+            .into());
+        };
+        let progress = match output.trim() {
+            "0" => Progress::Continue,
+            "1" => Progress::Abort,
+            "2" => Progress::Stop,
+            "3" => Progress::Halt,
+            code => {
+                return Err(format!("exec: {:?} exited with code {}", self.command, code).into())
+            },
+        };
+        debug!("{:?} decided to {:?}", self.command, progress);
+
+        self.cache = Some((Instant::now(), progress));
+        Ok(progress)
+    }
+}
+impl fmt::Debug for ExecModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ExecModule")
+    }
+}
+impl Module for ExecModule {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        self.decide()
+    }
+
+    // Running the command is a side effect, so this reports its last decision instead
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        Ok(self
+            .cache
+            .map_or(Progress::Continue, |(_, progress)| progress))
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.cache = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Asks systemd-logind for the inhibitor locks that are held, and
+//! refuses to let xidlehook run the next timer while any of the chosen
+//! types is blocked. Video players, presentation tools and browsers
+//! take an "idle" lock to keep the screen from locking. This is used to
+//! implement `--not-when-inhibited` in the xidlehook example
+//! application.
+
+use crate::{Module, Progress, Result, TimerInfo};
+
+use std::{
+    fmt, process,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use zbus::Connection;
+
+const LOGIND: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_MANAGER: &str = "org.freedesktop.login1.Manager";
+
+/// How long a result is reused before asking logind again, as
+/// `pre_timer` may be called several times in quick succession
+const CACHE_DURATION: Duration = Duration::from_secs(1);
+
+/// An inhibitor lock as listed by logind: What it inhibits, separated
+/// by colons, who took it, why, the mode, and its user and process ID
+type Inhibitor = (String, String, String, String, u32, u32);
+
+/// See the module-level documentation
+pub struct NotWhenInhibited {
+    conn: Connection,
+    types: Vec<String>,
+    cache: Option<(Instant, bool)>,
+}
+impl NotWhenInhibited {
+    /// Connect to the system bus. Only locks of these types count,
+    /// such as "idle", or "sleep" as well.
+    ///
+    /// # Errors
+    ///
+    /// If the system bus can't be connected to
+    pub fn new(types: Vec<String>) -> Result<Self> {
+        Ok(Self {
+            conn: Connection::new_system()?,
+            types,
+            cache: None,
+        })
+    }
+
+    /// Whether this lock blocks any of the chosen types. Delay locks
+    /// only hold off suspending for a moment, such as the one
+    /// xidlehook takes itself for `--lock-on-suspend`, so they don't
+    /// count.
+    fn blocks(&self, inhibitor: &Inhibitor) -> bool {
+        let (what, _, _, mode, _, pid) = inhibitor;
+        mode == "block"
+            && *pid != process::id()
+            && what
+                .split(':')
+                .any(|kind| self.types.iter().any(|t| t == kind))
+    }
+
+    /// Ask logind whether any lock of the chosen types is held
+    ///
+    /// # Errors
+    ///
+    /// If logind can't be asked
+    pub fn inhibited(&mut self) -> Result<bool> {
+        if let Some((time, inhibited)) = self.cache {
+            if time.elapsed() < CACHE_DURATION {
+                return Ok(inhibited);
+            }
+        }
+
+        let inhibitors: Vec<Inhibitor> = self
+            .conn
+            .call_method(
+                Some(LOGIND),
+                LOGIND_PATH,
+                Some(LOGIND_MANAGER),
+                "ListInhibitors",
+                &(),
+            )?
+            .body()?;
+        let blocking = inhibitors.iter().find(|inhibitor| self.blocks(inhibitor));
+        if let Some((what, who, why, ..)) = blocking {
+            debug!("{who} is inhibiting {what}: {why}");
+        }
+
+        let inhibited = blocking.is_some();
+        self.cache = Some((Instant::now(), inhibited));
+        Ok(inhibited)
+    }
+}
+impl fmt::Debug for NotWhenInhibited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NotWhenInhibited {{ types: {:?} }}", self.types)
+    }
+}
+impl Module for NotWhenInhibited {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.inhibited()? {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.inhibited()? {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.cache = None;
+        Ok(())
+    }
+}
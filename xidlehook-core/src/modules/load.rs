@@ -0,0 +1,103 @@
+//! Refuses to let xidlehook run the next timer while the system is
+//! busy, going by the 1-minute load average in `/proc/loadavg`. Long
+//! builds and renders keep the machine working without any input, and
+//! shouldn't be interrupted by a lock screen. This is used to
+//! implement `--not-when-load-above` in the xidlehook example
+//! application.
+
+use crate::{Module, Progress, Result, TimerInfo};
+
+use std::{
+    fmt, fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+const LOADAVG: &str = "/proc/loadavg";
+
+/// How long a load average is reused before reading it again, as
+/// `pre_timer` may be called several times in quick succession
+const CACHE_DURATION: Duration = Duration::from_secs(1);
+
+/// See the module-level documentation
+pub struct NotWhenLoadAbove {
+    threshold: f64,
+    path: PathBuf,
+    cache: Option<(Instant, f64)>,
+}
+impl NotWhenLoadAbove {
+    /// Abort the chain while the 1-minute load average is above
+    /// `threshold`
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            path: PathBuf::from(LOADAVG),
+            cache: None,
+        }
+    }
+
+    /// Read the load average from this file instead of
+    /// `/proc/loadavg`. It must be in the same format.
+    #[must_use]
+    pub fn with_path(mut self, path: PathBuf) -> Self {
+        self.path = path;
+        self
+    }
+
+    /// The current 1-minute load average
+    ///
+    /// # Errors
+    ///
+    /// If the load average can't be read or parsed
+    pub fn load(&mut self) -> Result<f64> {
+        if let Some((time, load)) = self.cache {
+            if time.elapsed() < CACHE_DURATION {
+                return Ok(load);
+            }
+        }
+
+        let contents = fs::read_to_string(&self.path)?;
+        let load = contents
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| format!("{} is empty", self.path.display()))?
+            .parse()
+            .map_err(|err| format!("invalid load average in {}: {}", self.path.display(), err))?;
+
+        self.cache = Some((Instant::now(), load));
+        Ok(load)
+    }
+}
+impl fmt::Debug for NotWhenLoadAbove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NotWhenLoadAbove({})", self.threshold)
+    }
+}
+impl Module for NotWhenLoadAbove {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        let load = self.load()?;
+        if load > self.threshold {
+            debug!(
+                "The load average {} is above {}, aborting",
+                load, self.threshold
+            );
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.load()? > self.threshold {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.cache = None;
+        Ok(())
+    }
+}
@@ -7,15 +7,56 @@ use log::warn;
 
 /// A decision each module has to take before a timer is executed:
 /// Should it be?
+///
+/// `Halt` and `Force` were added after version 0.1.1, which breaks
+/// exhaustive matches written against it. More decisions may be added
+/// again, so a match needs a wildcard arm.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Progress {
     /// Continue the program, no action taken.
     Continue,
-    /// Abort this chain, don't pursue it any longer.
+    /// Halt this chain without cancelling anything: No more timers
+    /// run until the user is active again, but unlike `Abort`, the
+    /// timer that last ran is left active. Once the user is active,
+    /// it's aborted as usual, and the chain starts over. Use this to
+    /// end the chain early, such as after locking but before
+    /// suspending.
+    Halt,
+    /// Abort this chain, don't pursue it any longer. The timer that
+    /// last ran is aborted right away.
     Abort,
+    /// Run the timer even if another module wants to abort the chain,
+    /// such as for a "lock now" request. Only `Stop` takes precedence
+    /// over this. As `combine` ranks it above `Abort`, no module can
+    /// hold back a forced timer, not even an inhibitor such as
+    /// `NotWhenAudio` or `NotWhenCamera`.
+    Force,
     /// Stop the program completely. Use this sparingly.
     Stop,
 }
+impl Progress {
+    /// Combine the decisions of two modules: `Stop` wins over
+    /// everything, then `Force`, then `Abort`, then `Halt`, and
+    /// `Continue` only if both continue.
+    #[must_use]
+    pub fn combine(self, other: Self) -> Self {
+        fn rank(progress: Progress) -> u8 {
+            match progress {
+                Progress::Continue => 0,
+                Progress::Halt => 1,
+                Progress::Abort => 2,
+                Progress::Force => 3,
+                Progress::Stop => 4,
+            }
+        }
+        if rank(other) > rank(self) {
+            other
+        } else {
+            self
+        }
+    }
+}
 
 /// A generic module that controls whether timers should execute or
 /// not (outside of the normal timer)
@@ -25,7 +66,35 @@ pub trait Module {
         Ok(Progress::Continue)
     }
 
-    /// Decides what happens after a timer has executed
+    /// Tells what `pre_timer` would decide right now, such as for a
+    /// countdown, without running the timer. Unlike `pre_timer`, this
+    /// must not have any side effects such as running commands or
+    /// changing the module's state, as it may be called any number of
+    /// times. A module that can't tell without side effects may report
+    /// its last decision instead. By default, it allows the timer.
+    ///
+    /// # Errors
+    ///
+    /// If the module can't tell, just like `pre_timer`
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        Ok(Progress::Continue)
+    }
+
+    /// Decides what happens after a timer has executed, and whether
+    /// the previous timer in the chain (if any) should be deactivated
+    /// now that this one took over.
+    ///
+    /// - `Continue` deactivates the previous timer as usual.
+    /// - `Halt` deactivates the previous timer as usual, but no more
+    ///   timers run until the user is active again.
+    /// - `Abort` vetoes the deactivation: The previous timer's
+    ///   deactivation isn't run, so it stays in effect along with this
+    ///   one. The rest of the chain is skipped. Once the user is active
+    ///   again, both are aborted, this one first, and the chain starts
+    ///   over.
+    /// - `Force` deactivates the previous timer, even if another module
+    ///   vetoed it.
+    /// - `Stop` deactivates the previous timer, then stops xidlehook.
     fn post_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
         Ok(Progress::Continue)
     }
@@ -55,6 +124,9 @@ impl Module for Box<dyn Module> {
     fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
         (&mut **self).pre_timer(timer)
     }
+    fn would_allow(&mut self, timer: TimerInfo) -> Result<Progress> {
+        (**self).would_allow(timer)
+    }
     fn post_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
         (&mut **self).post_timer(timer)
     }
@@ -68,6 +140,10 @@ impl Module for Box<dyn Module> {
 
 /// Combine two timers using the type-system. Can be recursed for a
 /// fixed-size amount of timers. Similar to iterator.chain.
+///
+/// The decisions are combined with `Progress::combine`. As a later
+/// module may force a timer that an earlier one aborted, both are
+/// asked unless the first one stops.
 impl<A, B> Module for (A, B)
 where
     A: Module,
@@ -75,17 +151,24 @@ where
 {
     fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
         let status = self.0.pre_timer(timer)?;
-        if status != Progress::Continue {
+        if status == Progress::Stop {
             return Ok(status);
         }
-        self.1.pre_timer(timer)
+        Ok(status.combine(self.1.pre_timer(timer)?))
+    }
+    fn would_allow(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let status = self.0.would_allow(timer)?;
+        if status == Progress::Stop {
+            return Ok(status);
+        }
+        Ok(status.combine(self.1.would_allow(timer)?))
     }
     fn post_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
         let status = self.0.post_timer(timer)?;
-        if status != Progress::Continue {
+        if status == Progress::Stop {
             return Ok(status);
         }
-        self.1.post_timer(timer)
+        Ok(status.combine(self.1.post_timer(timer)?))
     }
     fn warning(&mut self, error: &Error) -> Result<()> {
         self.0.warning(error)?;
@@ -97,25 +180,37 @@ where
     }
 }
 
-/// Combine multiple modules with a dynamic size
+/// Combine multiple modules with a dynamic size, just like `(A, B)`
 impl<M: Module> Module for Vec<M> {
     fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let mut status = Progress::Continue;
         for module in self {
-            let status = module.pre_timer(timer)?;
-            if status != Progress::Continue {
-                return Ok(status);
+            status = status.combine(module.pre_timer(timer)?);
+            if status == Progress::Stop {
+                break;
             }
         }
-        Ok(Progress::Continue)
+        Ok(status)
+    }
+    fn would_allow(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let mut status = Progress::Continue;
+        for module in self {
+            status = status.combine(module.would_allow(timer)?);
+            if status == Progress::Stop {
+                break;
+            }
+        }
+        Ok(status)
     }
     fn post_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let mut status = Progress::Continue;
         for module in self {
-            let status = module.post_timer(timer)?;
-            if status != Progress::Continue {
-                return Ok(status);
+            status = status.combine(module.post_timer(timer)?);
+            if status == Progress::Stop {
+                break;
             }
         }
-        Ok(Progress::Continue)
+        Ok(status)
     }
     fn warning(&mut self, error: &Error) -> Result<()> {
         for module in self {
@@ -131,11 +226,58 @@ impl<M: Module> Module for Vec<M> {
     }
 }
 
+pub mod battery;
+#[cfg(feature = "camera")]
+pub mod camera;
+pub mod exec;
+#[cfg(feature = "logind")]
+pub mod inhibit;
+pub mod load;
+#[cfg(feature = "mpris")]
+pub mod mpris;
+#[cfg(feature = "notify")]
+pub mod notify;
+pub mod on_resume;
+pub mod on_stop;
 #[cfg(feature = "pulse")]
 pub mod pulse;
+pub mod quiet;
+#[cfg(feature = "screencast")]
+pub mod screencast;
+pub mod startup_grace;
 pub mod stop_at;
+pub mod toggle;
+pub mod warning_summary;
+#[cfg(feature = "wayland")]
+pub mod wayland;
 pub mod xcb;
+pub mod xinput;
 
+#[cfg(feature = "camera")]
+pub use self::camera::NotWhenCamera;
+#[cfg(feature = "logind")]
+pub use self::inhibit::NotWhenInhibited;
+#[cfg(feature = "mpris")]
+pub use self::mpris::NotWhenPlaying;
+#[cfg(feature = "notify")]
+pub use self::notify::Notify;
 #[cfg(feature = "pulse")]
 pub use self::pulse::NotWhenAudio;
-pub use self::{stop_at::StopAt, xcb::Xcb};
+#[cfg(feature = "screencast")]
+pub use self::screencast::NotWhenScreencast;
+#[cfg(feature = "wayland")]
+pub use self::wayland::WaylandIdle;
+pub use self::{
+    battery::NotWhenBattery,
+    exec::ExecModule,
+    load::NotWhenLoadAbove,
+    on_resume::OnResume,
+    on_stop::OnStop,
+    quiet::Quiet,
+    startup_grace::StartupGrace,
+    stop_at::{StopAfter, StopAt},
+    toggle::Toggle,
+    warning_summary::WarningSummary,
+    xcb::{IdleSource, WindowState, Xcb},
+    xinput::{InputClasses, XInputIdle},
+};
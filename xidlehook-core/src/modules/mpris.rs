@@ -0,0 +1,106 @@
+//! Asks every media player on the session bus for its MPRIS playback
+//! status, and refuses to let xidlehook run the next timer while any
+//! of them is playing. Unlike `NotWhenAudio`, this goes by what the
+//! player says rather than by audio output, so a muted video still
+//! counts as playing. This is used to implement `--not-when-playing`
+//! in the xidlehook example application.
+
+use crate::{Module, Progress, Result, TimerInfo};
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use zbus::{fdo::DBusProxy, Connection, Proxy};
+
+/// Every MPRIS player owns a bus name with this prefix
+const PLAYER_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const PLAYER_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// How long a result is reused before asking the players again, as
+/// `pre_timer` may be called several times in quick succession
+const CACHE_DURATION: Duration = Duration::from_secs(1);
+
+/// See the module-level documentation
+pub struct NotWhenPlaying {
+    conn: Connection,
+    cache: Option<(Instant, bool)>,
+}
+impl NotWhenPlaying {
+    /// Connect to the session bus
+    ///
+    /// # Errors
+    ///
+    /// If the session bus can't be connected to
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            conn: Connection::new_session()?,
+            cache: None,
+        })
+    }
+
+    /// Ask every player whether it's playing. Players come and go, so
+    /// they're looked up again every time.
+    ///
+    /// # Errors
+    ///
+    /// If the players can't be listed
+    pub fn playing(&mut self) -> Result<bool> {
+        if let Some((time, playing)) = self.cache {
+            if time.elapsed() < CACHE_DURATION {
+                return Ok(playing);
+            }
+        }
+
+        let mut playing = false;
+        for name in DBusProxy::new(&self.conn)?.list_names()? {
+            if !name.starts_with(PLAYER_PREFIX) {
+                continue;
+            }
+            let status = Proxy::new(&self.conn, &name, PLAYER_PATH, PLAYER_INTERFACE)
+                .and_then(|player| Ok(player.get_property::<String>("PlaybackStatus")?));
+            match status {
+                Ok(ref status) if status == "Playing" => {
+                    debug!("Media player {name} is playing");
+                    playing = true;
+                    break;
+                },
+                Ok(_) => (),
+                // The player may have exited while we were looking at it
+                Err(err) => debug!("Can't get the status of media player {name}: {err}"),
+            }
+        }
+
+        self.cache = Some((Instant::now(), playing));
+        Ok(playing)
+    }
+}
+impl fmt::Debug for NotWhenPlaying {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NotWhenPlaying")
+    }
+}
+impl Module for NotWhenPlaying {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.playing()? {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.playing()? {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.cache = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+//! Shows a desktop notification when a timer goes off, through the
+//! freedesktop notification service on the session bus, and closes it
+//! once the user is back. Attached to the timer before a locker (see
+//! `Timer::module`), it warns that the screen is about to lock while
+//! there's still time to move the mouse. This is used to implement
+//! `--notify` in the xidlehook example application.
+
+use crate::{Module, Progress, Result, TimerInfo};
+
+use std::{collections::HashMap, fmt};
+
+use zbus::{zvariant::Value, Connection};
+
+const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+
+/// Let the notification service decide when the notification expires
+const DEFAULT_EXPIRY: i32 = -1;
+
+/// See the module-level documentation
+pub struct Notify {
+    conn: Connection,
+    summary: String,
+    body: String,
+    /// The notification that's shown, if any, which is replaced by the
+    /// next one instead of piling up
+    id: Option<u32>,
+}
+impl Notify {
+    /// Connect to the session bus. `{}` in the summary or body is
+    /// replaced by the index of the timer that went off.
+    ///
+    /// # Errors
+    ///
+    /// If the session bus can't be connected to
+    pub fn new(summary: String, body: String) -> Result<Self> {
+        Ok(Self {
+            conn: Connection::new_session()?,
+            summary,
+            body,
+            id: None,
+        })
+    }
+
+    /// Show the notification for this timer
+    ///
+    /// # Errors
+    ///
+    /// If the notification server can't be asked
+    pub fn show(&mut self, timer: TimerInfo) -> Result<()> {
+        let index = timer.index.to_string();
+        let summary = self.summary.replace("{}", &index);
+        let body = self.body.replace("{}", &index);
+        let actions: Vec<&str> = Vec::new();
+        let hints: HashMap<&str, Value> = HashMap::new();
+        let id = self
+            .conn
+            .call_method(
+                Some(NOTIFICATIONS),
+                NOTIFICATIONS_PATH,
+                Some(NOTIFICATIONS),
+                "Notify",
+                &(
+                    "xidlehook",
+                    self.id.unwrap_or_default(),
+                    "",
+                    summary.as_str(),
+                    body.as_str(),
+                    actions,
+                    hints,
+                    DEFAULT_EXPIRY,
+                ),
+            )?
+            .body()?;
+        self.id = Some(id);
+        Ok(())
+    }
+
+    /// Close the notification, if it's shown
+    ///
+    /// # Errors
+    ///
+    /// If the notification server can't be asked
+    pub fn close(&mut self) -> Result<()> {
+        if let Some(id) = self.id.take() {
+            self.conn.call_method(
+                Some(NOTIFICATIONS),
+                NOTIFICATIONS_PATH,
+                Some(NOTIFICATIONS),
+                "CloseNotification",
+                &id,
+            )?;
+        }
+        Ok(())
+    }
+}
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Notify({:?}, {:?})", self.summary, self.body)
+    }
+}
+impl Module for Notify {
+    fn post_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        self.show(timer)?;
+        Ok(Progress::Continue)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.close()
+    }
+}
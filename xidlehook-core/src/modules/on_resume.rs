@@ -0,0 +1,38 @@
+//! Runs a command whenever the user returns after at least one timer
+//! went off, no matter which one, such as to turn the monitor back
+//! on. Unlike a canceller, this doesn't belong to any timer. This is
+//! used to implement `--on-resume` in the xidlehook example
+//! application.
+
+use crate::{Module, Result};
+
+use std::{fmt, process::Command};
+
+use log::debug;
+
+/// See the module-level documentation
+#[derive(Clone)]
+pub struct OnResume {
+    command: Vec<String>,
+}
+impl OnResume {
+    /// Run this command, the first element being the program. It's
+    /// not waited for, so the application should reap its children.
+    pub fn new(command: Vec<String>) -> Self {
+        Self { command }
+    }
+}
+impl Module for OnResume {
+    fn reset(&mut self) -> Result<()> {
+        if let Some((program, args)) = self.command.split_first() {
+            debug!("Running {:?} as the user returned", self.command);
+            Command::new(program).args(args).spawn()?;
+        }
+        Ok(())
+    }
+}
+impl fmt::Debug for OnResume {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OnResume")
+    }
+}
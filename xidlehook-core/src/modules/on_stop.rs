@@ -0,0 +1,111 @@
+//! Runs a command right before xidlehook stops because the wrapped
+//! module decided so, such as `StopAt` once the chain is done. The
+//! command runs to completion first, so it's done by the time xidlehook
+//! exits, unless it takes longer than a timeout. This is used to
+//! implement `--once-command` in the xidlehook example application.
+
+use crate::{timers, Error, Module, Progress, Result, TimerInfo};
+
+use std::{convert::TryFrom, fmt, process::Command, time::Duration};
+
+use log::{debug, warn};
+use nix::{libc, unistd::Pid};
+
+/// How long the command may run by default before it's killed
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// See the module-level documentation
+pub struct OnStop<M> {
+    module: M,
+    command: Vec<String>,
+    timeout: Duration,
+}
+impl<M: Module> OnStop<M> {
+    /// Run this command, the first element being the program, whenever
+    /// `module` stops xidlehook. Whether it fails or not, xidlehook
+    /// still stops.
+    pub fn new(module: M, command: Vec<String>) -> Self {
+        Self {
+            module,
+            command,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Kill the command if it's still running after this long, like
+    /// `timers::CmdTimer::timeout` does. The default is 30 seconds.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns a reference to the wrapped module
+    pub fn inner(&self) -> &M {
+        &self.module
+    }
+
+    /// Returns a mutable reference to the wrapped module
+    pub fn inner_mut(&mut self) -> &mut M {
+        &mut self.module
+    }
+
+    /// Run the command and wait for it, if the decision is to stop
+    fn run_command(&self, progress: Progress) {
+        let (program, args) = match self.command.split_first() {
+            Some(command) if progress == Progress::Stop => command,
+            _ => return,
+        };
+        debug!("Running {:?} before stopping", self.command);
+        let mut child = match Command::new(program).args(args).spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                warn!("Failed to run {:?} before stopping: {}", self.command, err);
+                return;
+            },
+        };
+        if let Ok(pid) = i32::try_from(child.id()) {
+            if let Some(signal) = timers::terminate_after(Pid::from_raw(pid), self.timeout) {
+                warn!(
+                    "{:?} before stopping was still running after {:?}, killed it with {:?}",
+                    self.command, self.timeout, signal
+                );
+            }
+        }
+        match child.wait() {
+            Ok(status) if !status.success() => {
+                warn!("{:?} before stopping failed: {}", self.command, status);
+            },
+            Ok(_) => (),
+            // Another part of the application reaped it first, it has finished either way
+            Err(ref err) if err.raw_os_error() == Some(libc::ECHILD) => (),
+            Err(err) => warn!("Failed to wait for {:?}: {}", self.command, err),
+        }
+    }
+}
+impl<M> fmt::Debug for OnStop<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OnStop")
+    }
+}
+impl<M: Module> Module for OnStop<M> {
+    fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let progress = self.module.pre_timer(timer)?;
+        self.run_command(progress);
+        Ok(progress)
+    }
+    fn would_allow(&mut self, timer: TimerInfo) -> Result<Progress> {
+        self.module.would_allow(timer)
+    }
+    fn post_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let progress = self.module.post_timer(timer)?;
+        self.run_command(progress);
+        Ok(progress)
+    }
+    fn warning(&mut self, error: &Error) -> Result<()> {
+        self.module.warning(error)
+    }
+    fn reset(&mut self) -> Result<()> {
+        self.module.reset()
+    }
+}
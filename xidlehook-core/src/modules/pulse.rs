@@ -1,7 +1,9 @@
 //! Uses `PulseAudio`'s APIs to detect whenever audio is playing, and
-//! if so it refuses to let xidlehook run the next timer command. This
-//! is used to implement `--not-when-audio` in the xidlehook example
-//! application.
+//! if so it refuses to let xidlehook run the next timer command. It
+//! can optionally do the same when audio is being recorded, such as
+//! when a microphone is in use. This is used to implement
+//! `--not-when-audio` and `--not-when-recording` in the xidlehook
+//! example application.
 
 use crate::{Error, Module, Progress, Result, TimerInfo};
 
@@ -27,16 +29,125 @@ struct Counter {
 /// See module-level docs
 pub struct NotWhenAudio {
     counter: Rc<Counter>,
+    recording: Option<Rc<Counter>>,
     ctx: Rc<RefCell<Context>>,
     mainloop: Rc<RefCell<Mainloop>>,
 }
 impl NotWhenAudio {
     /// Connect to `PulseAudio` and subscribe to notification of changes
     pub fn new() -> Result<Self> {
+        Self::new_with_recording(false)
+    }
+    /// Like `new`, but if `recording` is true, streams that are
+    /// recording audio also count, not only streams playing audio
+    ///
+    /// # Errors
+    ///
+    /// If `PulseAudio` can't be connected to
+    pub fn new_with_recording(recording: bool) -> Result<Self> {
         let mainloop = Rc::new(RefCell::new(
             Mainloop::new().ok_or("pulseaudio: failed to create main loop")?,
         ));
+        let ctx = Self::connect(&mainloop)?;
+
+        let counter = Rc::new(Counter {
+            in_progress: Cell::new(0),
+            last_total: Cell::new(0),
+        });
+        let recording = if recording {
+            Some(Rc::new(Counter {
+                in_progress: Cell::new(0),
+                last_total: Cell::new(0),
+            }))
+        } else {
+            None
+        };
+
+        // Closure for setting up async count of input sinks
+        let get_sinks = |ctx: &mut Context, counter: Rc<Counter>| {
+            ctx.introspect()
+                .get_sink_input_info_list(move |res| match res {
+                    ListResult::Item(item) => {
+                        if !item.corked {
+                            let count = counter.in_progress.get().saturating_add(1);
+                            counter.in_progress.set(count);
+                            debug!("Partial count: {count}");
+                        }
+                    },
+                    ListResult::End | ListResult::Error => {
+                        let count = counter.in_progress.replace(0);
+                        counter.last_total.set(count);
+                        debug!("Total sum: {count}");
+                    },
+                });
+        };
+
+        // Closure for setting up async count of source outputs (recording streams)
+        let get_sources = |ctx: &mut Context, counter: Rc<Counter>| {
+            ctx.introspect()
+                .get_source_output_info_list(move |res| match res {
+                    ListResult::Item(item) => {
+                        if !item.corked {
+                            let count = counter.in_progress.get().saturating_add(1);
+                            counter.in_progress.set(count);
+                            debug!("Partial recording count: {count}");
+                        }
+                    },
+                    ListResult::End | ListResult::Error => {
+                        let count = counter.in_progress.replace(0);
+                        counter.last_total.set(count);
+                        debug!("Total recording sum: {count}");
+                    },
+                });
+        };
+
+        // Setup notification callback
+        //
+        // Upon notification of a change, we will make use of introspection
+        // to obtain a fresh count of active input sinks.
+        {
+            let ctx_ref = Rc::clone(&ctx);
+            let counter_ref = Rc::clone(&counter);
+            let recording_ref = recording.as_ref().map(Rc::clone);
+
+            ctx.borrow_mut()
+                .set_subscribe_callback(Some(Box::new(move |facility, _, _| {
+                    let ctx_ref = unsafe { &mut *ctx_ref.as_ptr() }; // Borrow checker workaround
+                    match (facility, &recording_ref) {
+                        (Some(Facility::SourceOutput), Some(recording_ref)) => {
+                            get_sources(ctx_ref, Rc::clone(recording_ref));
+                        },
+                        _ => get_sinks(ctx_ref, Rc::clone(&counter_ref)),
+                    }
+                })));
+        }
+
+        // Subscribe to sink input events, and source output events if
+        // recording is of interest
+        let mut mask = Facility::SinkInput.to_interest_mask();
+        if recording.is_some() {
+            mask |= Facility::SourceOutput.to_interest_mask();
+        }
+        ctx.borrow_mut().subscribe(mask, |_| ());
+
+        // Check if audio is already playing (or recording)
+        get_sinks(&mut ctx.borrow_mut(), Rc::clone(&counter));
+        if let Some(ref recording) = recording {
+            get_sources(&mut ctx.borrow_mut(), Rc::clone(recording));
+        }
+
+        mainloop.borrow_mut().unlock();
 
+        Ok(Self {
+            counter,
+            recording,
+            ctx,
+            mainloop,
+        })
+    }
+    /// Connect to `PulseAudio` and wait for the context to be ready.
+    /// The main loop is left locked.
+    fn connect(mainloop: &Rc<RefCell<Mainloop>>) -> Result<Rc<RefCell<Context>>> {
         let ctx = Rc::new(RefCell::new(
             Context::new(&*mainloop.borrow(), PA_NAME)
                 .ok_or("pulseaudio: failed to create context")?,
@@ -44,7 +155,7 @@ impl NotWhenAudio {
 
         // Setup context state change callback
         {
-            let mainloop_ref = Rc::clone(&mainloop);
+            let mainloop_ref = Rc::clone(mainloop);
             let ctx_ref = Rc::clone(&ctx);
 
             ctx.borrow_mut().set_state_callback(Some(Box::new(move || {
@@ -66,15 +177,14 @@ impl NotWhenAudio {
 
         ctx.borrow_mut()
             .connect(None, context::flags::NOFLAGS, None)
-            .map_err(|err| format!("pulseaudio: failed to connect context: {}", err))?;
+            .map_err(|err| format!("pulseaudio: failed to connect context: {err}"))?;
 
         mainloop.borrow_mut().lock();
 
         if let Err(err) = mainloop.borrow_mut().start() {
             mainloop.borrow_mut().unlock();
             return Err(Error::from(format!(
-                "pulseaudio: failed to start mainloop: {}",
-                err
+                "pulseaudio: failed to start mainloop: {err}"
             )));
         }
 
@@ -96,59 +206,7 @@ impl NotWhenAudio {
         }
         ctx.borrow_mut().set_state_callback(None);
 
-        let counter = Rc::new(Counter {
-            in_progress: Cell::new(0),
-            last_total: Cell::new(0),
-        });
-
-        // Closure for setting up async count of input sinks
-        let get_sinks = |ctx: &mut Context, counter: Rc<Counter>| {
-            ctx.introspect()
-                .get_sink_input_info_list(move |res| match res {
-                    ListResult::Item(item) => {
-                        if !item.corked {
-                            let count = counter.in_progress.get().saturating_add(1);
-                            counter.in_progress.set(count);
-                            debug!("Partial count: {}", count);
-                        }
-                    },
-                    ListResult::End | ListResult::Error => {
-                        let count = counter.in_progress.replace(0);
-                        counter.last_total.set(count);
-                        debug!("Total sum: {}", count);
-                    },
-                });
-        };
-
-        // Setup notification callback
-        //
-        // Upon notification of a change, we will make use of introspection
-        // to obtain a fresh count of active input sinks.
-        {
-            let ctx_ref = Rc::clone(&ctx);
-            let counter_ref = Rc::clone(&counter);
-
-            ctx.borrow_mut()
-                .set_subscribe_callback(Some(Box::new(move |_, _, _| {
-                    let ctx_ref = unsafe { &mut *ctx_ref.as_ptr() }; // Borrow checker workaround
-                    get_sinks(ctx_ref, Rc::clone(&counter_ref));
-                })));
-        }
-
-        // Subscribe to sink input events
-        ctx.borrow_mut()
-            .subscribe(Facility::SinkInput.to_interest_mask(), |_| ());
-
-        // Check if audio is already playing
-        get_sinks(&mut ctx.borrow_mut(), Rc::clone(&counter));
-
-        mainloop.borrow_mut().unlock();
-
-        Ok(Self {
-            counter,
-            ctx,
-            mainloop,
-        })
+        Ok(ctx)
     }
 }
 impl fmt::Debug for NotWhenAudio {
@@ -167,9 +225,16 @@ impl Drop for NotWhenAudio {
     }
 }
 impl Module for NotWhenAudio {
-    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+    fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        self.would_allow(timer)
+    }
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
         self.mainloop.borrow_mut().lock();
-        let players = self.counter.last_total.get();
+        let players = self.counter.last_total.get().saturating_add(
+            self.recording
+                .as_ref()
+                .map_or(0, |recording| recording.last_total.get()),
+        );
         self.mainloop.borrow_mut().unlock();
         if players == 0 {
             Ok(Progress::Continue)
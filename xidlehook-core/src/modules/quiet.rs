@@ -0,0 +1,11 @@
+//! Swallows warnings, in place of the default `()` module that logs
+//! them. Use it as the first module with `Xidlehook::with_module`.
+//! This is used to implement `--quiet` in the xidlehook example
+//! application.
+
+use crate::Module;
+
+/// See the module-level documentation
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Quiet;
+impl Module for Quiet {}
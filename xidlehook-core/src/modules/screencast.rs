@@ -0,0 +1,155 @@
+//! Refuses to let xidlehook run the next timer while the screen is
+//! being shared, such as in a video call. Screencasts requested
+//! through xdg-desktop-portal are streamed over `PipeWire`, but the
+//! portal doesn't let other clients see its sessions, so they're
+//! looked up in `PipeWire`'s graph instead, as printed by `pw-dump`: A
+//! screencast is a video source that's running, but that doesn't
+//! belong to a device, which tells it apart from a webcam. This is
+//! used to implement `--not-when-screencasting` in the xidlehook
+//! example application.
+
+use crate::{Module, Progress, Result, TimerInfo};
+
+use std::{
+    fmt,
+    io::Read,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use serde_json::Value;
+
+/// How long a result is reused by default before asking `PipeWire`
+/// again, as `pre_timer` may be called several times in quick
+/// succession
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+const NODE_TYPE: &str = "PipeWire:Interface:Node";
+const VIDEO_SOURCE: &str = "Video/Source";
+
+/// Returns the name of the first screencast among the objects that
+/// `pw-dump` printed, if there's any
+fn find_screencast(objects: &[Value]) -> Option<&str> {
+    objects.iter().find_map(|object| {
+        let info = &object["info"];
+        let props = &info["props"];
+        let screencast = object["type"] == NODE_TYPE
+            && info["state"] == "running"
+            && props["media.class"] == VIDEO_SOURCE
+            && props.get("device.id").is_none();
+        if screencast {
+            Some(props["node.name"].as_str().unwrap_or("unnamed"))
+        } else {
+            None
+        }
+    })
+}
+
+/// See the module-level documentation
+pub struct NotWhenScreencast {
+    command: Vec<String>,
+    interval: Duration,
+    cache: Option<(Instant, bool)>,
+}
+impl NotWhenScreencast {
+    /// Create a new instance, which runs `pw-dump` at most every 5
+    /// seconds
+    pub fn new() -> Self {
+        Self {
+            command: vec![String::from("pw-dump")],
+            interval: DEFAULT_INTERVAL,
+            cache: None,
+        }
+    }
+
+    /// Reuse the result for this long before asking `PipeWire` again
+    #[must_use]
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Run this command instead of `pw-dump`, such as to pass it a
+    /// different remote. It must print the same JSON.
+    #[must_use]
+    pub fn with_command(mut self, command: Vec<String>) -> Self {
+        self.command = command;
+        self
+    }
+
+    /// Ask `PipeWire` whether the screen is being shared
+    ///
+    /// # Errors
+    ///
+    /// If the command can't be run, or its output can't be parsed
+    pub fn screencasting(&mut self) -> Result<bool> {
+        if let Some((time, screencasting)) = self.cache {
+            if time.elapsed() < self.interval {
+                return Ok(screencasting);
+            }
+        }
+
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or("screencast: no command to run")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("screencast: can't run {program}: {err}"))?;
+        let mut output = String::new();
+        child
+            .stdout
+            .take()
+            .ok_or("screencast: no stdout")?
+            .read_to_string(&mut output)?;
+        // The xidlehook application reaps every child process as soon as it exits, in which case
+        // there's nothing left to wait for
+        let _ = child.wait();
+
+        let objects: Vec<Value> = serde_json::from_str(&output)
+            .map_err(|err| format!("screencast: can't read the output of {program}: {err}"))?;
+        let screencast = find_screencast(&objects);
+        if let Some(name) = screencast {
+            debug!("The screen is being shared by {name:?}");
+        }
+
+        self.cache = Some((Instant::now(), screencast.is_some()));
+        Ok(screencast.is_some())
+    }
+}
+impl Default for NotWhenScreencast {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl fmt::Debug for NotWhenScreencast {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NotWhenScreencast")
+    }
+}
+impl Module for NotWhenScreencast {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.screencasting()? {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+
+    // Running `pw-dump` is a side effect, so this reports its last result instead
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        match self.cache {
+            Some((_, true)) => Ok(Progress::Abort),
+            _ => Ok(Progress::Continue),
+        }
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.cache = None;
+        Ok(())
+    }
+}
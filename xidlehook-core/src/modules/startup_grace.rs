@@ -0,0 +1,71 @@
+//! Refuses to let any timer run for a while after startup. When
+//! xidlehook is started at login, the idle time may already be high
+//! from before it was launched, which would otherwise make it lock
+//! the screen right away. The chain is aborted instead, so it starts
+//! over once the user is active. This is used to implement
+//! `--startup-grace` in the xidlehook example application.
+
+use crate::{
+    clock::{Clock, MonotonicClock},
+    Module, Progress, Result, TimerInfo,
+};
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+/// See the module-level documentation
+pub struct StartupGrace {
+    grace: Duration,
+    started: Instant,
+    clock: Box<dyn Clock>,
+}
+impl StartupGrace {
+    /// Abort all timers for `grace` after now
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            started: Instant::now(),
+            clock: Box::new(MonotonicClock),
+        }
+    }
+
+    /// Return this module but with a different clock, which the grace
+    /// period is measured with from now
+    #[must_use]
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.started = clock.now();
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Whether the grace period is still ongoing
+    pub fn in_grace(&self) -> bool {
+        self.clock.now().saturating_duration_since(self.started) < self.grace
+    }
+}
+impl fmt::Debug for StartupGrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StartupGrace")
+    }
+}
+impl Module for StartupGrace {
+    fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        if self.in_grace() {
+            debug!("Not running timer {} this soon after startup", timer.index);
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.in_grace() {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+}
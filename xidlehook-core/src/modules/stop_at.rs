@@ -1,6 +1,7 @@
 //! Stops xidlehook completely at a specific index of the chain or at
-//! the end. This is used to implement `--once` in the xidlehook
-//! example application.
+//! the end, or after the chain ran a number of times. To run a command
+//! first, wrap these in `OnStop`. This is used to implement `--once`
+//! and `--stop-after` in the xidlehook example application.
 
 use crate::{Module, Progress, Result, TimerInfo};
 
@@ -45,3 +46,45 @@ impl fmt::Debug for StopAt {
         write!(f, "StopAt")
     }
 }
+
+/// Like `StopAt::completion`, but lets the chain run to the end a
+/// number of times before stopping, such as to nag three times and
+/// then give up. See the module-level documentation.
+#[derive(Clone, Copy, Debug)]
+pub struct StopAfter {
+    times: usize,
+    completed: usize,
+}
+impl StopAfter {
+    /// Returns a module which will stop execution once the chain of
+    /// timers has executed entirely this many times. Only chains that
+    /// reach the last enabled timer count: One that's aborted by a
+    /// module, or cancelled by the user being back, never gets there.
+    /// A snoozed timer only delays getting there. Neither
+    /// does the count start over once the user is back, as the chain
+    /// can only run again after that.
+    pub fn times(times: usize) -> Self {
+        Self {
+            times,
+            completed: 0,
+        }
+    }
+    /// How many times the chain has run to the end so far
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+}
+impl Module for StopAfter {
+    fn post_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        if !timer.last {
+            return Ok(Progress::Continue);
+        }
+        self.completed = self.completed.saturating_add(1);
+        trace!("The chain ran {}/{} times", self.completed, self.times);
+        if self.completed >= self.times {
+            Ok(Progress::Stop)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Wraps another module under a stable name, so that it can be turned
+//! off and on at runtime. While disabled, the module never stops a
+//! timer from running. This is used to implement enabling and
+//! disabling modules over the socket in the xidlehook example
+//! application.
+
+use crate::{Error, Module, Progress, Result, TimerInfo, DECISION_TARGET};
+
+use std::fmt;
+
+use log::{log_enabled, trace, Level};
+
+/// See the module-level documentation
+pub struct Toggle<M: Module> {
+    module: M,
+    name: String,
+    /// Whether or not the wrapped module has a say in running timers
+    pub enabled: bool,
+}
+impl<M: Module> Toggle<M> {
+    /// Wrap a module, which starts out enabled
+    pub fn new<S: Into<String>>(name: S, module: M) -> Self {
+        Self {
+            module,
+            name: name.into(),
+            enabled: true,
+        }
+    }
+
+    /// Trace what this module decided, see `DECISION_TARGET`
+    fn trace(&self, stage: &str, timer: TimerInfo, result: &Result<Progress>) {
+        if !log_enabled!(target: DECISION_TARGET, Level::Trace) {
+            return;
+        }
+        let progress = match result {
+            Ok(Progress::Continue) => "continue",
+            Ok(Progress::Halt) => "halt",
+            Ok(Progress::Abort) => "abort",
+            Ok(Progress::Force) => "force",
+            Ok(Progress::Stop) => "stop",
+            Err(_) => "error",
+        };
+        trace!(
+            target: DECISION_TARGET,
+            module = self.name.as_str(),
+            stage = stage,
+            timer = timer.index,
+            enabled = self.enabled,
+            progress = progress;
+            "Module {} returned {} from {}", self.name, progress, stage
+        );
+    }
+
+    /// The name this module was given
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns a reference to the wrapped module
+    pub fn inner(&self) -> &M {
+        &self.module
+    }
+
+    /// Returns a mutable reference to the wrapped module
+    pub fn inner_mut(&mut self) -> &mut M {
+        &mut self.module
+    }
+}
+impl<M: Module> fmt::Debug for Toggle<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Toggle({:?}, enabled: {})", self.name, self.enabled)
+    }
+}
+impl<M: Module> Module for Toggle<M> {
+    fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let result = if self.enabled {
+            self.module.pre_timer(timer)
+        } else {
+            Ok(Progress::Continue)
+        };
+        self.trace("pre_timer", timer, &result);
+        result
+    }
+    fn would_allow(&mut self, timer: TimerInfo) -> Result<Progress> {
+        if self.enabled {
+            self.module.would_allow(timer)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+    fn post_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+        let result = if self.enabled {
+            self.module.post_timer(timer)
+        } else {
+            Ok(Progress::Continue)
+        };
+        self.trace("post_timer", timer, &result);
+        result
+    }
+    // Warnings and resets are still passed on, so the module's state is fresh once it's enabled
+    // again
+    fn warning(&mut self, error: &Error) -> Result<()> {
+        self.module.warning(error)
+    }
+    fn reset(&mut self) -> Result<()> {
+        self.module.reset()
+    }
+}
@@ -0,0 +1,85 @@
+//! Counts the warnings that were reported during the session, so a
+//! summary can be shown at the end. Transient warnings easily scroll
+//! past over a long session, but a persistent failure stands out in
+//! the summary. This is used to implement the summary shown on exit
+//! in the xidlehook example application.
+//!
+//! Warnings are counted by kind, which is the message with every number
+//! in it (such as a PID, an exit code or a duration) replaced by `N`,
+//! so that the same failure is counted together every time.
+
+use crate::{Error, Module, Result};
+
+use std::{cell::RefCell, collections::BTreeMap, fmt, rc::Rc};
+
+use log::warn;
+
+/// Returns the kind of warning a message is, see the module-level
+/// documentation
+fn kind(message: &str) -> String {
+    let mut kind = String::with_capacity(message.len());
+    let mut chars = message.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !c.is_ascii_digit() {
+            kind.push(c);
+            continue;
+        }
+        kind.push('N');
+        // Skip the rest of the number, including any fraction
+        while let Some(&next) = chars.peek() {
+            if next.is_ascii_digit() || next == '.' {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+    kind
+}
+
+/// See the module-level documentation. Clones share the same
+/// counters, so keep a clone around to read them after registering
+/// the module.
+#[derive(Clone, Default)]
+pub struct WarningSummary {
+    counts: Rc<RefCell<BTreeMap<String, usize>>>,
+}
+impl WarningSummary {
+    /// Create a new instance with no warnings counted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return how many times each kind of warning was reported
+    pub fn counts(&self) -> BTreeMap<String, usize> {
+        self.counts.borrow().clone()
+    }
+
+    /// Log the summary, if there were any warnings at all
+    pub fn log(&self) {
+        let counts = self.counts.borrow();
+        if counts.is_empty() {
+            return;
+        }
+        let total = counts
+            .values()
+            .fold(0_usize, |sum, &count| sum.saturating_add(count));
+        warn!("There were {total} warnings during this session:");
+        for (kind, count) in counts.iter() {
+            warn!("  {count}x {kind}");
+        }
+    }
+}
+impl fmt::Debug for WarningSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WarningSummary")
+    }
+}
+impl Module for WarningSummary {
+    fn warning(&mut self, error: &Error) -> Result<()> {
+        let mut counts = self.counts.borrow_mut();
+        let count = counts.entry(kind(&error.to_string())).or_insert(0);
+        *count = count.saturating_add(1);
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+//! Reads the idle time from a Wayland compositor, such as sway or
+//! Hyprland, for sessions without an X server. This uses the
+//! `ext-idle-notify-v1` protocol, which doesn't tell how long the user
+//! has been idle, only when they became idle after a timeout and when
+//! they're back. So the idle time is counted from when the compositor
+//! says the user became idle. Only a handful of messages are needed,
+//! so this speaks the Wayland wire protocol directly.
+
+use crate::Result;
+
+use std::{
+    convert::TryFrom,
+    env, fmt,
+    io::{self, Read, Write},
+    net::Shutdown,
+    os::unix::{
+        io::{AsRawFd, RawFd},
+        net::UnixStream,
+    },
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, trace};
+
+const NOTIFIER_INTERFACE: &str = "ext_idle_notifier_v1";
+const SEAT_INTERFACE: &str = "wl_seat";
+
+/// After how long without input the compositor reports the user as
+/// idle. Until then, the idle time is zero, so this is how far off the
+/// idle time may be.
+const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(1);
+
+// The IDs of the objects used, which the client picks
+const DISPLAY_ID: u32 = 1;
+const REGISTRY_ID: u32 = 2;
+const CALLBACK_ID: u32 = 3;
+const SEAT_ID: u32 = 4;
+const NOTIFIER_ID: u32 = 5;
+const NOTIFICATION_ID: u32 = 6;
+const SECOND_CALLBACK_ID: u32 = 7;
+
+// Request opcodes
+const DISPLAY_SYNC: u16 = 0;
+const DISPLAY_GET_REGISTRY: u16 = 1;
+const REGISTRY_BIND: u16 = 0;
+const NOTIFIER_GET_IDLE_NOTIFICATION: u16 = 1;
+
+// Event opcodes
+const DISPLAY_ERROR: u16 = 0;
+const REGISTRY_GLOBAL: u16 = 0;
+const CALLBACK_DONE: u16 = 0;
+const NOTIFICATION_IDLED: u16 = 0;
+const NOTIFICATION_RESUMED: u16 = 1;
+
+/// Where the compositor's socket is, see `wl_display_connect`
+fn socket_path() -> Result<PathBuf> {
+    let display = env::var_os("WAYLAND_DISPLAY").unwrap_or_else(|| "wayland-0".into());
+    let display = PathBuf::from(display);
+    if display.is_absolute() {
+        return Ok(display);
+    }
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")
+        .ok_or("can't find the Wayland socket, $XDG_RUNTIME_DIR isn't set")?;
+    Ok(PathBuf::from(runtime_dir).join(display))
+}
+
+/// Round up to a multiple of 32 bits, as all arguments are padded
+fn padded(len: usize) -> usize {
+    len.saturating_add(3) & !3
+}
+
+/// Read a 32-bit word from the start of `bytes`, which must be long
+/// enough
+fn word(bytes: &[u8]) -> u32 {
+    u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// A message in either direction: The object it's sent to or from, the
+/// opcode, and the encoded arguments
+struct Message {
+    object: u32,
+    opcode: u16,
+    args: Vec<u8>,
+}
+impl Message {
+    fn new(object: u32, opcode: u16) -> Self {
+        Self {
+            object,
+            opcode,
+            args: Vec::new(),
+        }
+    }
+    fn uint(mut self, value: u32) -> Self {
+        self.args.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+    /// Strings are length-prefixed, nul-terminated and padded to 32
+    /// bits
+    fn string(mut self, value: &str) -> Self {
+        let len = u32::try_from(value.len().saturating_add(1)).unwrap_or(u32::MAX);
+        self = self.uint(len);
+        self.args.extend_from_slice(value.as_bytes());
+        self.args
+            .resize(padded(self.args.len().saturating_add(1)), 0);
+        self
+    }
+
+    fn send(&self, mut stream: &UnixStream) -> Result<()> {
+        let size = u32::try_from(self.args.len().saturating_add(8))?;
+        let mut buf = Vec::with_capacity(self.args.len().saturating_add(8));
+        buf.extend_from_slice(&self.object.to_ne_bytes());
+        buf.extend_from_slice(&(size << 16 | u32::from(self.opcode)).to_ne_bytes());
+        buf.extend_from_slice(&self.args);
+        stream.write_all(&buf)?;
+        Ok(())
+    }
+
+    fn receive(mut stream: &UnixStream) -> io::Result<Self> {
+        let mut header = [0; 8];
+        stream.read_exact(&mut header)?;
+        let (object, size_opcode) = (word(&header), word(&header[4..]));
+        let size = usize::try_from(size_opcode >> 16).unwrap_or_default();
+        let mut args = vec![0; size.saturating_sub(8)];
+        stream.read_exact(&mut args)?;
+        Ok(Self {
+            object,
+            opcode: u16::try_from(size_opcode & 0xFFFF).unwrap_or_default(),
+            args,
+        })
+    }
+}
+
+/// Decodes the arguments of a received message, in order
+struct Args<'a>(&'a [u8]);
+impl Args<'_> {
+    fn uint(&mut self) -> Result<u32> {
+        if self.0.len() < 4 {
+            return Err("truncated Wayland message".into());
+        }
+        let (value, rest) = self.0.split_at(4);
+        self.0 = rest;
+        Ok(word(value))
+    }
+    fn string(&mut self) -> Result<String> {
+        let len = usize::try_from(self.uint()?)?;
+        if self.0.len() < padded(len) {
+            return Err("truncated Wayland message".into());
+        }
+        let (value, rest) = self.0.split_at(padded(len));
+        self.0 = rest;
+        // Without the nul terminator
+        let value = &value[..len.saturating_sub(1)];
+        Ok(String::from_utf8_lossy(value).into_owned())
+    }
+}
+
+/// Turn a `wl_display.error` event into an error
+fn protocol_error(message: &Message) -> crate::Error {
+    let mut args = Args(&message.args);
+    match (args.uint(), args.uint(), args.string()) {
+        (Ok(object), Ok(code), Ok(text)) => {
+            format!("the Wayland compositor reported error {code} on object {object}: {text}")
+                .into()
+        },
+        _ => "the Wayland compositor reported an error".into(),
+    }
+}
+
+/// What the compositor last said about the user
+#[derive(Default)]
+struct State {
+    /// When the compositor said the user became idle, unless they've
+    /// been active since
+    idled: Option<Instant>,
+    /// Why the connection stopped working, if it did
+    error: Option<String>,
+}
+impl State {
+    /// Apply an event of the idle notification
+    fn notification_event(&mut self, opcode: u16) {
+        match opcode {
+            NOTIFICATION_IDLED => {
+                trace!("The compositor says the user is idle");
+                self.idled = Some(Instant::now());
+            },
+            NOTIFICATION_RESUMED => {
+                trace!("The compositor says the user is active");
+                self.idled = None;
+            },
+            _ => (),
+        }
+    }
+}
+
+/// See the module-level documentation
+pub struct WaylandIdle {
+    stream: UnixStream,
+    state: Arc<Mutex<State>>,
+}
+impl WaylandIdle {
+    /// Connect to the compositor in `$WAYLAND_DISPLAY`. This fails if
+    /// the compositor doesn't support `ext-idle-notify-v1`.
+    ///
+    /// # Errors
+    ///
+    /// If the compositor can't be connected to, or lacks the protocol
+    pub fn new() -> Result<Self> {
+        let path = socket_path()?;
+        let stream = UnixStream::connect(&path).map_err(|err| {
+            format!(
+                "can't connect to the Wayland compositor at {}: {}",
+                path.display(),
+                err
+            )
+        })?;
+        Self::with_stream(stream)
+    }
+
+    /// Like `new`, but use a connection that's already open
+    ///
+    /// # Errors
+    ///
+    /// If the compositor lacks the protocol, or the connection fails
+    pub fn with_stream(stream: UnixStream) -> Result<Self> {
+        let mut state = State::default();
+
+        Message::new(DISPLAY_ID, DISPLAY_GET_REGISTRY)
+            .uint(REGISTRY_ID)
+            .send(&stream)?;
+        Message::new(DISPLAY_ID, DISPLAY_SYNC)
+            .uint(CALLBACK_ID)
+            .send(&stream)?;
+
+        // The names of the globals to bind
+        let mut seat = None;
+        let mut notifier = None;
+        loop {
+            let message = Message::receive(&stream)?;
+            match (message.object, message.opcode) {
+                (DISPLAY_ID, DISPLAY_ERROR) => return Err(protocol_error(&message)),
+                (REGISTRY_ID, REGISTRY_GLOBAL) => {
+                    let mut args = Args(&message.args);
+                    let (name, interface) = (args.uint()?, args.string()?);
+                    match interface.as_str() {
+                        // Any seat will do, they all count as the same user
+                        SEAT_INTERFACE if seat.is_none() => seat = Some(name),
+                        NOTIFIER_INTERFACE => notifier = Some(name),
+                        _ => (),
+                    }
+                },
+                (CALLBACK_ID, CALLBACK_DONE) => break,
+                _ => (),
+            }
+        }
+        let notifier = notifier
+            .ok_or("the Wayland compositor doesn't support the ext-idle-notify-v1 protocol")?;
+        let seat = seat.ok_or("the Wayland compositor has no seat")?;
+
+        Message::new(REGISTRY_ID, REGISTRY_BIND)
+            .uint(seat)
+            .string(SEAT_INTERFACE)
+            .uint(1)
+            .uint(SEAT_ID)
+            .send(&stream)?;
+        Message::new(REGISTRY_ID, REGISTRY_BIND)
+            .uint(notifier)
+            .string(NOTIFIER_INTERFACE)
+            .uint(1)
+            .uint(NOTIFIER_ID)
+            .send(&stream)?;
+        Message::new(NOTIFIER_ID, NOTIFIER_GET_IDLE_NOTIFICATION)
+            .uint(NOTIFICATION_ID)
+            .uint(u32::try_from(NOTIFICATION_TIMEOUT.as_millis())?)
+            .uint(SEAT_ID)
+            .send(&stream)?;
+        Message::new(DISPLAY_ID, DISPLAY_SYNC)
+            .uint(SECOND_CALLBACK_ID)
+            .send(&stream)?;
+
+        // Make sure that all went through
+        loop {
+            let message = Message::receive(&stream)?;
+            match (message.object, message.opcode) {
+                (DISPLAY_ID, DISPLAY_ERROR) => return Err(protocol_error(&message)),
+                (NOTIFICATION_ID, opcode) => state.notification_event(opcode),
+                (SECOND_CALLBACK_ID, CALLBACK_DONE) => break,
+                _ => (),
+            }
+        }
+        debug!("Reading the idle time from the Wayland compositor");
+
+        let state = Arc::new(Mutex::new(state));
+        let reader = stream.try_clone()?;
+        let thread_state = Arc::clone(&state);
+        // Events are received right away, so it's known when the user became idle even while
+        // xidlehook sleeps
+        thread::spawn(move || loop {
+            let result = Message::receive(&reader);
+            let mut state = thread_state.lock().unwrap_or_else(PoisonError::into_inner);
+            match result {
+                Ok(ref message) if message.object == NOTIFICATION_ID => {
+                    state.notification_event(message.opcode);
+                },
+                Ok(ref message)
+                    if (message.object, message.opcode) == (DISPLAY_ID, DISPLAY_ERROR) =>
+                {
+                    state.error = Some(protocol_error(message).to_string());
+                    return;
+                },
+                Ok(_) => (),
+                Err(err) => {
+                    state.error = Some(format!(
+                        "lost the connection to the Wayland compositor: {err}"
+                    ));
+                    return;
+                },
+            }
+        });
+
+        Ok(Self { stream, state })
+    }
+
+    /// Get the user's idle time. It's zero until the user has been idle
+    /// for a second, as the compositor doesn't tell any sooner.
+    ///
+    /// # Errors
+    ///
+    /// If the connection to the compositor was lost
+    pub fn get_idle(&self) -> Result<Duration> {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(ref error) = state.error {
+            return Err(error.clone().into());
+        }
+        Ok(state.idled.map_or_else(Duration::default, |idled| {
+            NOTIFICATION_TIMEOUT.saturating_add(idled.elapsed())
+        }))
+    }
+
+    /// Get whether or not the connection to the compositor still works
+    pub fn is_connected(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .error
+            .is_none()
+    }
+}
+impl AsRawFd for WaylandIdle {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+impl Drop for WaylandIdle {
+    fn drop(&mut self) {
+        // Makes the thread reading events exit
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+impl fmt::Debug for WaylandIdle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WaylandIdle")
+    }
+}
@@ -4,29 +4,145 @@
 //! `NotWhenFullscreen` module is used to implement
 //! `--not-when-fullscreen` in the example client.
 
-use crate::{Module, Progress, Result, TimerInfo};
+use crate::{
+    modules::xinput::{InputClasses, XInputIdle},
+    Error, Module, Progress, Result, TimerInfo,
+};
 
-use std::{fmt, rc::Rc, slice, time::Duration};
+use std::{
+    cell::Cell,
+    cmp,
+    convert::{TryFrom, TryInto},
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
-use log::debug;
+use log::{debug, info, warn};
 
 const NET_WM_STATE: &str = "_NET_WM_STATE";
 const NET_WM_STATE_FULLSCREEN: &str = "_NET_WM_STATE_FULLSCREEN";
+const NET_WM_STATE_HIDDEN: &str = "_NET_WM_STATE_HIDDEN";
+const NET_WM_WINDOW_TYPE: &str = "_NET_WM_WINDOW_TYPE";
+const NET_WM_WINDOW_TYPE_DESKTOP: &str = "_NET_WM_WINDOW_TYPE_DESKTOP";
+const NET_CURRENT_DESKTOP: &str = "_NET_CURRENT_DESKTOP";
+const NET_WM_DESKTOP: &str = "_NET_WM_DESKTOP";
+const NET_ACTIVE_WINDOW: &str = "_NET_ACTIVE_WINDOW";
+const NET_CLIENT_LIST: &str = "_NET_CLIENT_LIST";
+const NET_WM_NAME: &str = "_NET_WM_NAME";
+const WM_WINDOW_ROLE: &str = "WM_WINDOW_ROLE";
+
+/// The `WM_WINDOW_ROLE`s of picture-in-picture windows, such as
+/// Firefox's
+const PIP_ROLES: &[&str] = &["PictureInPicture"];
+/// The titles of picture-in-picture windows that have no special
+/// role, such as Chromium's
+const PIP_TITLES: &[&str] = &["Picture-in-Picture", "Picture in picture"];
+/// The longest property value read, in 32-bit units
+const MAX_PROPERTY_LEN: u32 = 1024;
+/// The names of the core X protocol errors, by error code
+const CORE_ERRORS: &[&str] = &[
+    "Success",
+    "BadRequest",
+    "BadValue",
+    "BadWindow",
+    "BadPixmap",
+    "BadAtom",
+    "BadCursor",
+    "BadFont",
+    "BadMatch",
+    "BadDrawable",
+    "BadAccess",
+    "BadAlloc",
+    "BadColor",
+    "BadGC",
+    "BadIDChoice",
+    "BadName",
+    "BadLength",
+    "BadImplementation",
+];
+/// The names of the `SYNC` extension's errors, counting from its
+/// first error code
+const SYNC_ERRORS: &[&str] = &["BadCounter", "BadAlarm"];
+
+/// The `_NET_WM_DESKTOP` value of windows that are on all desktops
+const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+/// The name of the SYNC extension's system counter for idle time
+const IDLETIME_COUNTER: &str = "IDLETIME";
+
+/// Where the idle time is read from. Drivers sometimes disagree, so
+/// when one of them under-reports the idle time, try the other. By
+/// default, the first one that works is used, see `Xcb::new`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IdleSource {
+    /// The `MIT-SCREEN-SAVER` extension. This is the default.
+    #[default]
+    ScreenSaver,
+    /// The `IDLETIME` system counter of the `SYNC` extension
+    Sync,
+    /// Raw input events of the `XInput` extension, of which only these
+    /// classes count as activity, see `XInputIdle`
+    XInput(InputClasses),
+}
 
 /// See the crate-level documentation
 pub struct Xcb {
     conn: xcb::Connection,
+    /// The display connected to, if not the one in `$DISPLAY`
+    display: Option<String>,
     root_window: xcb::Window,
     atom_net_wm_state: xcb::Atom,
     atom_net_wm_state_fullscreen: xcb::Atom,
+    atom_net_wm_state_hidden: xcb::Atom,
+    atom_net_wm_window_type: xcb::Atom,
+    atom_net_wm_window_type_desktop: xcb::Atom,
+    atom_net_current_desktop: xcb::Atom,
+    atom_net_wm_desktop: xcb::Atom,
+    atom_net_client_list: xcb::Atom,
+    atom_net_wm_name: xcb::Atom,
+    atom_wm_window_role: xcb::Atom,
+    /// The `IDLETIME` counter, if the idle source is `IdleSource::Sync`
+    idle_counter: Option<xcb::sync::Counter>,
+    /// The raw input events, if the idle source is `IdleSource::XInput`
+    xinput: Option<XInputIdle>,
+    /// The `_NET_ACTIVE_WINDOW` atom, if changing the active window
+    /// counts as activity
+    atom_net_active_window: Option<xcb::Atom>,
+    /// When the active window last changed
+    last_window_change: Cell<Option<Instant>>,
+    /// Added to the idle time until the user is first active, along
+    /// with the last idle time read, see `set_initial_idle`
+    idle_offset: Cell<Option<(Duration, Duration)>>,
 }
 impl Xcb {
-    /// Initialize all the things, like setting up an X connection.
+    /// Initialize all the things, like setting up an X connection to
+    /// the display in `$DISPLAY`. Not every X server supports both
+    /// idle sources, so this tries `IdleSource::ScreenSaver` and then
+    /// `IdleSource::Sync`, and uses the first one that can read the
+    /// idle time. Use `set_idle_source` to pick one explicitly.
     pub fn new() -> Result<Self> {
-        let (conn, _) = xcb::Connection::connect(None)?;
+        Self::with_display(None)
+    }
+    /// Like `new`, but connect to this display (such as ":1") instead
+    /// of the one in `$DISPLAY`, if set
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be connected to
+    pub fn with_display(display: Option<&str>) -> Result<Self> {
+        let (conn, screen_num) = xcb::Connection::connect(display).map_err(|err| {
+            format!(
+                "can't connect to X display {}: {}",
+                display.map_or_else(|| String::from("from $DISPLAY"), |d| format!("{d:?}")),
+                err
+            )
+        })?;
 
         let setup = conn.get_setup();
-        let screen = setup.roots().next().ok_or("no xcb root")?;
+        let screen = usize::try_from(screen_num)
+            .ok()
+            .and_then(|num| setup.roots().nth(num))
+            .ok_or("no xcb root")?;
         let root_window = screen.root();
 
         let atom_net_wm_state = xcb::xproto::intern_atom(&conn, false, NET_WM_STATE)
@@ -36,65 +152,632 @@ impl Xcb {
             xcb::xproto::intern_atom(&conn, false, NET_WM_STATE_FULLSCREEN)
                 .get_reply()?
                 .atom();
+        let atom_net_wm_state_hidden = xcb::xproto::intern_atom(&conn, false, NET_WM_STATE_HIDDEN)
+            .get_reply()?
+            .atom();
+        let atom_net_wm_window_type = xcb::xproto::intern_atom(&conn, false, NET_WM_WINDOW_TYPE)
+            .get_reply()?
+            .atom();
+        let atom_net_wm_window_type_desktop =
+            xcb::xproto::intern_atom(&conn, false, NET_WM_WINDOW_TYPE_DESKTOP)
+                .get_reply()?
+                .atom();
+        let atom_net_current_desktop = xcb::xproto::intern_atom(&conn, false, NET_CURRENT_DESKTOP)
+            .get_reply()?
+            .atom();
+        let atom_net_wm_desktop = xcb::xproto::intern_atom(&conn, false, NET_WM_DESKTOP)
+            .get_reply()?
+            .atom();
+        let atom_net_client_list = xcb::xproto::intern_atom(&conn, false, NET_CLIENT_LIST)
+            .get_reply()?
+            .atom();
+        let atom_net_wm_name = xcb::xproto::intern_atom(&conn, false, NET_WM_NAME)
+            .get_reply()?
+            .atom();
+        let atom_wm_window_role = xcb::xproto::intern_atom(&conn, false, WM_WINDOW_ROLE)
+            .get_reply()?
+            .atom();
 
-        Ok(Self {
+        let mut me = Self {
             conn,
+            display: display.map(String::from),
             root_window,
             atom_net_wm_state,
             atom_net_wm_state_fullscreen,
-        })
+            atom_net_wm_state_hidden,
+            atom_net_wm_window_type,
+            atom_net_wm_window_type_desktop,
+            atom_net_current_desktop,
+            atom_net_wm_desktop,
+            atom_net_client_list,
+            atom_net_wm_name,
+            atom_wm_window_role,
+            idle_counter: None,
+            xinput: None,
+            atom_net_active_window: None,
+            last_window_change: Cell::new(None),
+            idle_offset: Cell::new(None),
+        };
+        me.probe_idle_source();
+        Ok(me)
     }
-    /// Get the user's idle time using the `XScreenSaver` plugin
+    /// Whether the X server supports this extension. A request to an
+    /// extension that isn't there breaks the connection, so this must
+    /// be checked first.
+    fn has_extension(&self, ext: &mut xcb::Extension) -> bool {
+        self.conn
+            .get_extension_data(ext)
+            .is_some_and(|data| data.present())
+    }
+    /// Describe why an X request failed, such as `SYNC QueryCounter
+    /// failed: BadCounter (X error code 154)`. Errors that aren't part
+    /// of the core protocol are looked up in `ext_errors`, the errors
+    /// of the extension the request belongs to.
+    fn request_error(
+        &self,
+        request: &str,
+        err: &xcb::GenericError,
+        ext_errors: Option<(&mut xcb::Extension, &[&str])>,
+    ) -> Error {
+        let code = err.error_code();
+        let name = CORE_ERRORS.get(usize::from(code)).copied().or_else(|| {
+            let (ext, names) = ext_errors?;
+            let first_error = self.conn.get_extension_data(ext)?.first_error();
+            names
+                .get(usize::from(code.checked_sub(first_error)?))
+                .copied()
+        });
+        format!(
+            "{} failed: {} (X error code {})",
+            request,
+            name.unwrap_or("unknown error"),
+            code
+        )
+        .into()
+    }
+    /// Select the first idle source that can read the idle time. If
+    /// none can, leave the default, and let `get_idle` report why.
+    fn probe_idle_source(&mut self) {
+        match self.get_idle_screensaver() {
+            Ok(_) => {
+                debug!("Reading the idle time from the screensaver extension");
+                return;
+            },
+            Err(err) => debug!("Can't read the idle time from the screensaver extension: {err}"),
+        }
+        let result = self.set_idle_source(IdleSource::Sync).and_then(|()| {
+            let counter = self.idle_counter.ok_or("no IDLETIME counter")?;
+            self.get_idle_sync(counter)
+        });
+        match result {
+            Ok(_) => info!(
+                "The screensaver extension doesn't work, reading the idle time from the SYNC \
+                 extension instead"
+            ),
+            Err(err) => {
+                warn!("Can't read the idle time from the SYNC extension either: {err}");
+                self.idle_counter = None;
+            },
+        }
+    }
+    /// The underlying X connection, for modules that need to make
+    /// their own X requests without opening a second connection.
+    ///
+    /// The connection is owned by this `Xcb` and lives as long as it
+    /// does. To use it from a module, share the `Xcb` instance with an
+    /// `Rc`, just like `not_when_fullscreen` does. As the connection is
+    /// shared, don't consume events from it, change its error
+    /// handling, or do anything else that other users of it don't
+    /// expect. Plain requests and replies are fine. The `xcb` crate
+    /// version must match the one this crate uses. Note that after
+    /// `watch_active_window`, this `Xcb` consumes any pending events
+    /// itself.
+    pub fn connection(&self) -> &xcb::Connection {
+        &self.conn
+    }
+    /// The root window of the first screen, which is the one every
+    /// other method operates on
+    pub fn root_window(&self) -> xcb::Window {
+        self.root_window
+    }
+    /// Return an error if the connection to the X server is broken,
+    /// such as when the X session has ended. Replies received on a
+    /// broken connection are empty, so this must be checked before
+    /// using them.
+    ///
+    /// # Errors
+    ///
+    /// If the connection is broken
+    pub fn check_connection(&self) -> Result<()> {
+        self.conn
+            .has_error()
+            .map_err(|err| format!("lost the connection to the X server: {err:?}").into())
+    }
+    /// Get whether or not the connection to the X server still works
+    pub fn is_connected(&self) -> bool {
+        self.check_connection().is_ok()
+    }
+    /// Count changes of the active window as user activity, even when
+    /// they didn't come from the keyboard or mouse (such as when a
+    /// script switches windows). This makes `get_idle` return at most
+    /// the time since the active window last changed.
+    ///
+    /// This selects property change events on the root window, which
+    /// `get_idle` then consumes from the connection.
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be asked
+    pub fn watch_active_window(&mut self) -> Result<()> {
+        let atom = xcb::xproto::intern_atom(&self.conn, false, NET_ACTIVE_WINDOW)
+            .get_reply()?
+            .atom();
+        xcb::xproto::change_window_attributes_checked(
+            &self.conn,
+            self.root_window,
+            &[(
+                xcb::xproto::CW_EVENT_MASK,
+                xcb::xproto::EVENT_MASK_PROPERTY_CHANGE,
+            )],
+        )
+        .request_check()?;
+        self.atom_net_active_window = Some(atom);
+        Ok(())
+    }
+    /// Check for changes of the active window since the last call, if
+    /// watching for them, and limit the idle time accordingly
+    fn apply_window_changes(&self, idle: Duration) -> Duration {
+        let Some(atom) = self.atom_net_active_window else {
+            return idle;
+        };
+        while let Some(event) = self.conn.poll_for_event() {
+            // The highest bit tells whether the event was sent by another client
+            if event.response_type() & !0x80 != xcb::xproto::PROPERTY_NOTIFY {
+                continue;
+            }
+            let event: &xcb::xproto::PropertyNotifyEvent = unsafe { xcb::cast_event(&event) };
+            if event.atom() == atom {
+                debug!("The active window changed, counting it as activity");
+                self.last_window_change.set(Some(Instant::now()));
+            }
+        }
+        match self.last_window_change.get() {
+            Some(time) => cmp::min(idle, time.elapsed()),
+            None => idle,
+        }
+    }
+    /// Look up the `IDLETIME` counter of the `SYNC` extension
+    fn find_idle_counter(&self) -> Result<xcb::sync::Counter> {
+        if !self.has_extension(xcb::sync::id()) {
+            return Err("the X server has no SYNC extension".into());
+        }
+        xcb::sync::initialize(&self.conn, 3, 1)
+            .get_reply()
+            .map_err(|err| self.request_error("SYNC Initialize", &err, None))?;
+        let counters = xcb::sync::list_system_counters(&self.conn)
+            .get_reply()
+            .map_err(|err| self.request_error("SYNC ListSystemCounters", &err, None))?;
+        let counter = counters
+            .counters()
+            .find(|counter| counter.name() == IDLETIME_COUNTER)
+            .ok_or("the X server has no IDLETIME counter")?
+            .counter();
+        Ok(counter)
+    }
+    /// Select where the idle time is read from. `IdleSource::XInput`
+    /// opens a second connection to the X server, and fails if it
+    /// doesn't support `XInput` 2, in which case the idle source is left
+    /// as it was.
+    ///
+    /// # Errors
+    ///
+    /// If the X server doesn't support the idle source
+    pub fn set_idle_source(&mut self, source: IdleSource) -> Result<()> {
+        self.xinput = match source {
+            IdleSource::ScreenSaver => {
+                self.idle_counter = None;
+                None
+            },
+            IdleSource::Sync => {
+                self.idle_counter = Some(self.find_idle_counter()?);
+                None
+            },
+            // Carries on from the idle time so far, so that it isn't reset by switching
+            IdleSource::XInput(classes) => Some(XInputIdle::new(
+                self.display.as_deref(),
+                classes,
+                self.get_counter_idle()?,
+            )?),
+        };
+        Ok(())
+    }
+    /// Get the currently selected idle source
+    pub fn idle_source(&self) -> IdleSource {
+        if let Some(ref xinput) = self.xinput {
+            IdleSource::XInput(xinput.classes())
+        } else if self.idle_counter.is_some() {
+            IdleSource::Sync
+        } else {
+            IdleSource::ScreenSaver
+        }
+    }
+    /// Pretend that the user had already been idle for this long
+    /// before the idle time was first read, until they are active.
+    /// This makes it possible to try out long timers without waiting,
+    /// or to continue an idle period from before a restart.
+    pub fn set_initial_idle(&mut self, offset: Duration) {
+        self.idle_offset.set(Some((offset, Duration::default())));
+    }
+    /// Add the initial idle time, unless the user was active since
+    fn apply_idle_offset(&self, idle: Duration) -> Duration {
+        let Some((offset, previous)) = self.idle_offset.get() else {
+            return idle;
+        };
+        if idle < previous {
+            debug!("The user was active, no longer adding the initial idle time");
+            self.idle_offset.set(None);
+            return idle;
+        }
+        self.idle_offset.set(Some((offset, idle)));
+        idle.checked_add(offset).unwrap_or(idle)
+    }
+    /// Get the user's idle time using the selected idle source (see
+    /// `set_idle_source`)
     pub fn get_idle(&self) -> Result<Duration> {
-        let info = xcb::screensaver::query_info(&self.conn, self.root_window).get_reply()?;
+        let idle = match self.xinput {
+            Some(ref xinput) => xinput.get_idle()?,
+            None => self.get_counter_idle()?,
+        };
+        Ok(self.apply_window_changes(self.apply_idle_offset(idle)))
+    }
+    /// Get the user's idle time from the screensaver extension or the
+    /// `IDLETIME` counter, whichever is selected, regardless of any
+    /// raw input events
+    fn get_counter_idle(&self) -> Result<Duration> {
+        match self.idle_counter {
+            Some(counter) => self.get_idle_sync(counter),
+            None => self.get_idle_screensaver(),
+        }
+    }
+    /// Get the user's idle time using the `XScreenSaver` plugin
+    fn get_idle_screensaver(&self) -> Result<Duration> {
+        if !self.has_extension(xcb::screensaver::id()) {
+            return Err("the X server has no MIT-SCREEN-SAVER extension".into());
+        }
+        let info = xcb::screensaver::query_info(&self.conn, self.root_window)
+            .get_reply()
+            .map_err(|err| self.request_error("MIT-SCREEN-SAVER QueryInfo", &err, None))?;
+        self.check_connection()
+            .map_err(|err| format!("MIT-SCREEN-SAVER QueryInfo failed: {err}"))?;
         Ok(Duration::from_millis(info.ms_since_user_input().into()))
     }
-    /// Get whether or not the user's currently active window is
-    /// fullscreen
-    pub fn get_fullscreen(&self) -> Result<bool> {
-        let focused_window = xcb::xproto::get_input_focus(&self.conn)
-            .get_reply()?
-            .focus();
+    /// Get the user's idle time using the `IDLETIME` counter, which
+    /// counts milliseconds
+    fn get_idle_sync(&self, counter: xcb::sync::Counter) -> Result<Duration> {
+        let reply = xcb::sync::query_counter(&self.conn, counter)
+            .get_reply()
+            .map_err(|err| {
+                self.request_error(
+                    "SYNC QueryCounter",
+                    &err,
+                    Some((xcb::sync::id(), SYNC_ERRORS)),
+                )
+            })?;
+        self.check_connection()
+            .map_err(|err| format!("SYNC QueryCounter failed: {err}"))?;
+        let value = reply.counter_value();
+        let millis = (i64::from(value.hi()) << 32) | i64::from(value.lo());
+        // The counter should never be negative, but don't trust the X server blindly
+        Ok(Duration::from_millis(millis.try_into().unwrap_or_default()))
+    }
+    /// Get the X server's own screensaver settings, like `xset q`
+    /// shows them
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be asked
+    pub fn get_screensaver(&self) -> Result<ScreenSaver> {
+        let reply = xcb::xproto::get_screen_saver(&self.conn).get_reply()?;
+        self.check_connection()?;
+        Ok(ScreenSaver {
+            timeout: Duration::from_secs(reply.timeout().into()),
+            interval: Duration::from_secs(reply.interval().into()),
+        })
+    }
+    /// Read a single `CARDINAL` property of a window, if it is set
+    fn get_cardinal(&self, window: xcb::Window, property: xcb::Atom) -> Result<Option<u32>> {
+        let prop = xcb::xproto::get_property(
+            &self.conn,                 // c
+            false,                      // delete
+            window,                     // window
+            property,                   // property
+            xcb::xproto::ATOM_CARDINAL, // type_
+            0,                          // long_offset
+            1,                          // long_length
+        )
+        .get_reply()?;
+        self.check_connection()?;
+
+        if prop.format() != 32 {
+            return Ok(None);
+        }
+        Ok(prop.value::<u32>().first().copied())
+    }
+    /// Read an `ATOM` list property of a window, empty if it isn't set
+    fn get_atoms(&self, window: xcb::Window, property: xcb::Atom) -> Result<Vec<xcb::Atom>> {
         let prop = xcb::xproto::get_property(
             &self.conn,             // c
             false,                  // delete
-            focused_window,         // window
-            self.atom_net_wm_state, // property
+            window,                 // window
+            property,               // property
             xcb::xproto::ATOM_ATOM, // type_
             0,                      // long_offset
-            u32::max_value(),       // long_length
+            MAX_PROPERTY_LEN,       // long_length
         )
         .get_reply()?;
+        self.check_connection()?;
 
-        // The safe API can't possibly know what value xcb returned,
-        // sadly. Here we are manually transmuting &[c_void] to
-        // &[Atom], as we specified we want an atom.
-        let value = prop.value();
+        if prop.format() != 32 {
+            return Ok(Vec::new());
+        }
+        Ok(prop.value::<xcb::Atom>().to_vec())
+    }
+    /// Get the windows the window manager lists in `_NET_CLIENT_LIST`,
+    /// unless it doesn't
+    fn get_client_list(&self) -> Result<Option<Vec<xcb::Window>>> {
+        let prop = xcb::xproto::get_property(
+            &self.conn,                // c
+            false,                     // delete
+            self.root_window,          // window
+            self.atom_net_client_list, // property
+            xcb::xproto::ATOM_WINDOW,  // type_
+            0,                         // long_offset
+            MAX_PROPERTY_LEN,          // long_length
+        )
+        .get_reply()?;
+        self.check_connection()?;
 
-        debug!("xcb::xproto::get_property(...) = {:?}", value);
-        debug!(
-            "NET_WM_STATE_FULLSCREEN = {:?}",
-            self.atom_net_wm_state_fullscreen
-        );
+        if prop.format() != 32 {
+            return Ok(None);
+        }
+        Ok(Some(prop.value::<xcb::Window>().to_vec()))
+    }
+    /// Read a text property of a window, if it is set. Invalid UTF-8 is
+    /// replaced, which is fine for comparing against known values.
+    fn get_text(&self, window: xcb::Window, property: xcb::Atom) -> Result<Option<String>> {
+        let prop = xcb::xproto::get_property(
+            &self.conn,            // c
+            false,                 // delete
+            window,                // window
+            property,              // property
+            xcb::xproto::ATOM_ANY, // type_
+            0,                     // long_offset
+            MAX_PROPERTY_LEN,      // long_length
+        )
+        .get_reply()?;
+        self.check_connection()?;
 
-        let value = unsafe {
-            slice::from_raw_parts(value.as_ptr() as *const xcb::xproto::Atom, value.len())
+        if prop.format() != 8 {
+            return Ok(None);
+        }
+        Ok(Some(
+            String::from_utf8_lossy(prop.value::<u8>()).into_owned(),
+        ))
+    }
+    /// Get the `WM_CLASS` of a window: its instance name and class
+    /// name, such as `["i3lock", "i3lock"]`. Empty if it isn't set.
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be asked about the window
+    pub fn get_class(&self, window: xcb::Window) -> Result<Vec<String>> {
+        let class = self
+            .get_text(window, xcb::xproto::ATOM_WM_CLASS)?
+            .unwrap_or_default();
+        Ok(class
+            .split('\0')
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect())
+    }
+    /// Get whether a window is a picture-in-picture video, going by
+    /// its role or title
+    fn is_pip(&self, window: xcb::Window) -> Result<bool> {
+        if let Some(role) = self.get_text(window, self.atom_wm_window_role)? {
+            if PIP_ROLES.contains(&role.as_str()) {
+                return Ok(true);
+            }
+        }
+        let title = match self.get_text(window, self.atom_net_wm_name)? {
+            Some(title) => Some(title),
+            None => self.get_text(window, xcb::xproto::ATOM_WM_NAME)?,
         };
-
-        for &atom in value {
-            if atom == self.atom_net_wm_state_fullscreen {
+        Ok(title.is_some_and(|title| PIP_TITLES.contains(&title.as_str())))
+    }
+    /// Get whether there's a picture-in-picture video window, as
+    /// browsers can open, on the currently visible desktop. This needs
+    /// a window manager that lists its windows in `_NET_CLIENT_LIST`.
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be asked about the windows
+    pub fn get_pip(&self) -> Result<bool> {
+        let Some(windows) = self.get_client_list()? else {
+            debug!("The window manager doesn't list its windows, can't look for PiP windows");
+            return Ok(false);
+        };
+        for window in windows {
+            if self.is_pip(window)? && self.is_on_current_desktop(window)? {
+                debug!("Window {window} is a picture-in-picture video");
                 return Ok(true);
             }
         }
         Ok(false)
     }
+    /// Get the names of the connected outputs (monitors), such as
+    /// `["eDP-1", "HDMI-1"]`, using the `RandR` extension
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be asked
+    pub fn get_connected_outputs(&self) -> Result<Vec<String>> {
+        if !self.has_extension(xcb::randr::id()) {
+            return Err("the X server has no RandR extension".into());
+        }
+        // Announce the version that has `get_screen_resources_current`
+        xcb::randr::query_version(&self.conn, 1, 3).get_reply()?;
+        let resources =
+            xcb::randr::get_screen_resources_current(&self.conn, self.root_window).get_reply()?;
+        self.check_connection()?;
+
+        let mut outputs = Vec::new();
+        for &output in resources.outputs() {
+            let info =
+                xcb::randr::get_output_info(&self.conn, output, resources.config_timestamp())
+                    .get_reply()?;
+            if u32::from(info.connection()) == xcb::randr::CONNECTION_CONNECTED {
+                outputs.push(String::from_utf8_lossy(info.name()).into_owned());
+            }
+        }
+        self.check_connection()?;
+        Ok(outputs)
+    }
+    /// Get the index of the currently visible desktop (workspace), if
+    /// the window manager supports it
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be asked
+    pub fn get_current_desktop(&self) -> Result<Option<u32>> {
+        self.get_cardinal(self.root_window, self.atom_net_current_desktop)
+    }
+    /// Get whether or not a window is on the currently visible
+    /// desktop. If the window manager doesn't tell which desktops
+    /// windows are on, this assumes it is.
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be asked about the window
+    pub fn is_on_current_desktop(&self, window: xcb::Window) -> Result<bool> {
+        let Some(current) = self.get_current_desktop()? else {
+            return Ok(true);
+        };
+        match self.get_cardinal(window, self.atom_net_wm_desktop)? {
+            Some(desktop) => {
+                debug!("Window desktop: {desktop}, current desktop: {current}");
+                Ok(desktop == current || desktop == ALL_DESKTOPS)
+            },
+            None => Ok(true),
+        }
+    }
+    /// Get what's known about a window when looking for fullscreen
+    /// windows
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be asked about the window
+    pub fn get_window_state(&self, window: xcb::Window) -> Result<WindowState> {
+        let state = self.get_atoms(window, self.atom_net_wm_state)?;
+        let fullscreen = state.contains(&self.atom_net_wm_state_fullscreen);
+        if !fullscreen {
+            // Nothing else matters then, so save the round trips
+            return Ok(WindowState::default());
+        }
+        let types = self.get_atoms(window, self.atom_net_wm_window_type)?;
+        Ok(WindowState {
+            fullscreen,
+            hidden: state.contains(&self.atom_net_wm_state_hidden),
+            desktop: types.contains(&self.atom_net_wm_window_type_desktop),
+            on_current_desktop: self.is_on_current_desktop(window)?,
+        })
+    }
+    /// Get whether or not there's a fullscreen window, see
+    /// `WindowState::counts_as_fullscreen` for which windows count
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be asked about the windows
+    pub fn get_fullscreen(&self) -> Result<bool> {
+        Ok(self.get_fullscreen_window()?.is_some())
+    }
+    /// Like `get_fullscreen`, but return the fullscreen window, if any.
+    /// The focused window is checked first, and then every window the
+    /// window manager lists in `_NET_CLIENT_LIST`, if it does.
+    ///
+    /// # Errors
+    ///
+    /// If the X server can't be asked about the windows
+    pub fn get_fullscreen_window(&self) -> Result<Option<xcb::Window>> {
+        let focus = xcb::xproto::get_input_focus(&self.conn).get_reply()?;
+        self.check_connection()?;
+        let focused_window = focus.focus();
+
+        let mut windows = vec![focused_window];
+        windows.extend(
+            self.get_client_list()?
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|&window| window != focused_window),
+        );
+        for window in windows {
+            let state = self.get_window_state(window)?;
+            debug!("Window {window}: {state:?}");
+            if state.counts_as_fullscreen() {
+                return Ok(Some(window));
+            }
+        }
+        Ok(None)
+    }
 
     /// Return a `NotWhenFullscreen` instance for a reference-counted
     /// self
     pub fn not_when_fullscreen(self: Rc<Self>) -> NotWhenFullscreen {
-        NotWhenFullscreen { xcb: self }
+        NotWhenFullscreen {
+            xcb: self,
+            grace: Duration::default(),
+            pip: false,
+            ignored_classes: Vec::new(),
+            last_fullscreen: None,
+        }
     }
 }
+/// What's known about a window when looking for fullscreen windows, see
+/// `Xcb::get_window_state`
+#[allow(clippy::struct_excessive_bools)] // independent properties of a window
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WindowState {
+    /// It's in the `_NET_WM_STATE_FULLSCREEN` state
+    pub fullscreen: bool,
+    /// It's minimized, in the `_NET_WM_STATE_HIDDEN` state
+    pub hidden: bool,
+    /// It's the desktop background, of the `_NET_WM_WINDOW_TYPE_DESKTOP`
+    /// type, which some desktop environments make fullscreen
+    pub desktop: bool,
+    /// It's on the currently visible desktop, see
+    /// `Xcb::is_on_current_desktop`
+    pub on_current_desktop: bool,
+}
+impl WindowState {
+    /// Whether the window counts as fullscreen: Only a fullscreen
+    /// window that can be seen does, so not one that's minimized, on
+    /// another desktop, or the desktop background.
+    pub fn counts_as_fullscreen(self) -> bool {
+        self.fullscreen && !self.hidden && !self.desktop && self.on_current_desktop
+    }
+}
+
+/// The X server's built-in screensaver settings. A duration of zero
+/// means that part is disabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScreenSaver {
+    /// How long the user must be idle before the server activates
+    /// the screensaver
+    pub timeout: Duration,
+    /// How often the server changes the screensaver's pattern, the
+    /// "cycle" in `xset q`
+    pub interval: Duration,
+}
+
 impl fmt::Debug for Xcb {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Xcb")
@@ -104,16 +787,96 @@ impl fmt::Debug for Xcb {
 /// See the module-level documentation
 pub struct NotWhenFullscreen {
     xcb: Rc<Xcb>,
+    grace: Duration,
+    /// Whether picture-in-picture windows count as fullscreen
+    pip: bool,
+    /// Fullscreen windows with any of these classes don't count
+    ignored_classes: Vec<String>,
+    last_fullscreen: Option<Instant>,
+}
+impl NotWhenFullscreen {
+    /// Keep inhibiting timers for this long after the application
+    /// stops being fullscreen. This avoids flapping when fullscreen
+    /// is toggled rapidly, such as when seeking in some video
+    /// players. The default is no grace period at all.
+    #[must_use]
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+    /// Also inhibit timers while there's a picture-in-picture video
+    /// window (see `Xcb::get_pip`), which keeps playing video without
+    /// being fullscreen
+    #[must_use]
+    pub fn with_pip(mut self, pip: bool) -> Self {
+        self.pip = pip;
+        self
+    }
+    /// Don't count fullscreen windows of this class (or instance name,
+    /// see `Xcb::get_class`), ignoring case. Useful for a lock screen
+    /// that goes fullscreen, which would otherwise keep any timers
+    /// after it from running, such as one to suspend.
+    #[must_use]
+    pub fn with_ignored_class(mut self, class: String) -> Self {
+        self.ignored_classes.push(class);
+        self
+    }
+
+    fn is_fullscreen(&self) -> Result<bool> {
+        let Some(window) = self.xcb.get_fullscreen_window()? else {
+            return Ok(false);
+        };
+        if self.ignored_classes.is_empty() {
+            return Ok(true);
+        }
+        let class = self.xcb.get_class(window)?;
+        let ignored = class.iter().any(|name| {
+            self.ignored_classes
+                .iter()
+                .any(|ignored| ignored.eq_ignore_ascii_case(name))
+        });
+        if ignored {
+            debug!("Ignoring fullscreen window of class {class:?}");
+        }
+        Ok(!ignored)
+    }
+
+    /// Whether fullscreen ended less than the grace period ago
+    fn in_grace(&self, now: Instant) -> bool {
+        self.last_fullscreen
+            .is_some_and(|time| now.saturating_duration_since(time) < self.grace)
+    }
 }
 impl Module for NotWhenFullscreen {
     fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
-        self.xcb.get_fullscreen().map(|fullscreen| {
-            if fullscreen {
-                Progress::Abort
-            } else {
-                Progress::Continue
-            }
-        })
+        let now = Instant::now();
+        if self.is_fullscreen()? || (self.pip && self.xcb.get_pip()?) {
+            self.last_fullscreen = Some(now);
+            return Ok(Progress::Abort);
+        }
+
+        if self.in_grace(now) {
+            debug!("Fullscreen ended recently, still inhibiting");
+            Ok(Progress::Abort)
+        } else {
+            self.last_fullscreen = None;
+            Ok(Progress::Continue)
+        }
+    }
+    fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        if self.is_fullscreen()? || (self.pip && self.xcb.get_pip()?) {
+            return Ok(Progress::Abort);
+        }
+        if self.in_grace(Instant::now()) {
+            Ok(Progress::Abort)
+        } else {
+            Ok(Progress::Continue)
+        }
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.last_fullscreen = None;
+        Ok(())
     }
 }
 impl fmt::Debug for NotWhenFullscreen {
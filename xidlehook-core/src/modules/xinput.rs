@@ -0,0 +1,357 @@
+//! Reads the idle time from the X server's raw input events, as
+//! delivered by the `XInput` 2 extension, so that only some kinds of input
+//! count as activity. Unlike the screensaver extension's counter, which
+//! is reset by any input at all, this can ignore a drifting trackpoint
+//! or a mouse that's bumped by accident. The xcb crate's `XInput` bindings
+//! don't build, and only two requests are needed, so they're sent
+//! directly. This is used to implement `--reset-on` in the xidlehook
+//! example application.
+
+use crate::{Error, Result};
+
+use std::{
+    convert::TryFrom,
+    fmt,
+    os::{raw::c_void, unix::io::AsRawFd},
+    ptr,
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, trace};
+use nix::libc::{self, c_int, c_uint};
+
+const EXTENSION_NAME: &str = "XInputExtension";
+
+/// The version asked for. Since 2.1, raw events are delivered even
+/// while another client has grabbed the device.
+const VERSION: (u16, u16) = (2, 2);
+
+// Request opcodes
+const XI_SELECT_EVENTS: u8 = 46;
+const XI_QUERY_VERSION: u8 = 47;
+
+/// Select the events of every device, master or slave. Input may be
+/// reported twice, which doesn't matter here.
+const XI_ALL_DEVICES: u16 = 0;
+
+// Event types
+const XI_RAW_KEY_PRESS: u16 = 13;
+const XI_RAW_BUTTON_PRESS: u16 = 15;
+const XI_RAW_MOTION: u16 = 17;
+
+/// The response type of generic events, which extensions use for
+/// events of their own
+const GE_GENERIC: u8 = 35;
+
+/// `XCB_REQUEST_CHECKED`, to get errors as replies
+const REQUEST_CHECKED: c_int = 1;
+
+/// `xcb_protocol_request_t`, which the xcb crate doesn't export
+#[repr(C)]
+struct ProtocolRequest {
+    count: usize,
+    ext: *mut c_void,
+    opcode: u8,
+    isvoid: u8,
+}
+
+/// The start of every generic event, `xcb_ge_generic_event_t`
+#[repr(C)]
+struct GenericEvent {
+    response_type: u8,
+    extension: u8,
+    _sequence: u16,
+    _length: u32,
+    event_type: u16,
+}
+
+extern "C" {
+    fn xcb_send_request(
+        c: *mut xcb::ffi::xcb_connection_t,
+        flags: c_int,
+        vector: *mut libc::iovec,
+        request: *const ProtocolRequest,
+    ) -> c_uint;
+    fn xcb_wait_for_reply(
+        c: *mut xcb::ffi::xcb_connection_t,
+        request: c_uint,
+        e: *mut *mut xcb::ffi::xcb_generic_error_t,
+    ) -> *mut c_void;
+}
+
+/// Which kinds of input count as activity
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InputClasses {
+    /// Pressing a key
+    pub keyboard: bool,
+    /// Pressing a mouse button, including scrolling and tapping a
+    /// touchpad
+    pub button: bool,
+    /// Moving the pointer
+    pub motion: bool,
+}
+impl InputClasses {
+    /// The `XInput` 2 event mask for these classes
+    fn mask(self) -> u32 {
+        let bit = |selected, event_type| if selected { 1u32 << event_type } else { 0 };
+        bit(self.keyboard, XI_RAW_KEY_PRESS)
+            | bit(self.button, XI_RAW_BUTTON_PRESS)
+            | bit(self.motion, XI_RAW_MOTION)
+    }
+}
+impl FromStr for InputClasses {
+    type Err = String;
+
+    /// Parse a comma-separated list, such as "keyboard,button"
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut classes = Self::default();
+        for class in s.split(',') {
+            match class.trim() {
+                "keyboard" => classes.keyboard = true,
+                "button" => classes.button = true,
+                "motion" => classes.motion = true,
+                other => {
+                    return Err(format!(
+                        "unknown input class {other:?}, expected keyboard, button or motion"
+                    ))
+                },
+            }
+        }
+        Ok(classes)
+    }
+}
+
+/// Send an `XInput` request. The first 4 bytes of `request` are left for
+/// the header, which xcb fills in except for the minor opcode.
+fn send_request(
+    conn: &xcb::Connection,
+    major_opcode: u8,
+    request: &mut [u8],
+    isvoid: bool,
+) -> c_uint {
+    let protocol_request = ProtocolRequest {
+        count: 2,
+        ext: ptr::null_mut(),
+        opcode: major_opcode,
+        isvoid: isvoid.into(),
+    };
+    let empty = libc::iovec {
+        iov_base: ptr::null_mut(),
+        iov_len: 0,
+    };
+    // xcb needs two vectors before the request for itself, and one after it for padding
+    let mut vector = [
+        empty,
+        empty,
+        libc::iovec {
+            iov_base: request.as_mut_ptr().cast(),
+            iov_len: request.len(),
+        },
+        empty,
+    ];
+    unsafe {
+        xcb_send_request(
+            conn.get_raw_conn(),
+            REQUEST_CHECKED,
+            vector[2..].as_mut_ptr(),
+            ptr::addr_of!(protocol_request),
+        )
+    }
+}
+
+/// Take the error of a request, if it failed
+fn request_error(request: &str, err: *mut xcb::ffi::xcb_generic_error_t) -> Error {
+    let err = xcb::GenericError { ptr: err };
+    format!("{} failed (X error code {})", request, err.error_code()).into()
+}
+
+/// Announce that `XInput` 2 is supported, which the X server requires
+/// before selecting any `XInput` 2 events
+fn query_version(conn: &xcb::Connection, major_opcode: u8) -> Result<(u16, u16)> {
+    let mut request = [0; 8];
+    request[1] = XI_QUERY_VERSION;
+    request[4..6].copy_from_slice(&VERSION.0.to_ne_bytes());
+    request[6..8].copy_from_slice(&VERSION.1.to_ne_bytes());
+    let sequence = send_request(conn, major_opcode, &mut request, false);
+
+    let mut err = ptr::null_mut();
+    let reply =
+        unsafe { xcb_wait_for_reply(conn.get_raw_conn(), sequence, ptr::addr_of_mut!(err)) };
+    if !err.is_null() {
+        return Err(request_error("XIQueryVersion", err));
+    }
+    if reply.is_null() {
+        return Err("XIQueryVersion failed: lost the connection to the X server".into());
+    }
+    let version = unsafe {
+        let bytes = reply.cast::<u8>();
+        let version = (
+            u16::from_ne_bytes([*bytes.add(8), *bytes.add(9)]),
+            u16::from_ne_bytes([*bytes.add(10), *bytes.add(11)]),
+        );
+        libc::free(reply);
+        version
+    };
+    Ok(version)
+}
+
+/// Ask for the raw events in `mask` on the root window
+fn select_events(
+    conn: &xcb::Connection,
+    major_opcode: u8,
+    root_window: xcb::Window,
+    mask: u32,
+) -> Result<()> {
+    let mut request = [0; 20];
+    request[1] = XI_SELECT_EVENTS;
+    request[4..8].copy_from_slice(&root_window.to_ne_bytes());
+    // One mask, of one 32-bit unit
+    request[8..10].copy_from_slice(&1u16.to_ne_bytes());
+    request[12..14].copy_from_slice(&XI_ALL_DEVICES.to_ne_bytes());
+    request[14..16].copy_from_slice(&1u16.to_ne_bytes());
+    request[16..20].copy_from_slice(&mask.to_ne_bytes());
+    let sequence = send_request(conn, major_opcode, &mut request, true);
+
+    let err = unsafe {
+        xcb::ffi::xcb_request_check(
+            conn.get_raw_conn(),
+            xcb::ffi::xcb_void_cookie_t { sequence },
+        )
+    };
+    if err.is_null() {
+        Ok(())
+    } else {
+        Err(request_error("XISelectEvents", err))
+    }
+}
+
+/// What the event thread last saw
+struct State {
+    /// When the user last gave input of the chosen classes
+    last_input: Instant,
+    /// Why the connection stopped working, if it did
+    error: Option<String>,
+}
+
+/// See the module-level documentation
+pub struct XInputIdle {
+    classes: InputClasses,
+    /// The socket of the connection, to make the event thread exit
+    fd: c_int,
+    state: Arc<Mutex<State>>,
+}
+impl XInputIdle {
+    /// Open a second connection to this display (such as ":1"), or the
+    /// one in `$DISPLAY`, and count only input of these classes as
+    /// activity. The idle time starts out at `idle`, such as the idle
+    /// time of the screensaver extension. This fails if the X server
+    /// doesn't support `XInput` 2.
+    ///
+    /// # Errors
+    ///
+    /// If the display can't be connected to, or doesn't support `XInput` 2
+    pub fn new(display: Option<&str>, classes: InputClasses, idle: Duration) -> Result<Self> {
+        if classes == InputClasses::default() {
+            return Err("no input classes to count as activity".into());
+        }
+        let (conn, screen_num) = xcb::Connection::connect(display)
+            .map_err(|err| format!("can't connect to the X display for XInput: {err}"))?;
+        let setup = conn.get_setup();
+        let root_window = usize::try_from(screen_num)
+            .ok()
+            .and_then(|num| setup.roots().nth(num))
+            .ok_or("no xcb root")?
+            .root();
+
+        let extension = xcb::xproto::query_extension(&conn, EXTENSION_NAME).get_reply()?;
+        if !extension.present() {
+            return Err("the X server has no XInputExtension".into());
+        }
+        let major_opcode = extension.major_opcode();
+        let version = query_version(&conn, major_opcode)?;
+        if version.0 < 2 {
+            return Err(format!(
+                "the X server only supports XInput {}.{}, not 2",
+                version.0, version.1
+            )
+            .into());
+        }
+        select_events(&conn, major_opcode, root_window, classes.mask())?;
+        debug!(
+            "Reading the idle time from XInput {}.{} raw events: {:?}",
+            version.0, version.1, classes
+        );
+
+        let state = Arc::new(Mutex::new(State {
+            last_input: Instant::now()
+                .checked_sub(idle)
+                .unwrap_or_else(Instant::now),
+            error: None,
+        }));
+        let fd = conn.as_raw_fd();
+        let thread_state = Arc::clone(&state);
+        // Events are received right away, as they only tell when the input happened by
+        // arriving
+        thread::spawn(move || loop {
+            let event = conn.wait_for_event();
+            let mut state = thread_state.lock().unwrap_or_else(PoisonError::into_inner);
+            let Some(event) = event else {
+                state.error = Some(String::from("lost the connection to the X server"));
+                return;
+            };
+            if event.response_type() & !0x80 != GE_GENERIC {
+                continue;
+            }
+            let event: &GenericEvent = unsafe { xcb::cast_event(&event) };
+            if event.extension == major_opcode {
+                trace!("XInput event of type {}", event.event_type);
+                state.last_input = Instant::now();
+            }
+        });
+
+        Ok(Self { classes, fd, state })
+    }
+
+    /// The classes of input that count as activity
+    pub fn classes(&self) -> InputClasses {
+        self.classes
+    }
+
+    /// Get the time since the last input of the chosen classes
+    ///
+    /// # Errors
+    ///
+    /// If the connection to the X server was lost
+    pub fn get_idle(&self) -> Result<Duration> {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(ref error) = state.error {
+            return Err(error.clone().into());
+        }
+        Ok(state.last_input.elapsed())
+    }
+
+    /// Get whether or not the connection to the X server still works
+    pub fn is_connected(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .error
+            .is_none()
+    }
+}
+impl Drop for XInputIdle {
+    fn drop(&mut self) {
+        // Makes the thread waiting for events exit
+        unsafe {
+            libc::shutdown(self.fd, libc::SHUT_RDWR);
+        }
+    }
+}
+impl fmt::Debug for XInputIdle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "XInputIdle({:?})", self.classes)
+    }
+}
@@ -1,7 +1,25 @@
 //! The timer trait and some useful implementations
 
-use crate::Result;
-use std::{process::Command, time::Duration};
+use crate::{
+    clock::{Clock, MonotonicClock},
+    Error, Module, Result, TimerInfo,
+};
+use log::{info, warn};
+use nix::{
+    libc,
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+use std::{
+    convert::TryFrom,
+    fmt, mem,
+    process::Command,
+    ptr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 /// The timer trait is used to tell xidlehook after how much idle time
 /// your timer should activate (relatively), and what activation
@@ -19,6 +37,18 @@ pub trait Timer {
         None
     }
 
+    /// Called whenever the chain starts over, such as when the user is
+    /// active again. No timer is counting down at that point, so this
+    /// is where to change how long the timer takes, such as to draw a
+    /// random duration.
+    fn reset(&mut self) {}
+
+    /// Called right before `activate`, `abort` or `deactivate`, with
+    /// where this timer is in the list. Indices change as timers are
+    /// added and removed, so use this to find out which one this is
+    /// when it runs, such as to tell a command.
+    fn prepare(&mut self, _timer: TimerInfo) {}
+
     /// Called when the timer was activated
     fn activate(&mut self) -> Result<()> {
         Ok(())
@@ -39,14 +69,112 @@ pub trait Timer {
     fn disabled(&mut self) -> bool {
         false
     }
+    /// A human-readable name or description of this timer, used in
+    /// log messages and such. Return `None` to refer to the timer by
+    /// its index instead.
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Errors that came up in the background since this was last
+    /// called, such as a command that had to be killed. They're passed
+    /// on to the module's `warning`.
+    fn take_warnings(&mut self) -> Vec<Error> {
+        Vec::new()
+    }
+
+    /// Modules that only apply to this timer. They're asked after the
+    /// global modules, in `Module::pre_timer` before this timer runs and
+    /// in `Module::post_timer` after, and reset with them.
+    fn module(&mut self) -> Option<&mut dyn Module> {
+        None
+    }
+}
+
+/// How long an activation command that timed out gets to exit after
+/// `SIGTERM`, before it's sent `SIGKILL`
+pub const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// How often to check whether a command with a timeout has exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether the process has exited, without reaping it, so that whoever
+/// handles `SIGCHLD` still gets its exit status. A process that was
+/// already reaped counts as exited.
+fn has_exited(pid: Pid) -> bool {
+    let Ok(id) = libc::id_t::try_from(pid.as_raw()) else {
+        return true;
+    };
+    let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+    let ret = unsafe {
+        libc::waitid(
+            libc::P_PID,
+            id,
+            ptr::addr_of_mut!(info),
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        )
+    };
+    // With WNOHANG, the PID is left at zero if the process is still running
+    ret != 0 || unsafe { info.si_pid() } != 0
+}
+
+/// Wait up to `timeout` for the process to exit, and return whether it
+/// did
+fn exits_within(pid: Pid, timeout: Duration) -> bool {
+    let start = Instant::now();
+    while !has_exited(pid) {
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return false;
+        }
+        thread::sleep(EXIT_POLL_INTERVAL.min(timeout.saturating_sub(elapsed)));
+    }
+    true
+}
+
+/// Wait up to `timeout` for the process to exit. If it doesn't, send it
+/// `SIGTERM`, and then `SIGKILL` after `KILL_GRACE`, and return the
+/// signal that it exited with.
+pub(crate) fn terminate_after(pid: Pid, timeout: Duration) -> Option<Signal> {
+    if exits_within(pid, timeout) {
+        return None;
+    }
+    let _ = signal::kill(pid, Signal::SIGTERM);
+    if exits_within(pid, KILL_GRACE) {
+        Some(Signal::SIGTERM)
+    } else {
+        let _ = signal::kill(pid, Signal::SIGKILL);
+        Some(Signal::SIGKILL)
+    }
+}
+
+/// What a `CmdTimer` does when its activation command fails to run.
+/// It can't tell whether the command exits unsuccessfully later on, as
+/// it doesn't wait for it, but an application that reaps it can do the
+/// same then.
+#[derive(Debug, Default)]
+pub enum OnFailure {
+    /// Log a warning and carry on
+    Ignore,
+    /// Log a warning and run this command instead
+    Fallback(Command),
+    /// Return the error, which stops xidlehook. This is the default.
+    #[default]
+    Exit,
 }
 
 /// A simple timer that runs a binary executable after a certain
 /// amount of time
-#[derive(Debug, Default)]
+#[derive(Default)]
 pub struct CmdTimer {
     /// The idle time required for this timer to activate
     pub time: Duration,
+    /// If set, `time` is drawn at random from this range, including
+    /// both ends, whenever the chain starts over, see `randomize`
+    pub range: Option<(Duration, Duration)>,
+    /// Where `range` is drawn from. Seed it to make the durations
+    /// repeatable, such as in tests.
+    pub rng: fastrand::Rng,
     /// The command, if any, to run upon activation
     pub activation: Option<Command>,
     /// The command, if any, to run upon abortion
@@ -55,6 +183,73 @@ pub struct CmdTimer {
     pub deactivation: Option<Command>,
     /// Whether or not to disable this timer
     pub disabled: bool,
+    /// The name of this timer, if any
+    pub name: Option<String>,
+    /// What to do if the activation command fails
+    pub on_failure: OnFailure,
+    /// The process ID of the last activation command, or `None` if it
+    /// couldn't be started
+    pub activation_pid: Option<u32>,
+    /// If the activation command is still running after this long,
+    /// send it `SIGTERM`, and then `SIGKILL` after `KILL_GRACE`. This is
+    /// reported as a warning.
+    pub timeout: Option<Duration>,
+    /// Activation commands that timed out, to be reported by
+    /// `take_warnings`
+    pub timed_out: Arc<Mutex<Vec<String>>>,
+    /// Modules that only apply to this timer, see `Timer::module`
+    pub modules: Vec<Box<dyn Module>>,
+    /// Only log the commands that would run, without running them
+    pub dry_run: bool,
+}
+impl fmt::Debug for CmdTimer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CmdTimer")
+            .field("time", &self.time)
+            .field("range", &self.range)
+            .field("rng", &self.rng)
+            .field("activation", &self.activation)
+            .field("abortion", &self.abortion)
+            .field("deactivation", &self.deactivation)
+            .field("disabled", &self.disabled)
+            .field("name", &self.name)
+            .field("on_failure", &self.on_failure)
+            .field("activation_pid", &self.activation_pid)
+            .field("timeout", &self.timeout)
+            .field("timed_out", &self.timed_out)
+            .field("modules", &self.modules.len())
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+impl CmdTimer {
+    /// Draw a new `time` from `range`, if it's set. This happens on its
+    /// own whenever the chain starts over, so only call it to draw the
+    /// first one.
+    pub fn randomize(&mut self) {
+        if let Some((min, max)) = self.range {
+            let spread = u64::try_from(max.saturating_sub(min).as_millis()).unwrap_or(u64::MAX);
+            self.time = min.saturating_add(Duration::from_millis(self.rng.u64(0..=spread)));
+        }
+    }
+
+    /// Kill the activation command with this process ID in the
+    /// background, if it's still running after `timeout`
+    fn kill_after(&self, pid: u32, timeout: Duration) {
+        let pid = match i32::try_from(pid) {
+            Ok(pid) => Pid::from_raw(pid),
+            Err(_) => return,
+        };
+        let timed_out = Arc::clone(&self.timed_out);
+        thread::spawn(move || {
+            let Some(signal) = terminate_after(pid, timeout) else {
+                return;
+            };
+            timed_out.lock().unwrap().push(format!(
+                "activation command (PID {pid}) was still running after {timeout:?}, killed it with {signal:?}"
+            ));
+        });
+    }
 }
 impl Timer for CmdTimer {
     fn time_left(&mut self, idle_time: Duration) -> Result<Option<Duration>> {
@@ -64,31 +259,83 @@ impl Timer for CmdTimer {
             .filter(|&dur| dur != Duration::default()))
     }
 
+    fn reset(&mut self) {
+        self.randomize();
+    }
+
     fn abort_urgency(&self) -> Option<Duration> {
         self.abortion.as_ref().map(|_| Duration::from_secs(1))
     }
 
     fn activate(&mut self) -> Result<()> {
         if let Some(ref mut activation) = self.activation {
-            activation.spawn()?;
+            self.activation_pid = None;
+            if self.dry_run {
+                info!("Would run the activation command {activation:?}");
+                return Ok(());
+            }
+            match activation.spawn() {
+                Ok(child) => {
+                    self.activation_pid = Some(child.id());
+                    if let Some(timeout) = self.timeout {
+                        self.kill_after(child.id(), timeout);
+                    }
+                },
+                Err(err) => match self.on_failure {
+                    OnFailure::Ignore => {
+                        warn!("Failed to run activation command: {err}");
+                    },
+                    OnFailure::Fallback(ref mut fallback) => {
+                        warn!("Failed to run activation command, running fallback: {err}");
+                        fallback.spawn()?;
+                    },
+                    OnFailure::Exit => return Err(err.into()),
+                },
+            }
         }
         Ok(())
     }
     fn abort(&mut self) -> Result<()> {
         if let Some(ref mut abortion) = self.abortion {
-            abortion.spawn()?;
+            if self.dry_run {
+                info!("Would run the abortion command {abortion:?}");
+            } else {
+                abortion.spawn()?;
+            }
         }
         Ok(())
     }
     fn deactivate(&mut self) -> Result<()> {
         if let Some(ref mut deactivation) = self.deactivation {
-            deactivation.spawn()?;
+            if self.dry_run {
+                info!("Would run the deactivation command {deactivation:?}");
+            } else {
+                deactivation.spawn()?;
+            }
         }
         Ok(())
     }
     fn disabled(&mut self) -> bool {
         self.disabled
     }
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    fn take_warnings(&mut self) -> Vec<Error> {
+        self.timed_out
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(Error::from)
+            .collect()
+    }
+    fn module(&mut self) -> Option<&mut dyn Module> {
+        if self.modules.is_empty() {
+            None
+        } else {
+            Some(&mut self.modules)
+        }
+    }
 }
 
 /// A timer that lets you easily execute a rust callback on
@@ -154,3 +401,437 @@ where
         self.disabled
     }
 }
+
+type TimeLeftFn<'a> = Box<dyn FnMut(Duration) -> Result<Option<Duration>> + 'a>;
+type EventFn<'a> = Box<dyn FnMut() -> Result<()> + 'a>;
+
+/// A timer made out of closures, for when writing a struct that
+/// implements `Timer` is too much ceremony. Unlike `CallbackTimer`,
+/// this can react to every event, and decide for itself how much time
+/// is left.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use xidlehook_core::timers::FnTimer;
+/// let timer = FnTimer::after(Duration::from_secs(60))
+///     .on_activate(|| {
+///         println!("Dimming the screen");
+///         Ok(())
+///     })
+///     .on_abort(|| {
+///         println!("Undimming the screen");
+///         Ok(())
+///     });
+/// ```
+pub struct FnTimer<'a> {
+    time_left: TimeLeftFn<'a>,
+    activate: Option<EventFn<'a>>,
+    abort: Option<EventFn<'a>>,
+    deactivate: Option<EventFn<'a>>,
+
+    /// Whether or not to disable this timer
+    pub disabled: bool,
+}
+impl<'a> FnTimer<'a> {
+    /// Create a new timer that decides how much time is left with a
+    /// closure, see `Timer::time_left`
+    pub fn new<F>(time_left: F) -> Self
+    where
+        F: FnMut(Duration) -> Result<Option<Duration>> + 'a,
+    {
+        Self {
+            time_left: Box::new(time_left),
+            activate: None,
+            abort: None,
+            deactivate: None,
+            disabled: false,
+        }
+    }
+    /// Create a new timer that activates after a fixed amount of idle
+    /// time, which is what you usually want
+    pub fn after(time: Duration) -> Self {
+        Self::new(move |idle_time| {
+            Ok(time
+                .checked_sub(idle_time)
+                .filter(|&d| d != Duration::default()))
+        })
+    }
+    /// Run this closure on activation
+    #[must_use]
+    pub fn on_activate<F>(mut self, f: F) -> Self
+    where
+        F: FnMut() -> Result<()> + 'a,
+    {
+        self.activate = Some(Box::new(f));
+        self
+    }
+    /// Run this closure on abortion
+    #[must_use]
+    pub fn on_abort<F>(mut self, f: F) -> Self
+    where
+        F: FnMut() -> Result<()> + 'a,
+    {
+        self.abort = Some(Box::new(f));
+        self
+    }
+    /// Run this closure on deactivation
+    #[must_use]
+    pub fn on_deactivate<F>(mut self, f: F) -> Self
+    where
+        F: FnMut() -> Result<()> + 'a,
+    {
+        self.deactivate = Some(Box::new(f));
+        self
+    }
+}
+impl fmt::Debug for FnTimer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FnTimer")
+    }
+}
+impl Timer for FnTimer<'_> {
+    fn time_left(&mut self, idle_time: Duration) -> Result<Option<Duration>> {
+        (self.time_left)(idle_time)
+    }
+    fn abort_urgency(&self) -> Option<Duration> {
+        self.abort.as_ref().map(|_| Duration::from_secs(1))
+    }
+    fn activate(&mut self) -> Result<()> {
+        self.activate.as_mut().map_or(Ok(()), |f| f())
+    }
+    fn abort(&mut self) -> Result<()> {
+        self.abort.as_mut().map_or(Ok(()), |f| f())
+    }
+    fn deactivate(&mut self) -> Result<()> {
+        self.deactivate.as_mut().map_or(Ok(()), |f| f())
+    }
+    fn disabled(&mut self) -> bool {
+        self.disabled
+    }
+}
+
+/// A timer that wraps another timer, but measures its time in elapsed
+/// session time instead of idle time: The inner timer's `time_left`
+/// is called with the time since this timer was created or last
+/// activated, regardless of whether the user has been idle or not.
+/// This is useful for things like reminders to take a break.
+///
+/// Note that this is still part of the timer chain, so it will only
+/// be considered once all timers before it have activated, and it
+/// won't activate again until the chain is reset (by user activity).
+/// In practice, you probably want this to be the first timer.
+pub struct SessionTimer<T: Timer> {
+    inner: T,
+    clock: Box<dyn Clock>,
+    baseline: Instant,
+}
+impl<T: Timer> SessionTimer<T> {
+    /// Wrap a timer, starting the session now
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            clock: Box::new(MonotonicClock),
+            baseline: Instant::now(),
+        }
+    }
+    /// Use a different clock, and restart the session according to
+    /// it
+    #[must_use]
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.baseline = clock.now();
+        self.clock = Box::new(clock);
+        self
+    }
+    /// Returns the inner timer
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+    /// Returns the inner timer mutably
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    /// Unwrap the inner timer
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+impl<T: Timer> Timer for SessionTimer<T> {
+    fn time_left(&mut self, _idle_time: Duration) -> Result<Option<Duration>> {
+        let elapsed = self.clock.now().saturating_duration_since(self.baseline);
+        self.inner.time_left(elapsed)
+    }
+    fn abort_urgency(&self) -> Option<Duration> {
+        self.inner.abort_urgency()
+    }
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+    fn prepare(&mut self, timer: TimerInfo) {
+        self.inner.prepare(timer);
+    }
+    fn activate(&mut self) -> Result<()> {
+        self.baseline = self.clock.now();
+        self.inner.activate()
+    }
+    fn abort(&mut self) -> Result<()> {
+        self.inner.abort()
+    }
+    fn deactivate(&mut self) -> Result<()> {
+        self.inner.deactivate()
+    }
+    fn disabled(&mut self) -> bool {
+        self.inner.disabled()
+    }
+    fn name(&self) -> Option<&str> {
+        self.inner.name()
+    }
+    fn take_warnings(&mut self) -> Vec<Error> {
+        self.inner.take_warnings()
+    }
+    fn module(&mut self) -> Option<&mut dyn Module> {
+        self.inner.module()
+    }
+}
+impl<T> fmt::Debug for SessionTimer<T>
+where
+    T: Timer + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SessionTimer({:?})", self.inner)
+    }
+}
+
+extern "C" {
+    // Not in the libc crate
+    fn tzset();
+}
+
+/// A time of the day, in local time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeOfDay {
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+impl TimeOfDay {
+    /// Returns `None` if the hour or minute is out of range
+    pub fn new(hour: u32, minute: u32) -> Option<Self> {
+        if hour < 24 && minute < 60 {
+            Some(Self {
+                hour,
+                minute,
+                second: 0,
+            })
+        } else {
+            None
+        }
+    }
+    /// The hour, from 0 to 23
+    pub fn hour(self) -> u32 {
+        self.hour
+    }
+    /// The minute, from 0 to 59
+    pub fn minute(self) -> u32 {
+        self.minute
+    }
+}
+impl FromStr for TimeOfDay {
+    type Err = String;
+
+    /// Parse a 24-hour time, such as "23:00"
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let hour = parts.next().and_then(|hour| hour.parse().ok());
+        let minute = parts.next().and_then(|minute| minute.parse().ok());
+        hour.zip(minute)
+            .and_then(|(hour, minute)| Self::new(hour, minute))
+            .ok_or_else(|| format!("invalid time of day {s:?}, expected something like 23:00"))
+    }
+}
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour(), self.minute())
+    }
+}
+
+/// A part of every day in local time, from `start` up until `end`. If
+/// `end` isn't after `start`, the window goes past midnight, and if
+/// they're the same, it lasts all day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeWindow {
+    /// When the window opens
+    pub start: TimeOfDay,
+    /// When the window closes
+    pub end: TimeOfDay,
+}
+impl TimeWindow {
+    /// Create a window that lasts from `start` until midnight
+    pub fn from(start: TimeOfDay) -> Self {
+        Self {
+            start,
+            end: TimeOfDay::default(),
+        }
+    }
+
+    /// Whether the window is open at this time of the day
+    pub fn contains(&self, time: TimeOfDay) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+
+    /// Returns how long it is from `now` until the window opens, or
+    /// `None` if it's open. This is in local time, as of the time zone
+    /// when it's called, so the wait is longer or shorter across a
+    /// daylight saving time change.
+    ///
+    /// # Errors
+    ///
+    /// If the local time can't be determined
+    pub fn time_until(&self, now: SystemTime) -> Result<Option<Duration>> {
+        let since_epoch = now.duration_since(UNIX_EPOCH)?;
+        let now_secs = libc::time_t::try_from(since_epoch.as_secs())?;
+        let mut tm: libc::tm = unsafe { mem::zeroed() };
+        unsafe {
+            // Pick up changes to the time zone
+            tzset();
+            if libc::localtime_r(ptr::addr_of!(now_secs), ptr::addr_of_mut!(tm)).is_null() {
+                return Err("can't get the local time".into());
+            }
+        }
+        let time = TimeOfDay {
+            hour: u32::try_from(tm.tm_hour)?,
+            minute: u32::try_from(tm.tm_min)?,
+            // A leap second counts as the second before
+            second: u32::try_from(tm.tm_sec)?.min(59),
+        };
+        if self.contains(time) {
+            return Ok(None);
+        }
+
+        // The next opening is either today or tomorrow. Time that's skipped by daylight saving
+        // time is moved forward by mktime.
+        tm.tm_hour = i32::try_from(self.start.hour())?;
+        tm.tm_min = i32::try_from(self.start.minute())?;
+        tm.tm_sec = 0;
+        tm.tm_isdst = -1;
+        if self.start < time {
+            tm.tm_mday = tm.tm_mday.saturating_add(1);
+        }
+        let start = unsafe { libc::mktime(ptr::addr_of_mut!(tm)) };
+        let start = u64::try_from(start)
+            .ok()
+            .and_then(|start| UNIX_EPOCH.checked_add(Duration::from_secs(start)))
+            .ok_or("can't convert the local time")?;
+        Ok(Some(
+            start
+                .duration_since(now)
+                .unwrap_or_default()
+                .max(Duration::from_secs(1)),
+        ))
+    }
+}
+impl FromStr for TimeWindow {
+    type Err = String;
+
+    /// Parse a start time, such as "23:00" until midnight, or a start
+    /// and end time, such as "23:00-06:00"
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '-');
+        let start = parts.next().unwrap_or_default().parse()?;
+        match parts.next() {
+            Some(end) => Ok(Self {
+                start,
+                end: end.parse()?,
+            }),
+            None => Ok(Self::from(start)),
+        }
+    }
+}
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// A timer that only activates within a time window of every day,
+/// such as to lock the screen if the user is idle past 23:00. The
+/// inner timer still decides how much idle time it takes, so that
+/// it doesn't go off the moment the user is active within the window.
+/// Outside the window, the time left is until it opens.
+///
+/// Like any other timer, this is part of the timer chain, so it's only
+/// considered once all timers before it have activated.
+pub struct AbsoluteTimer<T: Timer> {
+    inner: T,
+    window: TimeWindow,
+}
+impl<T: Timer> AbsoluteTimer<T> {
+    /// Wrap a timer, letting it activate within `window` only
+    pub fn new(inner: T, window: TimeWindow) -> Self {
+        Self { inner, window }
+    }
+    /// Returns the time window
+    pub fn window(&self) -> TimeWindow {
+        self.window
+    }
+    /// Returns the inner timer
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+    /// Returns the inner timer mutably
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    /// Unwrap the inner timer
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+impl<T: Timer> Timer for AbsoluteTimer<T> {
+    fn time_left(&mut self, idle_time: Duration) -> Result<Option<Duration>> {
+        let left = self.inner.time_left(idle_time)?;
+        // `None` is less than any time left, so this waits for both
+        Ok(left.max(self.window.time_until(SystemTime::now())?))
+    }
+    fn abort_urgency(&self) -> Option<Duration> {
+        self.inner.abort_urgency()
+    }
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+    fn prepare(&mut self, timer: TimerInfo) {
+        self.inner.prepare(timer);
+    }
+    fn activate(&mut self) -> Result<()> {
+        self.inner.activate()
+    }
+    fn abort(&mut self) -> Result<()> {
+        self.inner.abort()
+    }
+    fn deactivate(&mut self) -> Result<()> {
+        self.inner.deactivate()
+    }
+    fn disabled(&mut self) -> bool {
+        self.inner.disabled()
+    }
+    fn name(&self) -> Option<&str> {
+        self.inner.name()
+    }
+    fn take_warnings(&mut self) -> Vec<Error> {
+        self.inner.take_warnings()
+    }
+    fn module(&mut self) -> Option<&mut dyn Module> {
+        self.inner.module()
+    }
+}
+impl<T> fmt::Debug for AbsoluteTimer<T>
+where
+    T: Timer + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AbsoluteTimer({}, {:?})", self.window, self.inner)
+    }
+}
@@ -0,0 +1,193 @@
+use std::{cell::RefCell, rc::Rc};
+use xidlehook_core::{
+    modules::{WarningSummary, WindowState},
+    Error, Module, Progress, Result, TimerInfo,
+};
+
+const TIMER: TimerInfo = TimerInfo {
+    index: 0,
+    length: 1,
+    reached: None,
+    last: true,
+};
+
+type Calls = Rc<RefCell<Vec<String>>>;
+
+/// Records every call, and decides the same way every time
+struct Mock {
+    name: &'static str,
+    progress: Progress,
+    calls: Calls,
+}
+impl Mock {
+    fn new(name: &'static str, progress: Progress, calls: &Calls) -> Self {
+        Self {
+            name,
+            progress,
+            calls: Rc::clone(calls),
+        }
+    }
+    fn record(&self, call: &str) {
+        self.calls
+            .borrow_mut()
+            .push(format!("{} {}", call, self.name));
+    }
+}
+impl Module for Mock {
+    fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        self.record("pre");
+        Ok(self.progress)
+    }
+    fn post_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+        self.record("post");
+        Ok(self.progress)
+    }
+    fn warning(&mut self, error: &Error) -> Result<()> {
+        self.record(&format!("warning {}", error));
+        Ok(())
+    }
+    fn reset(&mut self) -> Result<()> {
+        self.record("reset");
+        Ok(())
+    }
+}
+
+/// The decision of `first` and then `second`, both as a tuple and as
+/// a `Vec`, along with which modules were asked
+fn decide(first: Progress, second: Progress) -> [(Progress, Vec<String>); 2] {
+    let calls = Calls::default();
+    let mut pair = (
+        Mock::new("a", first, &calls),
+        Mock::new("b", second, &calls),
+    );
+    let pair_progress = pair.pre_timer(TIMER).unwrap();
+    let pair_calls = calls.replace(Vec::new());
+
+    let mut list = vec![
+        Mock::new("a", first, &calls),
+        Mock::new("b", second, &calls),
+    ];
+    let list_progress = list.pre_timer(TIMER).unwrap();
+    let list_calls = calls.replace(Vec::new());
+
+    [(pair_progress, pair_calls), (list_progress, list_calls)]
+}
+
+#[test]
+fn combinators_decide() {
+    let both = vec![String::from("pre a"), String::from("pre b")];
+    let first = vec![String::from("pre a")];
+
+    for (progress, calls) in &decide(Progress::Continue, Progress::Continue) {
+        assert_eq!(*progress, Progress::Continue);
+        assert_eq!(*calls, both);
+    }
+    // An abort doesn't keep the second module from being asked, as it might force the timer
+    for (progress, calls) in &decide(Progress::Abort, Progress::Continue) {
+        assert_eq!(*progress, Progress::Abort);
+        assert_eq!(*calls, both);
+    }
+    for (progress, calls) in &decide(Progress::Abort, Progress::Force) {
+        assert_eq!(*progress, Progress::Force);
+        assert_eq!(*calls, both);
+    }
+    for (progress, calls) in &decide(Progress::Continue, Progress::Halt) {
+        assert_eq!(*progress, Progress::Halt);
+        assert_eq!(*calls, both);
+    }
+    // Only a stop short-circuits, and it wins over everything
+    for (progress, calls) in &decide(Progress::Stop, Progress::Force) {
+        assert_eq!(*progress, Progress::Stop);
+        assert_eq!(*calls, first);
+    }
+    for (progress, calls) in &decide(Progress::Force, Progress::Stop) {
+        assert_eq!(*progress, Progress::Stop);
+        assert_eq!(*calls, both);
+    }
+}
+
+#[test]
+fn combinators_fan_out() {
+    let calls = Calls::default();
+    let mut modules = (
+        Mock::new("a", Progress::Continue, &calls),
+        vec![
+            Mock::new("b", Progress::Continue, &calls),
+            Mock::new("c", Progress::Continue, &calls),
+        ],
+    );
+
+    assert_eq!(modules.post_timer(TIMER).unwrap(), Progress::Continue);
+    modules.warning(&"oops".into()).unwrap();
+    modules.reset().unwrap();
+    assert_eq!(
+        *calls.borrow(),
+        [
+            "post a",
+            "post b",
+            "post c",
+            "warning oops a",
+            "warning oops b",
+            "warning oops c",
+            "reset a",
+            "reset b",
+            "reset c",
+        ]
+    );
+}
+
+#[test]
+fn warning_summary_kinds() {
+    let mut summary = WarningSummary::new();
+    for message in &[
+        "activation command (PID 123) was still running after 5s, killed it with SIGTERM",
+        "activation command (PID 4567) was still running after 1.5s, killed it with SIGTERM",
+        "exec: \"true\" exited with code 1",
+    ] {
+        summary.warning(&(*message).into()).unwrap();
+    }
+    assert_eq!(
+        summary.counts().into_iter().collect::<Vec<_>>(),
+        [
+            (
+                String::from(
+                    "activation command (PID N) was still running after Ns, killed it with SIGTERM"
+                ),
+                2
+            ),
+            (String::from("exec: \"true\" exited with code N"), 1),
+        ]
+    );
+}
+
+#[test]
+fn fullscreen_windows() {
+    let visible = WindowState {
+        fullscreen: true,
+        on_current_desktop: true,
+        ..WindowState::default()
+    };
+    assert!(visible.counts_as_fullscreen());
+
+    // Every window is filtered the same way, focused or not
+    for window in [
+        WindowState {
+            fullscreen: false,
+            ..visible
+        },
+        WindowState {
+            hidden: true,
+            ..visible
+        },
+        WindowState {
+            desktop: true,
+            ..visible
+        },
+        WindowState {
+            on_current_desktop: false,
+            ..visible
+        },
+    ] {
+        assert!(!window.counts_as_fullscreen(), "{:?}", window);
+    }
+}
@@ -1,5 +1,19 @@
-use std::{cell::Cell, time::Duration};
-use xidlehook_core::{timers::CallbackTimer, Xidlehook};
+use std::{
+    cell::{Cell, RefCell},
+    process::Command,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use xidlehook_core::{
+    modules::{
+        battery::Power, ExecModule, NotWhenBattery, NotWhenLoadAbove, OnResume, OnStop,
+        StartupGrace, StopAfter, StopAt,
+    },
+    timers::{AbsoluteTimer, CallbackTimer, CmdTimer, FnTimer, SessionTimer, TimeWindow},
+    Action, Clock, Episode, Error, Module, Progress, Result, Timer, TimerInfo, TimerState,
+    Xidlehook,
+};
 
 const TEST_UNIT: Duration = Duration::from_millis(50);
 
@@ -129,3 +143,1419 @@ fn disabled_timers() {
     assert_eq!(timer.poll(TEST_UNIT * 04).unwrap(), Some(TEST_UNIT * 04));
     assert_eq!(triggered.get(), 0b0100);
 }
+
+#[test]
+fn changing_disabled() {
+    // Disabled every other time it's asked, like a condition on the outside world that changes
+    struct Flaky(bool);
+    impl Timer for Flaky {
+        fn time_left(&mut self, idle_time: Duration) -> Result<Option<Duration>> {
+            Ok((TEST_UNIT * 10)
+                .checked_sub(idle_time)
+                .filter(|&left| left != Duration::default()))
+        }
+        fn disabled(&mut self) -> bool {
+            self.0 = !self.0;
+            !self.0
+        }
+    }
+
+    let mut timer = Xidlehook::new(vec![Flaky(false), Flaky(false)]);
+    assert_eq!(
+        timer.step(TEST_UNIT * 10).unwrap(),
+        Action::Activated {
+            index: 0,
+            sleep: TEST_UNIT * 10
+        }
+    );
+}
+
+#[test]
+fn no_timers() {
+    let triggered = Cell::new(false);
+    let mut timer = Xidlehook::new(Vec::new());
+
+    // Nothing to do, so just wait for timers to be added
+    let long_sleep = Duration::from_secs(u32::MAX.into());
+    assert_eq!(timer.poll(Duration::default()).unwrap(), Some(long_sleep));
+    assert_eq!(timer.poll(long_sleep).unwrap(), Some(long_sleep));
+
+    // ... and pick them up once they are
+    timer
+        .timers_mut()
+        .unwrap()
+        .push(CallbackTimer::new(TEST_UNIT * 10, || triggered.set(true)));
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Some(TEST_UNIT * 10)
+    );
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Some(TEST_UNIT * 10));
+    assert!(triggered.get());
+}
+
+#[derive(Clone)]
+struct FakeClock(Rc<Cell<Instant>>);
+impl FakeClock {
+    fn new() -> Self {
+        Self(Rc::new(Cell::new(Instant::now())))
+    }
+    fn set(&self, instant: Instant) {
+        self.0.set(instant);
+    }
+    fn advance(&self, duration: Duration) {
+        self.0.set(self.0.get() + duration);
+    }
+}
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        self.0.get()
+    }
+}
+
+#[test]
+fn clock_jumps() {
+    let triggered = Cell::new(0);
+    let clock = FakeClock::new();
+    let start = clock.now();
+
+    let mut timer = Xidlehook::new(vec![
+        CallbackTimer::new(TEST_UNIT * 20, || triggered.set(triggered.get() | 1)),
+        CallbackTimer::new(TEST_UNIT * 20, || triggered.set(triggered.get() | 1 << 1)),
+    ])
+    .with_clock(clock.clone());
+
+    // Time and idle time progress at the same speed: business as usual
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Some(TEST_UNIT * 20)
+    );
+    clock.advance(TEST_UNIT * 20);
+    assert_eq!(timer.poll(TEST_UNIT * 20).unwrap(), Some(TEST_UNIT * 20));
+    assert_eq!(triggered.get(), 0b01);
+    triggered.set(0);
+
+    // The process gets paused for an hour. When it wakes up, the idle time is higher than
+    // before, but not by an hour - so the user must have been active while we weren't
+    // looking. This is a new idle episode, where only the first timer has passed.
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(timer.poll(TEST_UNIT * 45).unwrap(), Some(TEST_UNIT * 20));
+    assert_eq!(triggered.get(), 0b01);
+    triggered.set(0);
+
+    // The new idle episode continues normally
+    clock.advance(TEST_UNIT * 10);
+    assert_eq!(timer.poll(TEST_UNIT * 55).unwrap(), Some(TEST_UNIT * 10));
+
+    // A clock that jumps backwards doesn't confuse anything
+    clock.set(start);
+    assert_eq!(timer.poll(TEST_UNIT * 60).unwrap(), Some(TEST_UNIT * 5));
+    assert_eq!(triggered.get(), 0b00);
+    assert_eq!(timer.poll(TEST_UNIT * 65).unwrap(), Some(TEST_UNIT * 20));
+    assert_eq!(triggered.get(), 0b10);
+}
+
+#[test]
+fn startup_grace() {
+    let triggered = Cell::new(0);
+    let clock = FakeClock::new();
+
+    let mut timer = Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 10, || {
+        triggered.set(triggered.get() + 1)
+    })])
+    .register(StartupGrace::new(TEST_UNIT * 30).with_clock(clock.clone()))
+    .with_clock(clock.clone());
+
+    // The idle time was already high at startup, but the timer doesn't run
+    assert_eq!(timer.poll(TEST_UNIT * 100).unwrap(), Some(TEST_UNIT * 10));
+    clock.advance(TEST_UNIT * 10);
+    assert_eq!(timer.poll(TEST_UNIT * 110).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(triggered.get(), 0);
+
+    // Once the user has been active after the grace period, it works as usual
+    clock.advance(TEST_UNIT * 20);
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Some(TEST_UNIT * 10)
+    );
+    clock.advance(TEST_UNIT * 10);
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(triggered.get(), 1);
+}
+
+#[test]
+fn veto_deactivation() {
+    struct Veto<'a>(&'a Cell<bool>);
+    impl Module for Veto<'_> {
+        fn post_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+            Ok(if self.0.get() && timer.index > 0 {
+                Progress::Abort
+            } else {
+                Progress::Continue
+            })
+        }
+    }
+
+    let events = RefCell::new(Vec::new());
+    let veto = Cell::new(true);
+
+    let mut timer = Xidlehook::new(vec![
+        FnTimer::after(TEST_UNIT * 10)
+            .on_activate(|| {
+                events.borrow_mut().push("activate 0");
+                Ok(())
+            })
+            .on_abort(|| {
+                events.borrow_mut().push("abort 0");
+                Ok(())
+            })
+            .on_deactivate(|| {
+                events.borrow_mut().push("deactivate 0");
+                Ok(())
+            }),
+        FnTimer::after(TEST_UNIT * 5)
+            .on_activate(|| {
+                events.borrow_mut().push("activate 1");
+                Ok(())
+            })
+            .on_abort(|| {
+                events.borrow_mut().push("abort 1");
+                Ok(())
+            }),
+    ])
+    .register(Veto(&veto));
+
+    // The second timer runs, but the first one isn't deactivated. Both are aborted once the user
+    // is active again.
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Some(TEST_UNIT * 5));
+    assert_eq!(timer.poll(TEST_UNIT * 15).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(*events.borrow(), ["activate 0", "activate 1"]);
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Some(TEST_UNIT * 10)
+    );
+    assert_eq!(
+        *events.borrow(),
+        ["activate 0", "activate 1", "abort 1", "abort 0"]
+    );
+
+    // Only once
+    timer.poll(TEST_UNIT).unwrap();
+    timer.poll(Duration::default()).unwrap();
+    assert_eq!(events.borrow().len(), 4);
+
+    // Without the veto, it's deactivated as usual
+    veto.set(false);
+    events.borrow_mut().clear();
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Some(TEST_UNIT * 5));
+    assert_eq!(timer.poll(TEST_UNIT * 15).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(
+        *events.borrow(),
+        ["activate 0", "activate 1", "deactivate 0"]
+    );
+}
+
+#[test]
+fn forcing() {
+    struct Fixed(Progress);
+    impl Module for Fixed {
+        fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+            Ok(self.0)
+        }
+    }
+
+    let triggered = Cell::new(0);
+    let timers = || {
+        vec![CallbackTimer::new(TEST_UNIT * 10, || {
+            triggered.set(triggered.get() + 1)
+        })]
+    };
+
+    // Forcing wins over aborting, no matter the order
+    let mut timer = Xidlehook::new(timers())
+        .register(Fixed(Progress::Force))
+        .register(Fixed(Progress::Abort));
+    assert_eq!(
+        timer.step(TEST_UNIT * 10).unwrap(),
+        Action::Activated {
+            index: 0,
+            sleep: TEST_UNIT * 10
+        }
+    );
+    let mut timer = Xidlehook::new(timers()).with_module(vec![
+        Fixed(Progress::Abort),
+        Fixed(Progress::Force),
+        Fixed(Progress::Continue),
+    ]);
+    assert_eq!(
+        timer.step(TEST_UNIT * 10).unwrap(),
+        Action::Activated {
+            index: 0,
+            sleep: TEST_UNIT * 10
+        }
+    );
+    assert_eq!(triggered.get(), 2);
+
+    // Stopping wins over forcing
+    let mut timer = Xidlehook::new(timers())
+        .register(Fixed(Progress::Force))
+        .register(Fixed(Progress::Stop));
+    assert_eq!(timer.step(TEST_UNIT * 10).unwrap(), Action::Stop);
+    assert_eq!(triggered.get(), 2);
+}
+
+#[test]
+fn halting() {
+    struct Fixed(Progress);
+    impl Module for Fixed {
+        fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+            Ok(if timer.index > 0 {
+                self.0
+            } else {
+                Progress::Continue
+            })
+        }
+    }
+
+    let events = RefCell::new(Vec::new());
+    let timers = || {
+        vec![
+            FnTimer::after(TEST_UNIT * 10)
+                .on_activate(|| {
+                    events.borrow_mut().push("activate 0");
+                    Ok(())
+                })
+                .on_abort(|| {
+                    events.borrow_mut().push("abort 0");
+                    Ok(())
+                }),
+            FnTimer::after(TEST_UNIT * 5).on_activate(|| {
+                events.borrow_mut().push("activate 1");
+                Ok(())
+            }),
+        ]
+    };
+
+    // Halting leaves the first timer active until the user is active again
+    let mut timer = Xidlehook::new(timers()).register(Fixed(Progress::Halt));
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Some(TEST_UNIT * 5));
+    assert_eq!(
+        timer.step(TEST_UNIT * 15).unwrap(),
+        Action::Halted {
+            index: 1,
+            sleep: TEST_UNIT * 10
+        }
+    );
+    assert_eq!(timer.poll(TEST_UNIT * 20).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(*events.borrow(), ["activate 0"]);
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Some(TEST_UNIT * 10)
+    );
+    assert_eq!(*events.borrow(), ["activate 0", "abort 0"]);
+
+    // Aborting aborts the first timer right away
+    events.borrow_mut().clear();
+    let mut timer = Xidlehook::new(timers()).register(Fixed(Progress::Abort));
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Some(TEST_UNIT * 5));
+    assert_eq!(
+        timer.step(TEST_UNIT * 15).unwrap(),
+        Action::Aborted {
+            index: 1,
+            sleep: TEST_UNIT * 10
+        }
+    );
+    assert_eq!(*events.borrow(), ["activate 0", "abort 0"]);
+
+    // Aborting wins over halting
+    assert_eq!(Progress::Halt.combine(Progress::Abort), Progress::Abort);
+    assert_eq!(Progress::Continue.combine(Progress::Halt), Progress::Halt);
+}
+
+#[test]
+fn reset_activity() {
+    let events = RefCell::new(Vec::new());
+    let clock = FakeClock::new();
+
+    let mut timer = Xidlehook::new(vec![
+        FnTimer::after(TEST_UNIT * 10)
+            .on_activate(|| {
+                events.borrow_mut().push("activate 0");
+                Ok(())
+            })
+            .on_abort(|| {
+                events.borrow_mut().push("abort 0");
+                Ok(())
+            }),
+        FnTimer::after(TEST_UNIT * 100).on_activate(|| {
+            events.borrow_mut().push("activate 1");
+            Ok(())
+        }),
+    ])
+    .with_clock(clock.clone());
+
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(*events.borrow(), ["activate 0"]);
+
+    // The lock command ran for a while, and the idle time hasn't caught up with the user
+    // unlocking the screen yet
+    clock.advance(TEST_UNIT * 50);
+    timer.reset_activity(TEST_UNIT * 60).unwrap();
+    assert_eq!(*events.borrow(), ["activate 0", "abort 0"]);
+
+    // The chain starts over from there, instead of from zero idle time
+    clock.advance(TEST_UNIT * 5);
+    assert_eq!(timer.poll(TEST_UNIT * 65).unwrap(), Some(TEST_UNIT * 5));
+    clock.advance(TEST_UNIT * 5);
+    assert_eq!(timer.poll(TEST_UNIT * 70).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(*events.borrow(), ["activate 0", "abort 0", "activate 0"]);
+}
+
+#[test]
+fn snooze() {
+    let events = RefCell::new(Vec::new());
+
+    let mut timer = Xidlehook::new(vec![
+        FnTimer::after(TEST_UNIT * 10).on_activate(|| {
+            events.borrow_mut().push("activate 0");
+            Ok(())
+        }),
+        FnTimer::after(TEST_UNIT * 10).on_activate(|| {
+            events.borrow_mut().push("activate 1");
+            Ok(())
+        }),
+    ]);
+
+    timer.poll(TEST_UNIT * 5).unwrap();
+    assert!(timer.snooze(TEST_UNIT * 20));
+    timer.poll(TEST_UNIT * 10).unwrap();
+    timer.poll(TEST_UNIT * 29).unwrap();
+    assert!(events.borrow().is_empty());
+    timer.poll(TEST_UNIT * 30).unwrap();
+    assert_eq!(*events.borrow(), ["activate 0"]);
+
+    // Only the timer that was pending was pushed back
+    timer.poll(TEST_UNIT * 40).unwrap();
+    assert_eq!(*events.borrow(), ["activate 0", "activate 1"]);
+    assert!(!timer.snooze(TEST_UNIT * 20));
+
+    // Activity forgets any snoozing
+    timer.poll(Duration::default()).unwrap();
+    assert!(timer.snooze(TEST_UNIT * 20));
+    timer.poll(TEST_UNIT * 5).unwrap();
+    timer.poll(TEST_UNIT * 1).unwrap();
+    timer.poll(TEST_UNIT * 11).unwrap();
+    assert_eq!(*events.borrow(), ["activate 0", "activate 1", "activate 0"]);
+}
+
+#[test]
+fn pause() {
+    let events = RefCell::new(Vec::new());
+
+    let mut timer = Xidlehook::new(vec![
+        FnTimer::after(TEST_UNIT * 10)
+            .on_activate(|| {
+                events.borrow_mut().push("activate 0");
+                Ok(())
+            })
+            .on_abort(|| {
+                events.borrow_mut().push("abort 0");
+                Ok(())
+            }),
+        FnTimer::after(TEST_UNIT * 10).on_activate(|| {
+            events.borrow_mut().push("activate 1");
+            Ok(())
+        }),
+    ]);
+
+    timer.poll(TEST_UNIT * 10).unwrap();
+    assert_eq!(*events.borrow(), ["activate 0"]);
+
+    // Pausing counts as activity
+    timer.set_paused(true).unwrap();
+    assert!(timer.is_paused());
+    assert_eq!(*events.borrow(), ["activate 0", "abort 0"]);
+    timer.poll(TEST_UNIT * 30).unwrap();
+    assert_eq!(timer.next_activation(TEST_UNIT * 30).unwrap(), None);
+    assert_eq!(*events.borrow(), ["activate 0", "abort 0"]);
+
+    // The idle time counts from the resume
+    timer.set_paused(false).unwrap();
+    timer.poll(TEST_UNIT * 30).unwrap();
+    assert_eq!(
+        timer.next_activation(TEST_UNIT * 35).unwrap(),
+        Some((0, TEST_UNIT * 5))
+    );
+    timer.poll(TEST_UNIT * 35).unwrap();
+    assert_eq!(*events.borrow(), ["activate 0", "abort 0"]);
+    timer.poll(TEST_UNIT * 45).unwrap();
+    assert_eq!(*events.borrow(), ["activate 0", "abort 0", "activate 0"]);
+}
+
+#[test]
+fn resume_episode() {
+    let events = RefCell::new(Vec::new());
+    let timers = || {
+        vec![
+            FnTimer::after(TEST_UNIT * 10)
+                .on_activate(|| {
+                    events.borrow_mut().push("activate 0");
+                    Ok(())
+                })
+                .on_abort(|| {
+                    events.borrow_mut().push("abort 0");
+                    Ok(())
+                }),
+            FnTimer::after(TEST_UNIT * 10)
+                .on_activate(|| {
+                    events.borrow_mut().push("activate 1");
+                    Ok(())
+                })
+                .on_abort(|| {
+                    events.borrow_mut().push("abort 1");
+                    Ok(())
+                }),
+        ]
+    };
+
+    let mut old = Xidlehook::new(timers());
+    assert_eq!(old.episode(), None);
+    old.poll(TEST_UNIT * 12).unwrap();
+    let episode = old.episode().unwrap();
+    assert_eq!(
+        episode,
+        Episode {
+            next_index: 1,
+            base_idle_time: TEST_UNIT * 12
+        }
+    );
+    assert_eq!(*events.borrow(), ["activate 0"]);
+
+    // A new instance picks up where the old one left off, without activating the first timer
+    // again
+    let mut new = Xidlehook::new(timers());
+    assert!(!new
+        .resume_episode(
+            Episode {
+                next_index: 3,
+                ..episode
+            },
+            TEST_UNIT * 15
+        )
+        .unwrap());
+    assert!(new.resume_episode(episode, TEST_UNIT * 15).unwrap());
+    assert_eq!(new.timer_state(0), TimerState::Active);
+    assert_eq!(new.episode(), Some(episode));
+    new.poll(TEST_UNIT * 20).unwrap();
+    assert_eq!(*events.borrow(), ["activate 0"]);
+    new.poll(TEST_UNIT * 22).unwrap();
+    assert_eq!(*events.borrow(), ["activate 0", "activate 1"]);
+
+    // Activity aborts it as usual
+    new.poll(Duration::default()).unwrap();
+    assert_eq!(*events.borrow(), ["activate 0", "activate 1", "abort 1"]);
+    assert_eq!(new.episode(), None);
+}
+
+#[test]
+fn timer_states() {
+    let mut timer = Xidlehook::new(vec![
+        CallbackTimer::new(TEST_UNIT * 10, || ()),
+        CallbackTimer::new(TEST_UNIT * 10, || ()),
+        CallbackTimer::new(TEST_UNIT * 10, || ()),
+    ]);
+    let states = |timer: &Xidlehook<_, _>| (0..3).map(|i| timer.timer_state(i)).collect::<Vec<_>>();
+
+    assert_eq!(timer.poll(TEST_UNIT * 5).unwrap(), Some(TEST_UNIT * 5));
+    assert_eq!(states(&timer), [TimerState::Armed; 3]);
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(
+        states(&timer),
+        [TimerState::Active, TimerState::Armed, TimerState::Armed]
+    );
+    assert_eq!(timer.poll(TEST_UNIT * 20).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(
+        states(&timer),
+        [
+            TimerState::Deactivated,
+            TimerState::Active,
+            TimerState::Armed
+        ]
+    );
+
+    // Changing the timers aborts the chain
+    timer.timers_mut().unwrap();
+    assert_eq!(states(&timer), [TimerState::Skipped; 3]);
+
+    // Everything is armed again once the user is active
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Some(TEST_UNIT * 10)
+    );
+    assert_eq!(states(&timer), [TimerState::Armed; 3]);
+}
+
+#[test]
+fn exec_module() {
+    let decide = |command: &str| ExecModule::new(command.into(), TEST_UNIT * 20).decide();
+
+    assert_eq!(decide("exit 0").unwrap(), Progress::Continue);
+    assert_eq!(decide("echo 2; exit 1").unwrap(), Progress::Abort);
+    assert_eq!(decide("exit 2").unwrap(), Progress::Stop);
+    assert_eq!(decide("exit 3").unwrap(), Progress::Halt);
+    assert!(decide("exit 4").is_err());
+    assert!(decide("sleep 10").is_err());
+
+    // The command is passed through the shell that's set
+    let shell = vec!["/usr/bin/env".into(), "CODE=3".into(), "/bin/sh".into()];
+    let mut module = ExecModule::new("exit $CODE".into(), TEST_UNIT * 20).with_shell(shell);
+    assert_eq!(module.decide().unwrap(), Progress::Halt);
+}
+
+#[test]
+fn session_timers() {
+    let triggered = Cell::new(0);
+    let clock = FakeClock::new();
+
+    let mut timer = Xidlehook::new(vec![SessionTimer::new(CallbackTimer::new(
+        TEST_UNIT * 10,
+        || triggered.set(triggered.get() + 1),
+    ))
+    .with_clock(clock.clone())]);
+
+    // Idle time doesn't matter, only the time that passed
+    assert_eq!(timer.poll(TEST_UNIT * 50).unwrap(), Some(TEST_UNIT * 10));
+    clock.advance(TEST_UNIT * 4);
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Some(TEST_UNIT * 6)
+    );
+    clock.advance(TEST_UNIT * 6);
+    assert_eq!(triggered.get(), 0);
+    assert_eq!(timer.poll(TEST_UNIT * 1).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(triggered.get(), 1);
+
+    // The session restarts when the timer is activated, and it fires again once the chain is
+    // reset by user activity
+    clock.advance(TEST_UNIT * 10);
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Some(TEST_UNIT * 10)
+    );
+    assert_eq!(triggered.get(), 2);
+}
+
+#[test]
+fn absolute_timers() {
+    // Every test that depends on the time zone is in here, so they can't race
+    std::env::set_var("TZ", "UTC");
+    let window = |s: &str| s.parse::<TimeWindow>().unwrap();
+    // 2026-01-01 22:30 UTC
+    let evening = UNIX_EPOCH + Duration::from_secs(1_767_306_600);
+    let minutes = |n: u64| Duration::from_secs(n * 60);
+
+    assert_eq!(
+        window("23:00").time_until(evening).unwrap(),
+        Some(minutes(30))
+    );
+    assert_eq!(
+        window("23:00").time_until(evening + minutes(40)).unwrap(),
+        None
+    );
+    // Until midnight by default
+    assert_eq!(
+        window("23:00").time_until(evening + minutes(100)).unwrap(),
+        Some(minutes(22 * 60 + 50))
+    );
+    assert_eq!(
+        window("23:00-06:00")
+            .time_until(evening + minutes(100))
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        window("08:00-17:00").time_until(evening).unwrap(),
+        Some(minutes(9 * 60 + 30))
+    );
+    assert_eq!(window("12:00-12:00").time_until(evening).unwrap(), None);
+    assert!("24:00".parse::<TimeWindow>().is_err());
+    assert!("23".parse::<TimeWindow>().is_err());
+
+    // The clocks go forward an hour at 02:00 on 2026-03-29, so 04:00 is only 2 hours after
+    // 01:00
+    std::env::set_var("TZ", "CET-1CEST,M3.5.0,M10.5.0/3");
+    let night = UNIX_EPOCH + Duration::from_secs(1_774_742_400);
+    assert_eq!(
+        window("04:00").time_until(night).unwrap(),
+        Some(minutes(2 * 60))
+    );
+
+    // In a chain, it takes both the idle time and the time window
+    std::env::set_var("TZ", "UTC");
+    let activations = Cell::new(0);
+    let mut timer = Xidlehook::new(vec![AbsoluteTimer::new(
+        CallbackTimer::new(TEST_UNIT * 10, || activations.set(activations.get() + 1)),
+        window("00:00-00:00"),
+    )]);
+    timer.step(TEST_UNIT * 5).unwrap();
+    assert_eq!(activations.get(), 0);
+    timer.step(TEST_UNIT * 10).unwrap();
+    assert_eq!(activations.get(), 1);
+
+    let hour = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        / 3600
+        % 24;
+    let later = window(&format!(
+        "{:02}:00-{:02}:00",
+        (hour + 2) % 24,
+        (hour + 3) % 24
+    ));
+    let mut timer = Xidlehook::new(vec![AbsoluteTimer::new(
+        CallbackTimer::new(TEST_UNIT * 10, || activations.set(activations.get() + 1)),
+        later,
+    )]);
+    timer.step(TEST_UNIT * 10).unwrap();
+    assert_eq!(activations.get(), 1);
+    let (index, left) = timer.next_activation(TEST_UNIT * 10).unwrap().unwrap();
+    assert_eq!(index, 0);
+    assert!(left > Duration::from_secs(3600));
+}
+
+#[test]
+fn random_durations() {
+    let random = |seed| {
+        let timer = CmdTimer {
+            time: TEST_UNIT * 10,
+            range: Some((TEST_UNIT * 10, TEST_UNIT * 20)),
+            ..CmdTimer::default()
+        };
+        timer.rng.seed(seed);
+        timer
+    };
+    let mut timer = Xidlehook::new(vec![random(1), CmdTimer::default()]);
+
+    // A new duration is drawn every time the chain starts over
+    let mut times = Vec::new();
+    for _ in 0..10 {
+        timer.step(TEST_UNIT).unwrap();
+        timer.step(Duration::default()).unwrap();
+        times.push(timer.timers()[0].time);
+    }
+    assert!(times
+        .iter()
+        .all(|&time| time >= TEST_UNIT * 10 && time <= TEST_UNIT * 20));
+    assert!(times.iter().any(|&time| time != times[0]));
+
+    // But not while it's counting down
+    let time = timer.timers()[0].time;
+    timer.step(TEST_UNIT * 5).unwrap();
+    timer.step(TEST_UNIT * 9).unwrap();
+    assert_eq!(timer.timers()[0].time, time);
+    assert!(matches!(
+        timer.step(time).unwrap(),
+        Action::Activated { index: 0, .. }
+    ));
+    assert_eq!(timer.timers()[0].time, time);
+
+    // The same seed draws the same durations
+    let (mut first, mut second) = (random(7), random(7));
+    for _ in 0..10 {
+        first.randomize();
+        second.randomize();
+        assert_eq!(first.time, second.time);
+    }
+}
+
+#[test]
+fn stepping() {
+    let triggered = Cell::new(0);
+
+    let mut timer = Xidlehook::new(vec![
+        CallbackTimer::new(TEST_UNIT * 10, || triggered.set(triggered.get() | 1)),
+        CallbackTimer::new(TEST_UNIT * 20, || triggered.set(triggered.get() | 1 << 1)),
+    ])
+    .register(StopAt::completion());
+
+    assert_eq!(
+        timer.step(Duration::default()).unwrap(),
+        Action::Sleep(TEST_UNIT * 10)
+    );
+    assert_eq!(
+        timer.step(TEST_UNIT * 10).unwrap(),
+        Action::Activated {
+            index: 0,
+            sleep: TEST_UNIT * 10
+        }
+    );
+    assert_eq!(triggered.get(), 0b01);
+    assert_eq!(
+        timer.step(TEST_UNIT * 15).unwrap(),
+        Action::Sleep(TEST_UNIT * 10)
+    );
+    assert_eq!(timer.step(TEST_UNIT * 30).unwrap(), Action::Stop);
+    assert_eq!(triggered.get(), 0b11);
+}
+
+#[test]
+fn stop_after() {
+    let mut timer = Xidlehook::new(vec![
+        CallbackTimer::new(TEST_UNIT * 10, || ()),
+        CallbackTimer::new(TEST_UNIT * 20, || ()),
+    ])
+    .register(StopAfter::times(2));
+
+    // The first chain runs to the end
+    timer.step(TEST_UNIT * 10).unwrap();
+    assert_eq!(
+        timer.step(TEST_UNIT * 30).unwrap(),
+        Action::Activated {
+            index: 1,
+            sleep: TEST_UNIT * 10
+        }
+    );
+
+    // The user is back during the second one, which doesn't count
+    timer.step(Duration::default()).unwrap();
+    timer.step(TEST_UNIT * 10).unwrap();
+    timer.step(Duration::default()).unwrap();
+    assert_eq!(timer.module_mut().1.completed(), 1);
+
+    // The third one does, and stops
+    timer.step(TEST_UNIT * 10).unwrap();
+    assert_eq!(timer.step(TEST_UNIT * 30).unwrap(), Action::Stop);
+    assert_eq!(timer.module_mut().1.completed(), 2);
+}
+
+#[test]
+fn stop_after_disabled() {
+    let mut last = CallbackTimer::new(TEST_UNIT * 20, || ());
+    last.disabled = true;
+    let mut timer = Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 10, || ()), last])
+        .register(StopAfter::times(1));
+
+    // The chain ends with the first timer, as the last one is disabled
+    assert_eq!(timer.step(TEST_UNIT * 10).unwrap(), Action::Stop);
+    assert_eq!(timer.module_mut().1.completed(), 1);
+}
+
+#[test]
+fn stop_at_command() {
+    let path = std::env::temp_dir().join(format!("xidlehook-stop-at-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut timer =
+        Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 10, || ())]).register(OnStop::new(
+            StopAt::completion(),
+            vec![
+                "/bin/sh".into(),
+                "-c".into(),
+                format!("echo stopped > {}", path.display()),
+            ],
+        ));
+
+    assert_eq!(timer.step(TEST_UNIT * 10).unwrap(), Action::Stop);
+    // The command has finished by the time the stop is reported
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "stopped\n");
+    std::fs::remove_file(&path).unwrap();
+
+    // A command that hangs is killed, so xidlehook still stops
+    let mut timer = Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 10, || ())]).register(
+        OnStop::new(StopAt::completion(), vec!["sleep".into(), "10".into()])
+            .with_timeout(TEST_UNIT * 2),
+    );
+    let start = Instant::now();
+    assert_eq!(timer.step(TEST_UNIT * 10).unwrap(), Action::Stop);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn on_resume() {
+    let path = std::env::temp_dir().join(format!("xidlehook-on-resume-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut timer = Xidlehook::new(vec![CallbackTimer::new(TEST_UNIT * 10, || ())]).register(
+        OnResume::new(vec![
+            "/bin/sh".into(),
+            "-c".into(),
+            format!("echo resumed > {}", path.display()),
+        ]),
+    );
+
+    // Not run if the user returns before any timer went off
+    timer.step(TEST_UNIT * 5).unwrap();
+    timer.step(Duration::default()).unwrap();
+
+    timer.step(TEST_UNIT * 10).unwrap();
+    std::thread::sleep(TEST_UNIT * 4);
+    assert!(!path.exists());
+
+    timer.step(Duration::default()).unwrap();
+    // The command isn't waited for
+    let started = Instant::now();
+    while !path.exists() && started.elapsed() < TEST_UNIT * 40 {
+        std::thread::sleep(TEST_UNIT);
+    }
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "resumed\n");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn max_sleep() {
+    let mut timer = Xidlehook::new(vec![
+        CallbackTimer::new(TEST_UNIT * 100, || ()),
+        CallbackTimer::new(TEST_UNIT * 5, || ()),
+    ])
+    .with_max_sleep(TEST_UNIT * 10);
+
+    // Far from a timer, the sleep is capped
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Some(TEST_UNIT * 10)
+    );
+    assert_eq!(timer.poll(TEST_UNIT * 50).unwrap(), Some(TEST_UNIT * 10));
+    // Close to one, it sleeps right until it's due
+    assert_eq!(timer.poll(TEST_UNIT * 96).unwrap(), Some(TEST_UNIT * 4));
+    assert_eq!(
+        timer.step(TEST_UNIT * 100).unwrap(),
+        Action::Activated {
+            index: 0,
+            sleep: TEST_UNIT * 5
+        }
+    );
+}
+
+#[test]
+fn replace_timers() {
+    let aborted = Cell::new(0);
+    let timer = |time| {
+        FnTimer::after(time).on_abort(|| {
+            aborted.set(aborted.get() + 1);
+            Ok(())
+        })
+    };
+
+    let mut xidlehook = Xidlehook::new(vec![timer(TEST_UNIT * 10), timer(TEST_UNIT * 10)]);
+    assert!(matches!(
+        xidlehook.step(TEST_UNIT * 10).unwrap(),
+        Action::Activated { index: 0, .. }
+    ));
+
+    // The first timer went off and is unchanged, so the chain carries on with the new second one
+    assert!(xidlehook
+        .replace_timers(vec![timer(TEST_UNIT * 10), timer(TEST_UNIT * 5)], |_, _| {
+            true
+        })
+        .unwrap());
+    assert_eq!(xidlehook.timer_state(0), TimerState::Active);
+    assert!(matches!(
+        xidlehook.step(TEST_UNIT * 15).unwrap(),
+        Action::Activated { index: 1, .. }
+    ));
+    assert_eq!(aborted.get(), 0);
+
+    // Changing a timer that went off aborts the chain
+    assert!(!xidlehook
+        .replace_timers(vec![timer(TEST_UNIT * 10), timer(TEST_UNIT * 5)], |_, _| {
+            false
+        })
+        .unwrap());
+    assert_eq!(aborted.get(), 1);
+    assert_eq!(xidlehook.timer_state(1), TimerState::Skipped);
+}
+
+#[test]
+fn insert_remove_timers() {
+    let aborted = Cell::new(0);
+    let timer = |time| {
+        FnTimer::after(time).on_abort(|| {
+            aborted.set(aborted.get() + 1);
+            Ok(())
+        })
+    };
+
+    let mut xidlehook = Xidlehook::new(vec![timer(TEST_UNIT * 10), timer(TEST_UNIT * 10)]);
+    assert!(matches!(
+        xidlehook.step(TEST_UNIT * 10).unwrap(),
+        Action::Activated { index: 0, .. }
+    ));
+
+    // Timers that haven't gone off yet come and go without disturbing the chain
+    assert!(xidlehook.remove_timer(1).unwrap().1);
+    assert!(xidlehook.insert_timer(1, timer(TEST_UNIT * 5)).unwrap());
+    assert!(matches!(
+        xidlehook.step(TEST_UNIT * 12).unwrap(),
+        Action::Sleep(_)
+    ));
+
+    // One that's already due goes off right away
+    assert!(xidlehook.insert_timer(1, timer(TEST_UNIT)).unwrap());
+    assert!(matches!(
+        xidlehook.step(TEST_UNIT * 12).unwrap(),
+        Action::Activated { index: 1, .. }
+    ));
+    assert_eq!(xidlehook.timers().len(), 3);
+    assert_eq!(aborted.get(), 0);
+
+    // Removing the active timer aborts the chain
+    assert!(!xidlehook.remove_timer(1).unwrap().1);
+    assert_eq!(aborted.get(), 1);
+    assert_eq!(xidlehook.timers().len(), 2);
+
+    assert!(xidlehook.remove_timer(2).is_err());
+    assert!(xidlehook.insert_timer(3, timer(TEST_UNIT)).is_err());
+}
+
+#[test]
+fn not_when_load_above() {
+    let path = std::env::temp_dir().join(format!("xidlehook-loadavg-{}", std::process::id()));
+    let info = TimerInfo {
+        index: 0,
+        length: 1,
+        reached: None,
+        last: true,
+    };
+    let mut module = NotWhenLoadAbove::new(4.0).with_path(path.clone());
+
+    std::fs::write(&path, "5.50 2.00 1.00 3/400 1234\n").unwrap();
+    assert_eq!(module.pre_timer(info).unwrap(), Progress::Abort);
+
+    // The load average is cached until the chain is reset
+    std::fs::write(&path, "0.50 2.00 1.00 1/400 1234\n").unwrap();
+    assert_eq!(module.pre_timer(info).unwrap(), Progress::Abort);
+    module.reset().unwrap();
+    assert_eq!(module.pre_timer(info).unwrap(), Progress::Continue);
+
+    std::fs::write(&path, "").unwrap();
+    module.reset().unwrap();
+    assert!(module.pre_timer(info).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn not_when_battery() {
+    let path = std::env::temp_dir().join(format!("xidlehook-power-{}", std::process::id()));
+    let supply = |name: &str, attributes: &[(&str, &str)]| {
+        let dir = path.join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (attribute, value) in attributes {
+            std::fs::write(dir.join(attribute), format!("{}\n", value)).unwrap();
+        }
+    };
+    let info = TimerInfo {
+        index: 0,
+        length: 1,
+        reached: None,
+        last: true,
+    };
+
+    // Without a battery, it must be running on mains power
+    std::fs::create_dir_all(&path).unwrap();
+    let mut on_ac = NotWhenBattery::on_ac().with_path(path.clone());
+    assert_eq!(on_ac.pre_timer(info).unwrap(), Progress::Abort);
+
+    supply("AC", &[("type", "Mains"), ("online", "0")]);
+    supply(
+        "BAT0",
+        &[
+            ("type", "Battery"),
+            ("energy_now", "10"),
+            ("energy_full", "40"),
+        ],
+    );
+    supply("BAT1", &[("type", "Battery"), ("capacity", "50")]);
+    supply(
+        "hidpp_battery_0",
+        &[("type", "Battery"), ("scope", "Device"), ("capacity", "1")],
+    );
+    on_ac.reset().unwrap();
+    assert_eq!(on_ac.pre_timer(info).unwrap(), Progress::Continue);
+
+    let mut below = NotWhenBattery::battery_below(50.0).with_path(path.clone());
+    assert_eq!(
+        below.power().unwrap(),
+        Power {
+            on_ac: false,
+            battery: Some(60.0 / 140.0 * 100.0),
+        }
+    );
+    assert_eq!(below.pre_timer(info).unwrap(), Progress::Abort);
+
+    // The power state is cached until the chain is reset
+    supply("AC", &[("online", "1")]);
+    assert_eq!(on_ac.pre_timer(info).unwrap(), Progress::Continue);
+    on_ac.reset().unwrap();
+    assert_eq!(on_ac.pre_timer(info).unwrap(), Progress::Abort);
+
+    // Only flips of the decision are reported
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    let mut on_ac = NotWhenBattery::on_ac().with_path(path.clone()).on_change({
+        let changes = Rc::clone(&changes);
+        move |aborting, power| changes.borrow_mut().push((aborting, power.on_ac))
+    });
+    on_ac.pre_timer(info).unwrap();
+    on_ac.reset().unwrap();
+    on_ac.pre_timer(info).unwrap();
+    supply("AC", &[("online", "0")]);
+    on_ac.reset().unwrap();
+    on_ac.pre_timer(info).unwrap();
+    assert_eq!(on_ac.would_allow(info).unwrap(), Progress::Continue);
+    assert_eq!(*changes.borrow(), [(true, true), (false, false)]);
+
+    std::fs::remove_dir_all(&path).unwrap();
+}
+
+#[cfg(feature = "screencast")]
+#[test]
+fn not_when_screencasting() {
+    use xidlehook_core::modules::NotWhenScreencast;
+
+    let path = std::env::temp_dir().join(format!("xidlehook-pw-dump-{}", std::process::id()));
+    let node = |state: &str, class: &str, device: bool| {
+        let device = if device { r#", "device.id": 40"# } else { "" };
+        format!(
+            r#"{{ "id": 50, "type": "PipeWire:Interface:Node", "info": {{ "state": "{}",
+                "props": {{ "node.name": "screen", "media.class": "{}"{} }} }} }}"#,
+            state, class, device
+        )
+    };
+    let info = TimerInfo {
+        index: 0,
+        length: 1,
+        reached: None,
+        last: true,
+    };
+    let mut module = NotWhenScreencast::new().with_command(vec![
+        String::from("cat"),
+        path.to_string_lossy().into_owned(),
+    ]);
+
+    // A webcam, an idle screencast and audio aren't screencasting
+    std::fs::write(
+        &path,
+        format!(
+            "[{}, {}, {}]",
+            node("running", "Video/Source", true),
+            node("suspended", "Video/Source", false),
+            node("running", "Audio/Source", false),
+        ),
+    )
+    .unwrap();
+    assert_eq!(module.pre_timer(info).unwrap(), Progress::Continue);
+
+    // The result is cached until the chain is reset
+    std::fs::write(
+        &path,
+        format!("[{}]", node("running", "Video/Source", false)),
+    )
+    .unwrap();
+    assert_eq!(module.pre_timer(info).unwrap(), Progress::Continue);
+    module.reset().unwrap();
+    assert_eq!(module.pre_timer(info).unwrap(), Progress::Abort);
+
+    std::fs::write(&path, "").unwrap();
+    module.reset().unwrap();
+    assert!(module.pre_timer(info).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn next_activation() {
+    struct Inhibit<'a>(&'a Cell<bool>, &'a Cell<usize>);
+    impl Module for Inhibit<'_> {
+        fn pre_timer(&mut self, timer: TimerInfo) -> Result<Progress> {
+            self.1.set(self.1.get() + 1);
+            self.would_allow(timer)
+        }
+        fn would_allow(&mut self, _timer: TimerInfo) -> Result<Progress> {
+            Ok(if self.0.get() {
+                Progress::Abort
+            } else {
+                Progress::Continue
+            })
+        }
+    }
+
+    let inhibit = Cell::new(false);
+    let asked = Cell::new(0);
+    let mut timer = Xidlehook::new(vec![
+        CallbackTimer::new(TEST_UNIT * 10, || ()),
+        CallbackTimer::new(TEST_UNIT * 5, || ()),
+    ])
+    .register(Inhibit(&inhibit, &asked));
+
+    assert_eq!(
+        timer.next_activation(TEST_UNIT * 4).unwrap(),
+        Some((0, TEST_UNIT * 6))
+    );
+    timer.step(TEST_UNIT * 10).unwrap();
+    assert_eq!(
+        timer.next_activation(TEST_UNIT * 12).unwrap(),
+        Some((1, TEST_UNIT * 3))
+    );
+
+    // A module holding the timer back
+    inhibit.set(true);
+    assert_eq!(timer.next_activation(TEST_UNIT * 12).unwrap(), None);
+    inhibit.set(false);
+
+    // Only the timer that ran was decided on with pre_timer
+    assert_eq!(asked.get(), 1);
+
+    // Nothing is left once the chain is done, until the user is active again
+    timer.step(TEST_UNIT * 15).unwrap();
+    assert_eq!(timer.next_activation(TEST_UNIT * 16).unwrap(), None);
+    assert_eq!(
+        timer.next_activation(TEST_UNIT * 2).unwrap(),
+        Some((0, TEST_UNIT * 8))
+    );
+}
+
+#[test]
+fn fn_timers() {
+    let events = RefCell::new(Vec::new());
+
+    let mut timer = Xidlehook::new(vec![
+        FnTimer::after(TEST_UNIT * 10)
+            .on_activate(|| {
+                events.borrow_mut().push("activate 0");
+                Ok(())
+            })
+            .on_abort(|| {
+                events.borrow_mut().push("abort 0");
+                Ok(())
+            })
+            .on_deactivate(|| {
+                events.borrow_mut().push("deactivate 0");
+                Ok(())
+            }),
+        FnTimer::new(|idle| {
+            Ok((TEST_UNIT * 5)
+                .checked_sub(idle)
+                .filter(|d| *d > Duration::default()))
+        })
+        .on_activate(|| {
+            events.borrow_mut().push("activate 1");
+            Ok(())
+        }),
+    ]);
+
+    assert_eq!(
+        timer.poll(Duration::default()).unwrap(),
+        Some(TEST_UNIT * 10)
+    );
+    assert_eq!(timer.poll(TEST_UNIT * 10).unwrap(), Some(TEST_UNIT * 5));
+    assert_eq!(*events.borrow(), ["activate 0"]);
+
+    // User activity aborts the first timer
+    assert_eq!(timer.poll(TEST_UNIT * 1).unwrap(), Some(TEST_UNIT * 9));
+    assert_eq!(*events.borrow(), ["activate 0", "abort 0"]);
+
+    // Going all the way deactivates it instead
+    assert_eq!(timer.poll(TEST_UNIT * 11).unwrap(), Some(TEST_UNIT * 5));
+    assert_eq!(timer.poll(TEST_UNIT * 16).unwrap(), Some(TEST_UNIT * 10));
+    assert_eq!(
+        *events.borrow(),
+        [
+            "activate 0",
+            "abort 0",
+            "activate 0",
+            "activate 1",
+            "deactivate 0"
+        ]
+    );
+}
+
+#[test]
+fn prepare_timers() {
+    struct Prepared<'a> {
+        time: Duration,
+        events: &'a RefCell<Vec<(&'static str, usize, Option<usize>)>>,
+        index: usize,
+        reached: Option<usize>,
+    }
+    impl Timer for Prepared<'_> {
+        fn time_left(&mut self, idle_time: Duration) -> Result<Option<Duration>> {
+            Ok(self
+                .time
+                .checked_sub(idle_time)
+                .filter(|&d| d != Duration::default()))
+        }
+        fn prepare(&mut self, timer: TimerInfo) {
+            self.index = timer.index;
+            self.reached = timer.reached;
+        }
+        fn activate(&mut self) -> Result<()> {
+            self.events
+                .borrow_mut()
+                .push(("activate", self.index, self.reached));
+            Ok(())
+        }
+        fn abort(&mut self) -> Result<()> {
+            self.events
+                .borrow_mut()
+                .push(("abort", self.index, self.reached));
+            Ok(())
+        }
+        fn deactivate(&mut self) -> Result<()> {
+            self.events
+                .borrow_mut()
+                .push(("deactivate", self.index, self.reached));
+            Ok(())
+        }
+    }
+
+    let events = RefCell::new(Vec::new());
+    let timer = |time| Prepared {
+        time,
+        events: &events,
+        index: usize::MAX,
+        reached: None,
+    };
+    let mut xidlehook = Xidlehook::new(vec![timer(TEST_UNIT * 10), timer(TEST_UNIT * 5)]);
+
+    xidlehook.poll(TEST_UNIT * 10).unwrap();
+    xidlehook.poll(TEST_UNIT * 15).unwrap();
+    xidlehook.poll(TEST_UNIT * 10).unwrap();
+    xidlehook.poll(TEST_UNIT * 1).unwrap();
+    assert_eq!(
+        *events.borrow(),
+        [
+            ("activate", 0, Some(0)),
+            ("activate", 1, Some(1)),
+            // Deactivated as the next timer took over
+            ("deactivate", 0, Some(1)),
+            ("abort", 1, Some(1)),
+            ("activate", 0, Some(0)),
+            ("abort", 0, Some(0))
+        ]
+    );
+
+    // The index follows the timer as others are added before it
+    events.borrow_mut().clear();
+    xidlehook.insert_timer(0, timer(TEST_UNIT * 100)).unwrap();
+    xidlehook.poll(TEST_UNIT * 100).unwrap();
+    xidlehook.poll(TEST_UNIT * 110).unwrap();
+    assert_eq!(
+        *events.borrow(),
+        [
+            ("activate", 0, Some(0)),
+            ("activate", 1, Some(1)),
+            ("deactivate", 0, Some(1))
+        ]
+    );
+}
+
+#[test]
+fn cmd_timeout() {
+    struct Warnings<'a>(&'a RefCell<Vec<String>>);
+    impl Module for Warnings<'_> {
+        fn warning(&mut self, error: &Error) -> Result<()> {
+            self.0.borrow_mut().push(error.to_string());
+            Ok(())
+        }
+    }
+
+    let command = |secs: &str| {
+        let mut command = Command::new("sleep");
+        command.arg(secs);
+        command
+    };
+    let warnings = RefCell::new(Vec::new());
+    let mut xidlehook = Xidlehook::new(vec![
+        CmdTimer {
+            time: TEST_UNIT,
+            activation: Some(command("10")),
+            timeout: Some(TEST_UNIT),
+            ..CmdTimer::default()
+        },
+        CmdTimer {
+            time: TEST_UNIT,
+            activation: Some(command("0")),
+            timeout: Some(TEST_UNIT * 100),
+            ..CmdTimer::default()
+        },
+    ])
+    .register(Warnings(&warnings));
+
+    xidlehook.poll(TEST_UNIT).unwrap();
+    xidlehook.poll(TEST_UNIT * 2).unwrap();
+
+    // Only the command that hangs is killed, which is reported on the next poll
+    let start = Instant::now();
+    while warnings.borrow().is_empty() {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+        thread::sleep(TEST_UNIT);
+        xidlehook.poll(TEST_UNIT * 2).unwrap();
+    }
+    assert_eq!(warnings.borrow().len(), 1);
+    assert!(warnings.borrow()[0].contains("SIGTERM"));
+}
+
+#[test]
+fn timer_modules() {
+    struct Veto(Rc<Cell<bool>>);
+    impl Module for Veto {
+        fn pre_timer(&mut self, _timer: TimerInfo) -> Result<Progress> {
+            Ok(if self.0.get() {
+                Progress::Abort
+            } else {
+                Progress::Continue
+            })
+        }
+    }
+
+    let veto = Rc::new(Cell::new(true));
+    let mut xidlehook = Xidlehook::new(vec![
+        CmdTimer {
+            time: TEST_UNIT * 10,
+            ..CmdTimer::default()
+        },
+        CmdTimer {
+            time: TEST_UNIT * 10,
+            modules: vec![Box::new(Veto(Rc::clone(&veto)))],
+            ..CmdTimer::default()
+        },
+    ]);
+
+    // Only the timer with the module is held back
+    assert!(matches!(
+        xidlehook.step(TEST_UNIT * 10).unwrap(),
+        Action::Activated { index: 0, .. }
+    ));
+    assert!(matches!(
+        xidlehook.step(TEST_UNIT * 20).unwrap(),
+        Action::Aborted { index: 1, .. }
+    ));
+
+    veto.set(false);
+    xidlehook.step(TEST_UNIT).unwrap();
+    xidlehook.step(TEST_UNIT * 10).unwrap();
+    assert!(matches!(
+        xidlehook.step(TEST_UNIT * 20).unwrap(),
+        Action::Activated { index: 1, .. }
+    ));
+}
+
+#[test]
+fn dry_run() {
+    let mut xidlehook = Xidlehook::new(vec![CmdTimer {
+        time: TEST_UNIT,
+        activation: Some(Command::new("/nonexistent/xidlehook-dry-run")),
+        abortion: Some(Command::new("/nonexistent/xidlehook-dry-run")),
+        dry_run: true,
+        ..CmdTimer::default()
+    }]);
+
+    // Nothing runs, so nothing fails to run either
+    assert!(matches!(
+        xidlehook.step(TEST_UNIT).unwrap(),
+        Action::Activated { index: 0, .. }
+    ));
+    assert_eq!(xidlehook.timers()[0].activation_pid, None);
+    assert!(matches!(
+        xidlehook.step(Duration::default()).unwrap(),
+        Action::Sleep(_)
+    ));
+}
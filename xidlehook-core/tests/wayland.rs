@@ -0,0 +1,115 @@
+#![cfg(feature = "wayland")]
+
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    thread,
+    time::{Duration, Instant},
+};
+use xidlehook_core::modules::WaylandIdle;
+
+/// Send an event with these 32-bit arguments
+fn send(stream: &mut UnixStream, object: u32, opcode: u32, args: &[u32]) {
+    let size = 8 + 4 * args.len() as u32;
+    let mut buf = Vec::new();
+    for word in [object, size << 16 | opcode].iter().chain(args) {
+        buf.extend_from_slice(&word.to_ne_bytes());
+    }
+    stream.write_all(&buf).unwrap();
+}
+
+/// Announce a global, with the interface name padded to 32 bits
+fn send_global(stream: &mut UnixStream, name: u32, interface: &str) {
+    let mut bytes = interface.as_bytes().to_vec();
+    bytes.push(0);
+    let len = bytes.len() as u32;
+    bytes.resize((bytes.len() + 3) & !3, 0);
+    let mut args = vec![name, len];
+    args.extend(
+        bytes
+            .chunks(4)
+            .map(|word| u32::from_ne_bytes([word[0], word[1], word[2], word[3]])),
+    );
+    args.push(1);
+    send(stream, 2, 0, &args);
+}
+
+/// Receive a request, returning its object, opcode and arguments
+fn receive(stream: &mut UnixStream) -> (u32, u32, Vec<u8>) {
+    let mut header = [0; 8];
+    stream.read_exact(&mut header).unwrap();
+    let object = u32::from_ne_bytes([header[0], header[1], header[2], header[3]]);
+    let size_opcode = u32::from_ne_bytes([header[4], header[5], header[6], header[7]]);
+    let mut args = vec![0; (size_opcode >> 16) as usize - 8];
+    stream.read_exact(&mut args).unwrap();
+    (object, size_opcode & 0xFFFF, args)
+}
+
+/// Wait for the idle time to satisfy `cond`, as events arrive in the
+/// background
+fn wait_for(wayland: &WaylandIdle, cond: impl Fn(Option<Duration>) -> bool) {
+    let start = Instant::now();
+    while !cond(wayland.get_idle().ok()) {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn wayland_idle() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+    let compositor = thread::spawn(move || {
+        // wl_display.get_registry and wl_display.sync
+        assert_eq!(receive(&mut server).0, 1);
+        assert_eq!(receive(&mut server).0, 1);
+        send_global(&mut server, 10, "wl_output");
+        send_global(&mut server, 11, "wl_seat");
+        send_global(&mut server, 12, "ext_idle_notifier_v1");
+        send(&mut server, 3, 0, &[0]);
+
+        // Two binds, get_idle_notification, then another sync
+        assert_eq!(receive(&mut server).0, 2);
+        assert_eq!(receive(&mut server).0, 2);
+        let (object, opcode, args) = receive(&mut server);
+        assert_eq!((object, opcode), (5, 1));
+        assert_eq!(&args[4..8], &1000u32.to_ne_bytes());
+        let (object, _, args) = receive(&mut server);
+        assert_eq!(object, 1);
+        let callback = u32::from_ne_bytes([args[0], args[1], args[2], args[3]]);
+        send(&mut server, callback, 0, &[0]);
+        server
+    });
+
+    let wayland = WaylandIdle::with_stream(client).unwrap();
+    let mut server = compositor.join().unwrap();
+    assert_eq!(wayland.get_idle().unwrap(), Duration::default());
+
+    // Idled, which counts from the notification's timeout
+    send(&mut server, 6, 0, &[]);
+    wait_for(&wayland, |idle| idle >= Some(Duration::from_secs(1)));
+
+    // Resumed
+    send(&mut server, 6, 1, &[]);
+    wait_for(&wayland, |idle| idle == Some(Duration::default()));
+    assert!(wayland.is_connected());
+
+    drop(server);
+    wait_for(&wayland, |idle| idle.is_none());
+    assert!(!wayland.is_connected());
+}
+
+#[test]
+fn wayland_unsupported() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+    let compositor = thread::spawn(move || {
+        receive(&mut server);
+        receive(&mut server);
+        send_global(&mut server, 11, "wl_seat");
+        send(&mut server, 3, 0, &[0]);
+        server
+    });
+
+    let err = WaylandIdle::with_stream(client).unwrap_err();
+    assert!(err.to_string().contains("ext-idle-notify-v1"));
+    compositor.join().unwrap();
+}
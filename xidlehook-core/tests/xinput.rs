@@ -0,0 +1,22 @@
+use xidlehook_core::modules::InputClasses;
+
+#[test]
+fn input_classes() {
+    assert_eq!(
+        "keyboard, button".parse(),
+        Ok(InputClasses {
+            keyboard: true,
+            button: true,
+            motion: false,
+        })
+    );
+    assert_eq!(
+        "motion,motion".parse(),
+        Ok(InputClasses {
+            motion: true,
+            ..InputClasses::default()
+        })
+    );
+    assert!("keyboard,mouse".parse::<InputClasses>().is_err());
+    assert!("".parse::<InputClasses>().is_err());
+}
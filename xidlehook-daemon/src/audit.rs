@@ -0,0 +1,80 @@
+//! An append-only log of timer activations, one line of JSON each,
+//! such as to prove that the screen locked. Unlike the regular log,
+//! this is written to and synced right away, and doesn't depend on
+//! the log level. This is used to implement `--audit-log` in the
+//! xidlehook example application.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+
+use crate::socket::Event;
+
+pub struct AuditLog {
+    path: PathBuf,
+    file: File,
+}
+impl AuditLog {
+    /// Open the log, creating it if it doesn't exist
+    pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let path = path.into();
+        let file = Self::open_file(&path)?;
+        Ok(Self { path, file })
+    }
+
+    fn open_file(path: &Path) -> io::Result<File> {
+        OpenOptions::new().append(true).create(true).open(path)
+    }
+
+    /// Whether the file at the path is no longer the one that's open,
+    /// as it was rotated or removed
+    fn moved(&self) -> bool {
+        match (fs::metadata(&self.path), self.file.metadata()) {
+            (Ok(current), Ok(open)) => current.dev() != open.dev() || current.ino() != open.ino(),
+            _ => true,
+        }
+    }
+
+    fn append(&mut self, line: &[u8]) -> io::Result<()> {
+        self.file.write_all(line)?;
+        self.file.sync_data()
+    }
+
+    pub fn record(&mut self, event: &Event) {
+        let mut line = match serde_json::to_vec(event) {
+            Ok(line) => line,
+            Err(err) => {
+                warn!("Failed to serialize {event:?} for the audit log: {err}");
+                return;
+            },
+        };
+        line.push(b'\n');
+
+        if self.moved() {
+            // Rotated, write to the new file from now on
+            if let Ok(file) = Self::open_file(&self.path) {
+                self.file = file;
+            }
+        }
+        if self.append(&line).is_ok() {
+            return;
+        }
+        // Try once more with the file opened anew
+        let result = Self::open_file(&self.path).and_then(|file| {
+            self.file = file;
+            self.append(&line)
+        });
+        if let Err(err) = result {
+            warn!(
+                "Failed to write to the audit log {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
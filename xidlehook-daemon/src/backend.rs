@@ -0,0 +1,85 @@
+//! Where the idle time comes from: The X server, or with the `wayland`
+//! feature, a Wayland compositor. The session type is detected at
+//! startup. This is used to pick the backend in the xidlehook example
+//! application.
+
+use std::{
+    env,
+    os::unix::io::{AsRawFd, RawFd},
+    rc::Rc,
+    time::Duration,
+};
+
+#[cfg(feature = "wayland")]
+use xidlehook_core::modules::WaylandIdle;
+use xidlehook_core::{modules::Xcb, Error, Result};
+
+/// Whether there's a Wayland session, but no X server to use instead.
+/// Under `XWayland`, `$DISPLAY` is set too, and the X server is preferred,
+/// as it supports more flags.
+pub fn use_wayland(display: Option<&str>) -> bool {
+    let is_set = |var| env::var_os(var).is_some_and(|value| !value.is_empty());
+    display.is_none() && !is_set("DISPLAY") && is_set("WAYLAND_DISPLAY")
+}
+
+/// The error for a flag that needs the X server
+pub fn x11_only(flag: &str) -> Error {
+    format!("{flag} is only supported on X11, not with the Wayland backend").into()
+}
+
+/// See the module-level documentation
+#[derive(Debug)]
+pub enum Backend {
+    Xcb(Rc<Xcb>),
+    #[cfg(feature = "wayland")]
+    Wayland(WaylandIdle),
+}
+impl Backend {
+    /// Connect to the Wayland compositor in `$WAYLAND_DISPLAY`
+    #[cfg(feature = "wayland")]
+    pub fn wayland() -> Result<Self> {
+        Ok(Self::Wayland(WaylandIdle::new()?))
+    }
+    /// Without Wayland support, explain why there's no backend
+    #[cfg(not(feature = "wayland"))]
+    pub fn wayland() -> Result<Self> {
+        Err("$DISPLAY isn't set, and xidlehook was built without the wayland feature".into())
+    }
+
+    /// Get the user's idle time
+    pub fn get_idle(&self) -> Result<Duration> {
+        match *self {
+            Self::Xcb(ref xcb) => xcb.get_idle(),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(ref wayland) => wayland.get_idle(),
+        }
+    }
+
+    /// Get whether or not the connection still works
+    pub fn is_connected(&self) -> bool {
+        match *self {
+            Self::Xcb(ref xcb) => xcb.is_connected(),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(ref wayland) => wayland.is_connected(),
+        }
+    }
+
+    /// Get the X server, for a flag that only works with one
+    #[cfg_attr(not(feature = "wayland"), allow(unused_variables))]
+    pub fn xcb(&self, flag: &str) -> Result<&Rc<Xcb>> {
+        match *self {
+            Self::Xcb(ref xcb) => Ok(xcb),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(_) => Err(x11_only(flag)),
+        }
+    }
+}
+impl AsRawFd for Backend {
+    fn as_raw_fd(&self) -> RawFd {
+        match *self {
+            Self::Xcb(ref xcb) => xcb.connection().as_raw_fd(),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(ref wayland) => wayland.as_raw_fd(),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Built-in screen dimming, by writing to the kernel's backlight
+//! interface in `/sys/class/backlight`. Unlike a dimming script, this
+//! ramps the brightness smoothly and remembers what to restore it to.
+
+use std::{
+    convert::TryFrom,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_std::task;
+use log::{debug, warn};
+
+const BACKLIGHT_DIR: &str = "/sys/class/backlight";
+/// How many brightness changes a ramp is made up of
+const RAMP_STEPS: u32 = 20;
+
+fn read_value(path: &Path) -> io::Result<u32> {
+    fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Report a failed write. Permission problems are common, as the
+/// brightness file is only writable by root by default.
+fn write_failed(path: &Path, err: &io::Error) {
+    if err.kind() == io::ErrorKind::PermissionDenied {
+        warn!(
+            "backlight: no permission to write to {}. Add a udev rule that lets your user write \
+             to it, such as by making it writable by the video group",
+            path.display()
+        );
+    } else {
+        warn!("backlight: failed to write to {}: {}", path.display(), err);
+    }
+}
+
+/// The shared state of a dim, which is locked for every write so that
+/// a restore can't be overwritten by a ramp step that was in flight
+struct State {
+    /// The brightness before dimming, if currently dimmed
+    saved: Option<u32>,
+    /// Increased to make running ramps stop
+    generation: u64,
+}
+
+/// Dims the backlight to a fraction of the maximum brightness, and
+/// restores it again
+pub struct Dim {
+    brightness: PathBuf,
+    max: u32,
+    /// The brightness to dim to, in percent of the maximum
+    percent: u32,
+    ramp: Duration,
+    state: Arc<Mutex<State>>,
+}
+impl Dim {
+    /// Use the first backlight device found
+    pub fn new(percent: u32, ramp: Duration) -> io::Result<Self> {
+        let device = fs::read_dir(BACKLIGHT_DIR)?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no backlight device was found")
+        })??;
+        let path = device.path();
+        debug!("Using backlight device {}", path.display());
+
+        Ok(Self {
+            brightness: path.join("brightness"),
+            max: read_value(&path.join("max_brightness"))?,
+            percent: percent.min(100),
+            ramp,
+            state: Arc::new(Mutex::new(State {
+                saved: None,
+                generation: 0,
+            })),
+        })
+    }
+
+    /// Save the current brightness and start ramping it down
+    pub fn dim(&self) {
+        let current = match read_value(&self.brightness) {
+            Ok(current) => current,
+            Err(err) => {
+                warn!(
+                    "backlight: failed to read {}: {}",
+                    self.brightness.display(),
+                    err
+                );
+                return;
+            },
+        };
+        let target = u64::from(self.max)
+            .saturating_mul(self.percent.into())
+            .checked_div(100)
+            .and_then(|target| u32::try_from(target).ok())
+            .unwrap_or(0);
+        if target >= current {
+            return;
+        }
+
+        let generation = {
+            let mut state = self.state.lock().unwrap();
+            // Keep the original brightness if this dims again without restoring in between
+            state.saved.get_or_insert(current);
+            state.generation = state.generation.wrapping_add(1);
+            state.generation
+        };
+
+        let path = self.brightness.clone();
+        let state = Arc::clone(&self.state);
+        let delay = self.ramp.checked_div(RAMP_STEPS).unwrap_or_default();
+        task::spawn(async move {
+            let distance = current.saturating_sub(target);
+            for step in 1..=RAMP_STEPS {
+                task::sleep(delay).await;
+
+                let done = distance
+                    .checked_mul(step)
+                    .and_then(|done| done.checked_div(RAMP_STEPS))
+                    .unwrap_or(distance);
+                let state = state.lock().unwrap();
+                if state.generation != generation {
+                    // Restored or dimmed again in the meantime
+                    return;
+                }
+                if let Err(err) = fs::write(&path, current.saturating_sub(done).to_string()) {
+                    write_failed(&path, &err);
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Stop any ramp and restore the brightness from before dimming
+    pub fn restore(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation = state.generation.wrapping_add(1);
+        if let Some(saved) = state.saved.take() {
+            if let Err(err) = fs::write(&self.brightness, saved.to_string()) {
+                write_failed(&self.brightness, &err);
+            }
+        }
+    }
+}
+impl PartialEq for Dim {
+    fn eq(&self, other: &Self) -> bool {
+        self.brightness == other.brightness
+            && self.percent == other.percent
+            && self.ramp == other.ramp
+    }
+}
@@ -10,6 +10,9 @@ use structopt::{clap::arg_enum, StructOpt};
 mod socket {
     include!("../socket/models.rs");
 }
+mod duration {
+    include!("../duration.rs");
+}
 
 arg_enum! {
     #[derive(Debug)]
@@ -21,6 +24,15 @@ arg_enum! {
     }
 }
 
+arg_enum! {
+    #[derive(Debug)]
+    enum OptModuleAction {
+        Enable,
+        Disable,
+        Toggle,
+    }
+}
+
 #[derive(StructOpt, Debug)]
 struct Opt {
     /// Listen to a unix socket at this address for events.
@@ -36,14 +48,19 @@ struct Opt {
 enum Subcommands {
     /// Create a new timer
     Add {
-        /// The desired idle time, in seconds, which the timer will go
-        /// off after
-        #[structopt(long)]
-        time: u64,
+        /// The desired idle time which the timer will go off after,
+        /// such as "90s", "5m" or "1h30m". A plain number is the
+        /// number of seconds.
+        #[structopt(long, parse(try_from_str = duration::parse))]
+        time: Duration,
         /// Where to insert this timer. To insert it at the beginning,
         /// set this to 0. To insert it at the end, skip this.
         #[structopt(long)]
         index: Option<socket::TimerId>,
+        /// A name for this timer, used in logs and queries. Defaults
+        /// to the activation command.
+        #[structopt(long)]
+        name: Option<String>,
         /// The shell command to run on activation, *not* passed to
         /// "sh -c" (unlike the regular application)
         #[structopt(long, value_terminator = ";", allow_hyphen_values = true)]
@@ -59,6 +76,20 @@ enum Subcommands {
         /// deactivation = next timer's activation basically)
         #[structopt(long, value_terminator = ";", allow_hyphen_values = true)]
         deactivation: Vec<String>,
+        /// Don't let any later timer go off until the activation
+        /// command has exited
+        #[structopt(long)]
+        wait: bool,
+        /// Start over from the first timer once the activation command
+        /// exits, as if the user was active
+        #[structopt(long)]
+        reset_on_exit: bool,
+    },
+    /// Remove a timer. The current chain carries on unless the timer
+    /// already went off in it.
+    Remove {
+        #[structopt(long)]
+        index: socket::TimerId,
     },
     /// A control operation
     Control {
@@ -77,6 +108,53 @@ enum Subcommands {
         #[structopt(long)]
         timer: Vec<socket::TimerId>,
     },
+    /// Hold back all timers until resume-all, leaving alone which
+    /// ones are disabled
+    PauseAll,
+    /// Let the timers run again after pause-all
+    ResumeAll,
+    /// Stop running timers until resume, treating the user as active
+    /// meanwhile, unlike pause-all
+    Pause,
+    /// Run timers again after pause, counting the idle time from now
+    Resume,
+    /// Enable, disable or toggle a module, such as
+    /// --not-when-fullscreen
+    ControlModule {
+        /// The name of the module, which is the name of the flag
+        /// that enabled it, such as "not-when-fullscreen"
+        #[structopt(long)]
+        name: String,
+        /// Whether the module should be enabled or disabled, or
+        /// toggled from one to the other, such as from a hotkey
+        #[structopt(long, possible_values = &OptModuleAction::variants(), case_insensitive = true)]
+        action: OptModuleAction,
+    },
+    /// Query the list of modules and whether they're enabled
+    QueryModules,
+    /// Make xidlehook exit
+    Shutdown,
+    /// Check that xidlehook is responsive and can reach the X server.
+    /// Exits unsuccessfully if not.
+    Ping,
+    /// Show what happened recently, such as timers going off
+    History {
+        /// Only show this many of the most recent events
+        #[structopt(long)]
+        limit: Option<usize>,
+    },
+    /// Push back the timer that's up next, such as "5m", until it
+    /// activates or the user is active again
+    Snooze {
+        #[structopt(parse(try_from_str = duration::parse))]
+        time: Duration,
+    },
+    /// Show which timer is up next and how long until it goes off,
+    /// such as for a countdown. Both are null if a module is holding
+    /// it back, or no timer is left until the user is active again.
+    NextTimer,
+    /// Show how long the user has been idle, in milliseconds
+    Idle,
 }
 
 fn filter(filter: Vec<socket::TimerId>) -> socket::Filter {
@@ -93,16 +171,23 @@ fn main() -> xidlehook_core::Result<()> {
         Subcommands::Add {
             time,
             index,
+            name,
             activation,
             abortion,
             deactivation,
+            wait,
+            reset_on_exit,
         } => socket::Message::Add(socket::Add {
-            time: Duration::from_secs(time),
+            time,
             index,
+            name,
             activation,
             abortion,
             deactivation,
+            wait,
+            reset_on_exit,
         }),
+        Subcommands::Remove { index } => socket::Message::RemoveTimer { index },
         Subcommands::Control { timer, action } => socket::Message::Control(socket::Control {
             timer: filter(timer),
             action: match action {
@@ -115,6 +200,29 @@ fn main() -> xidlehook_core::Result<()> {
         Subcommands::Query { timer } => socket::Message::Query(socket::Query {
             timer: filter(timer),
         }),
+        Subcommands::PauseAll => socket::Message::PauseAll,
+        Subcommands::ResumeAll => socket::Message::ResumeAll,
+        Subcommands::Pause => socket::Message::Pause,
+        Subcommands::Resume => socket::Message::Resume,
+        Subcommands::ControlModule { name, action } => {
+            socket::Message::ControlModule(socket::ControlModule {
+                name,
+                action: match action {
+                    OptModuleAction::Enable => socket::ModuleAction::Enable,
+                    OptModuleAction::Disable => socket::ModuleAction::Disable,
+                    OptModuleAction::Toggle => socket::ModuleAction::Toggle,
+                },
+            })
+        },
+        Subcommands::QueryModules => socket::Message::QueryModules,
+        Subcommands::Shutdown => socket::Message::Shutdown,
+        Subcommands::Ping => socket::Message::Ping,
+        Subcommands::History { limit } => socket::Message::History { limit },
+        Subcommands::Snooze { time } => socket::Message::Snooze {
+            secs: time.as_secs(),
+        },
+        Subcommands::NextTimer => socket::Message::NextTimer,
+        Subcommands::Idle => socket::Message::Idle,
     };
 
     let stream = UnixStream::connect(opt.socket)?;
@@ -127,7 +235,13 @@ fn main() -> xidlehook_core::Result<()> {
 
     if let Some(line) = reader.lines().next() {
         let reply: socket::Reply = serde_json::from_str(&line?)?;
-        println!("{:#?}", reply);
+        if !reply.ok {
+            return Err(reply
+                .error
+                .unwrap_or_else(|| String::from("unknown error"))
+                .into());
+        }
+        println!("{:#?}", reply.data);
     }
 
     Ok(())
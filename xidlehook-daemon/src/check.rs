@@ -0,0 +1,109 @@
+//! A one-shot status check of an instance of xidlehook that's already
+//! running, for `--check`. It's asked over its socket for the idle
+//! time and timers, and the result is printed as one line and
+//! reported in the exit code, like a Nagios plugin: 0 if it's healthy
+//! and the user is active, 1 if the user has been idle past the first
+//! timer, and 2 if something is wrong.
+
+use std::{
+    io::{prelude::*, BufReader},
+    os::unix::net::UnixStream,
+    time::Duration,
+};
+
+use crate::socket::{Filter, Message, Query, Reply, ReplyData};
+
+/// How long the running instance gets to reply
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const OK: i32 = 0;
+pub const IDLE: i32 = 1;
+pub const ERROR: i32 = 2;
+
+/// Send one message and read its reply
+fn request(stream: &UnixStream, reader: &mut impl BufRead, msg: &Message) -> Result<Reply, String> {
+    let mut writer = stream;
+    serde_json::to_writer(writer, msg).map_err(|err| err.to_string())?;
+    writer
+        .write_all(b"\n")
+        .and_then(|()| writer.flush())
+        .map_err(|err| format!("couldn't send message: {err}"))?;
+
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => return Err("the socket was closed without a reply".into()),
+        Ok(_) => (),
+        Err(err) => return Err(format!("couldn't read reply: {err}")),
+    }
+    let reply: Reply = serde_json::from_str(&line).map_err(|err| err.to_string())?;
+    match reply.error {
+        Some(err) if !reply.ok => Err(err),
+        _ if !reply.ok => Err("request failed".into()),
+        _ => Ok(reply),
+    }
+}
+
+/// Find out the idle time and how long the first enabled timer is
+fn status(socket: &str) -> Result<(Duration, Option<Duration>), String> {
+    let stream =
+        UnixStream::connect(socket).map_err(|err| format!("can't connect to {socket}: {err}"))?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .map_err(|err| err.to_string())?;
+    let mut reader = BufReader::new(&stream);
+
+    let idle = match request(&stream, &mut reader, &Message::Ping)?.data {
+        ReplyData::Health { xcb_ok: true, idle } => idle,
+        ReplyData::Health { xcb_ok: false, .. } => {
+            return Err("xidlehook can't reach the X server".into())
+        },
+        _ => return Err("unexpected reply to ping".into()),
+    };
+    let first = match request(
+        &stream,
+        &mut reader,
+        &Message::Query(Query { timer: Filter::All }),
+    )?
+    .data
+    {
+        ReplyData::QueryResult { timers } => timers
+            .iter()
+            .find(|timer| !timer.disabled)
+            .map(|timer| timer.time),
+        _ => return Err("unexpected reply to query".into()),
+    };
+    Ok((idle, first))
+}
+
+/// Check the instance listening on this socket, print the result, and
+/// return the exit code
+pub fn check(socket: &str) -> i32 {
+    // Whole seconds are plenty for a status line
+    let secs = |duration: Duration| Duration::from_secs(duration.as_secs());
+    match status(socket) {
+        Ok((idle, Some(first))) if idle >= first => {
+            println!(
+                "IDLE - idle for {:?}, past the first timer at {:?}",
+                secs(idle),
+                secs(first)
+            );
+            IDLE
+        },
+        Ok((idle, Some(first))) => {
+            println!(
+                "OK - idle for {:?}, the first timer is at {:?}",
+                secs(idle),
+                secs(first)
+            );
+            OK
+        },
+        Ok((idle, None)) => {
+            println!("OK - idle for {:?}, no timers are enabled", secs(idle));
+            OK
+        },
+        Err(err) => {
+            println!("ERROR - {err}");
+            ERROR
+        },
+    }
+}
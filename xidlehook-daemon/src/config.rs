@@ -0,0 +1,286 @@
+//! Reads the timers and flags from a TOML file given with `--config`,
+//! so that a long chain doesn't have to be spelled out in the command
+//! line, and can be edited and picked up again with SIGHUP. The file is
+//! turned into the command line arguments it stands for, which are
+//! parsed together with the real ones:
+//!
+//! ```toml
+//! [[timer]]
+//! duration = "5m"
+//! command = "xrandr --output eDP-1 --brightness .5"
+//! canceller = "xrandr --output eDP-1 --brightness 1"
+//!
+//! [[timer]]
+//! name = "lock"
+//! duration = 60
+//! command = "i3lock -n"
+//!
+//! [modules]
+//! not-when-fullscreen = true
+//! not-when-load-above = 4
+//! ```
+//!
+//! Arguments given in the command line override the file: Any flag in
+//! `[modules]` that's also in the command line is ignored, and so are
+//! all of the file's timers if the command line has a `--timer`.
+
+use std::{collections::BTreeMap, ffi::OsString, fs, path::Path};
+
+use serde::Deserialize;
+use toml::Value;
+
+const FLAG: &str = "--config";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default)]
+    timer: Vec<Timer>,
+    /// Any flag, without the leading dashes, and its value. Underscores
+    /// may be used instead of dashes.
+    #[serde(default)]
+    modules: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Timer {
+    /// A duration like "5m", or a number of seconds
+    duration: Value,
+    /// If set, the duration is drawn at random up to this one
+    up_to: Option<Value>,
+    command: String,
+    #[serde(default)]
+    canceller: String,
+    name: Option<String>,
+    timeout: Option<String>,
+    /// A time window, such as "23:00-06:00"
+    at: Option<String>,
+    #[serde(default)]
+    wait: bool,
+}
+
+/// Turn a scalar into the argument it stands for
+fn scalar(key: &str, value: &Value) -> Result<OsString, String> {
+    match value {
+        Value::String(s) => Ok(OsString::from(s)),
+        Value::Integer(i) => Ok(OsString::from(i.to_string())),
+        Value::Float(f) => Ok(OsString::from(f.to_string())),
+        _ => Err(format!("{key} must be a string or a number")),
+    }
+}
+
+/// Whether this flag is given in the command line
+fn has_flag(args: &[OsString], flag: &str) -> bool {
+    args.iter()
+        .take_while(|arg| *arg != "--")
+        .filter_map(|arg| arg.to_str())
+        .any(|arg| {
+            arg == flag
+                || arg
+                    .strip_prefix(flag)
+                    .is_some_and(|rest| rest.starts_with('='))
+        })
+}
+
+/// The path given with `--config`, if any. A missing path is left for
+/// the argument parser to report.
+fn path(args: &[OsString]) -> Option<&Path> {
+    let mut args = args.iter().take_while(|arg| *arg != "--");
+    while let Some(arg) = args.next() {
+        if arg == FLAG {
+            return args.next().map(Path::new);
+        }
+        if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return Some(Path::new(path));
+        }
+    }
+    None
+}
+
+/// The command line arguments a config file stands for, leaving out
+/// whatever `cli` overrides
+fn parse(text: &str, cli: &[OsString]) -> Result<Vec<OsString>, String> {
+    let config: Config = toml::from_str(text).map_err(|err| err.to_string())?;
+    let mut args = Vec::new();
+
+    for (key, value) in &config.modules {
+        let flag = format!("--{}", key.replace('_', "-"));
+        if has_flag(cli, &flag) {
+            continue;
+        }
+        match value {
+            Value::Boolean(true) => args.push(OsString::from(flag)),
+            Value::Boolean(false) => (),
+            Value::Array(values) => {
+                for value in values {
+                    args.push(OsString::from(&flag));
+                    args.push(scalar(key, value)?);
+                }
+            },
+            value => {
+                args.push(OsString::from(&flag));
+                args.push(scalar(key, value)?);
+            },
+        }
+    }
+
+    if !has_flag(cli, "--timer") {
+        for timer in &config.timer {
+            if let Some(ref name) = timer.name {
+                args.extend(vec![OsString::from("--timer-name"), OsString::from(name)]);
+            }
+            if let Some(ref timeout) = timer.timeout {
+                args.extend(vec![
+                    OsString::from("--timer-timeout"),
+                    OsString::from(timeout),
+                ]);
+            }
+            if let Some(ref up_to) = timer.up_to {
+                args.extend(vec![
+                    OsString::from("--timer-up-to"),
+                    scalar("up_to", up_to)?,
+                ]);
+            }
+            if let Some(ref at) = timer.at {
+                args.extend(vec![OsString::from("--timer-at"), OsString::from(at)]);
+            }
+            if timer.wait {
+                args.push(OsString::from("--wait"));
+            }
+            args.extend(vec![
+                OsString::from("--timer"),
+                scalar("duration", &timer.duration)?,
+                OsString::from(&timer.command),
+                OsString::from(&timer.canceller),
+            ]);
+        }
+    }
+    Ok(args)
+}
+
+/// Insert the arguments from the file given with `--config`, if any,
+/// right after the program name. The file is read again every time, so
+/// that reloading picks up changes to it.
+pub fn args<I: IntoIterator<Item = OsString>>(args: I) -> Result<Vec<OsString>, String> {
+    let mut args: Vec<OsString> = args.into_iter().collect();
+    let path = match path(&args) {
+        Some(path) => path.to_owned(),
+        None => return Ok(args),
+    };
+    let text = fs::read_to_string(&path)
+        .map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+    let config = parse(&text, &args).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let at = args.len().min(1);
+    args.splice(at..at, config);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strs(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    const CONFIG: &str = r#"
+        [[timer]]
+        duration = "5m"
+        up_to = "10m"
+        command = "dim"
+        canceller = "undim"
+
+        [[timer]]
+        name = "lock"
+        at = "23:00"
+        wait = true
+        duration = 60
+        command = "i3lock -n"
+
+        [modules]
+        not-when-fullscreen = true
+        not_when_audio = false
+        not-when-load-above = 4.5
+        fullscreen-ignore-class = ["i3lock", "mpv"]
+    "#;
+
+    #[test]
+    fn config() {
+        assert_eq!(
+            parse(CONFIG, &strs(&["xidlehook"])).unwrap(),
+            strs(&[
+                "--fullscreen-ignore-class",
+                "i3lock",
+                "--fullscreen-ignore-class",
+                "mpv",
+                "--not-when-fullscreen",
+                "--not-when-load-above",
+                "4.5",
+                "--timer-up-to",
+                "10m",
+                "--timer",
+                "5m",
+                "dim",
+                "undim",
+                "--timer-name",
+                "lock",
+                "--timer-at",
+                "23:00",
+                "--wait",
+                "--timer",
+                "60",
+                "i3lock -n",
+                "",
+            ])
+        );
+
+        assert!(parse("[[timer]]\nduration = 1", &[]).is_err());
+        assert!(parse("[modules]\nonce = [true]", &[]).is_err());
+        assert!(parse("[misc]", &[]).is_err());
+    }
+
+    #[test]
+    fn overrides() {
+        assert_eq!(
+            parse(
+                CONFIG,
+                &strs(&[
+                    "xidlehook",
+                    "--not-when-load-above=2",
+                    "--fullscreen-ignore-class",
+                    "xterm",
+                    "--timer",
+                    "1",
+                    "x",
+                    "",
+                    "--",
+                    "--not-when-fullscreen",
+                ])
+            )
+            .unwrap(),
+            strs(&["--not-when-fullscreen"])
+        );
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            path(&strs(&["xidlehook", "--config", "a.toml"])),
+            Some(Path::new("a.toml"))
+        );
+        assert_eq!(
+            path(&strs(&["xidlehook", "--config=a.toml"])),
+            Some(Path::new("a.toml"))
+        );
+        assert_eq!(path(&strs(&["xidlehook", "--config"])), None);
+        assert_eq!(
+            path(&strs(&["xidlehook", "--", "--config", "a.toml"])),
+            None
+        );
+        assert_eq!(
+            args(strs(&["xidlehook", "--once"])).unwrap(),
+            strs(&["xidlehook", "--once"])
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Accepts the same commands as the socket over D-Bus, as
+//! `com.github.xidlehook` on the session bus (or the system bus), for
+//! desktop integrations that would rather not speak JSON over a unix
+//! socket. Timer activations and abortions, and changes of the power
+//! modules' decisions, are emitted as signals. This
+//! is used to implement `--dbus` in the xidlehook example application.
+//!
+//! ```sh
+//! busctl --user call com.github.xidlehook /com/github/xidlehook com.github.xidlehook GetIdle
+//! ```
+
+use std::{
+    convert::TryInto,
+    sync::mpsc,
+    thread::{self, JoinHandle},
+};
+
+use async_std::{sync, task};
+use log::{trace, warn};
+use zbus::{dbus_interface, fdo, Connection, ObjectServer};
+
+use crate::socket::{
+    Action, Control, Event, EventKind, Filter, Message, Reply, ReplyData, TimerId,
+};
+
+pub const NAME: &str = "com.github.xidlehook";
+const PATH: &str = "/com/github/xidlehook";
+const INTERFACE: &str = "com.github.xidlehook";
+
+/// The object that's exported, which passes every call on to the main
+/// loop just like a socket connection would
+struct Xidlehook {
+    socket_tx: sync::Sender<(Message, sync::Sender<Reply>)>,
+}
+impl Xidlehook {
+    /// Send a message to the main loop and wait for its reply
+    fn send(&self, msg: Message) -> Reply {
+        let (reply_tx, reply_rx) = sync::channel(1);
+        task::block_on(async {
+            self.socket_tx.send((msg, reply_tx)).await;
+            reply_rx.recv().await
+        })
+        // There's no reply if the message stopped xidlehook
+        .unwrap_or_else(Reply::empty)
+    }
+
+    /// Like `send`, but turn a failure into a D-Bus error
+    fn request(&self, msg: Message) -> fdo::Result<ReplyData> {
+        match self.send(msg) {
+            Reply { ok: true, data, .. } => Ok(data),
+            Reply { error, .. } => Err(fdo::Error::Failed(
+                error.unwrap_or_else(|| String::from("unknown error")),
+            )),
+        }
+    }
+
+    /// Apply an action to these timers, or all of them if there are none
+    fn control(&self, timers: Vec<TimerId>, action: Action) -> fdo::Result<()> {
+        let timer = if timers.is_empty() {
+            Filter::All
+        } else {
+            Filter::Selected(timers)
+        };
+        self.request(Message::Control(Control { timer, action }))
+            .map(drop)
+    }
+}
+
+#[dbus_interface(name = "com.github.xidlehook")]
+impl Xidlehook {
+    /// The user's idle time, in milliseconds
+    fn get_idle(&self) -> fdo::Result<u64> {
+        match self.request(Message::Idle)? {
+            ReplyData::Idle { idle_ms } => Ok(idle_ms),
+            _ => Err(fdo::Error::Failed(String::from("unexpected reply"))),
+        }
+    }
+
+    fn trigger(&self, timer: TimerId) -> fdo::Result<()> {
+        self.control(vec![timer], Action::Trigger)
+    }
+
+    /// Enable these timers, or every timer if none are given
+    fn enable(&self, timers: Vec<TimerId>) -> fdo::Result<()> {
+        self.control(timers, Action::Enable)
+    }
+
+    /// Disable these timers, or every timer if none are given
+    fn disable(&self, timers: Vec<TimerId>) -> fdo::Result<()> {
+        self.control(timers, Action::Disable)
+    }
+
+    fn pause_all(&self) -> fdo::Result<()> {
+        self.request(Message::PauseAll).map(drop)
+    }
+
+    fn resume_all(&self) -> fdo::Result<()> {
+        self.request(Message::ResumeAll).map(drop)
+    }
+
+    fn pause(&self) -> fdo::Result<()> {
+        self.request(Message::Pause).map(drop)
+    }
+
+    fn resume(&self) -> fdo::Result<()> {
+        self.request(Message::Resume).map(drop)
+    }
+
+    fn snooze(&self, secs: u64) -> fdo::Result<()> {
+        self.request(Message::Snooze { secs }).map(drop)
+    }
+
+    fn shutdown(&self) -> fdo::Result<()> {
+        self.request(Message::Shutdown).map(drop)
+    }
+
+    /// Send any socket message as JSON, and get the JSON reply. This
+    /// covers every message, not just the ones with their own method.
+    fn call(&self, message: &str) -> fdo::Result<String> {
+        let msg = serde_json::from_str(message)
+            .map_err(|err| fdo::Error::InvalidArgs(format!("couldn't interpret message: {err}")))?;
+        serde_json::to_string(&self.send(msg)).map_err(|err| fdo::Error::Failed(err.to_string()))
+    }
+}
+
+/// Emit a signal for every timer that activates or is aborted, and
+/// every change of a power module's decision, until there are no more
+/// events
+fn emit_signals(conn: &Connection, events: &mpsc::Receiver<Event>) {
+    for event in events {
+        let (signal, body) = match event.kind {
+            EventKind::Activated => ("TimerActivated", event.timer),
+            EventKind::Aborted => ("TimerAborted", event.timer),
+            EventKind::PowerChanged => ("PowerChanged", event.message),
+            EventKind::Deactivated | EventKind::Warning => continue,
+        };
+        let body = body.unwrap_or_default();
+        if let Err(err) = conn.emit_signal(None, PATH, INTERFACE, signal, &body) {
+            warn!("D-Bus: can't emit {signal}: {err}");
+        }
+    }
+}
+
+/// Connect to the session bus, or the system bus if `system` is set,
+/// take the name `com.github.xidlehook`, and serve calls in a separate
+/// thread by sending them to `socket_tx`. Each of `events` is emitted
+/// as a signal if it's about a timer activating or being aborted, or
+/// about a power module's decision.
+pub fn serve(
+    system: bool,
+    socket_tx: sync::Sender<(Message, sync::Sender<Reply>)>,
+    events: mpsc::Receiver<Event>,
+) -> xidlehook_core::Result<JoinHandle<()>> {
+    let conn = if system {
+        Connection::new_system()?
+    } else {
+        Connection::new_session()?
+    };
+    let reply =
+        fdo::DBusProxy::new(&conn)?.request_name(NAME, fdo::RequestNameFlags::DoNotQueue.into())?;
+    if reply != fdo::RequestNameReply::PrimaryOwner {
+        return Err(format!("the D-Bus name {NAME} is already taken").into());
+    }
+
+    trace!("Serving {NAME} on D-Bus");
+
+    let signal_conn = conn.clone();
+    // Never joined, it's fine for it to be killed on exit
+    thread::spawn(move || emit_signals(&signal_conn, &events));
+
+    Ok(thread::spawn(move || {
+        let mut server = ObjectServer::new(&conn);
+        if let Err(err) = server.at(&PATH.try_into().unwrap(), Xidlehook { socket_tx }) {
+            warn!("D-Bus: can't export {PATH}: {err}");
+            return;
+        }
+        loop {
+            if let Err(err) = server.try_handle_next() {
+                if let zbus::Error::Io(_) = err {
+                    warn!("D-Bus: lost the connection: {err}");
+                    return;
+                }
+                warn!("D-Bus: couldn't handle a call: {err}");
+            }
+        }
+    }))
+}
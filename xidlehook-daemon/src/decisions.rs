@@ -0,0 +1,68 @@
+//! Prints the structured trace of xidlehook's decisions (see
+//! `xidlehook_core::DECISION_TARGET`) as one line of JSON per record
+//! on stderr, for debugging and dashboards. All other records are
+//! passed on to the regular logger.
+
+use std::io::{self, Write};
+
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    Log, Metadata, Record,
+};
+use serde_json::{Map, Value as Json};
+use xidlehook_core::DECISION_TARGET;
+
+pub struct DecisionLogger {
+    inner: Box<dyn Log>,
+}
+impl DecisionLogger {
+    pub fn new(inner: Box<dyn Log>) -> Self {
+        Self { inner }
+    }
+}
+
+/// Collects the key-value pairs of a record, keeping numbers and
+/// booleans as such
+struct Fields(Map<String, Json>);
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(n) = value.to_u64() {
+            Json::Number(n.into())
+        } else if let Some(b) = value.to_bool() {
+            Json::Bool(b)
+        } else if value.to_string() == "None" {
+            Json::Null
+        } else {
+            Json::String(value.to_string())
+        };
+        self.0.insert(key.as_str().into(), value);
+        Ok(())
+    }
+}
+
+impl Log for DecisionLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target() == DECISION_TARGET || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.target() != DECISION_TARGET {
+            self.inner.log(record);
+            return;
+        }
+
+        let mut fields = Fields(Map::new());
+        fields
+            .0
+            .insert("message".into(), Json::String(record.args().to_string()));
+        // Can only fail if the visitor fails, which it doesn't
+        let _ = record.key_values().visit(&mut fields);
+
+        // There's nowhere left to report logging errors to
+        let _ = writeln!(io::stderr(), "{}", Json::Object(fields.0));
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
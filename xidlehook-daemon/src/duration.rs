@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+/// Parse a bare amount of seconds, such as `90` or `0.5`, with
+/// millisecond precision. Any further decimals are ignored.
+fn parse_secs(input: &str) -> Option<Duration> {
+    let mut parts = input.splitn(2, '.');
+    let whole = parts.next().unwrap_or_default();
+    let fraction = parts.next().unwrap_or_default();
+    if whole.is_empty() && fraction.is_empty()
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let secs = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let millis = format!("{:0<3}", fraction.get(..3).unwrap_or(fraction))
+        .parse()
+        .ok()?;
+    Duration::from_secs(secs).checked_add(Duration::from_millis(millis))
+}
+
+/// Parse a human-friendly duration such as `90s`, `5m` or `1h30m`.
+/// For compatibility, a bare number is treated as an amount of
+/// seconds, which may have decimals such as `0.5`.
+pub fn parse(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    if let Some(duration) = parse_secs(input) {
+        return Ok(duration);
+    }
+    humantime::parse_duration(input).map_err(|err| {
+        format!("invalid duration {input:?}: {err} (try something like 90, 0.5, 90s, 5m or 1h30m)")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_seconds() {
+        assert_eq!(parse("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse("0.5"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse(".25"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse("2."), Ok(Duration::from_secs(2)));
+        assert_eq!(parse("1.2345"), Ok(Duration::from_millis(1234)));
+        assert_eq!(parse("1m30s"), Ok(Duration::from_secs(90)));
+        assert!(parse(".").is_err());
+        assert!(parse("-1").is_err());
+        assert!(parse("1.5.5").is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Remembers how far the chain of timers got when exiting, so that
+//! the next instance can pick up where this one left off, instead of
+//! running the same timers again. The episode is only continued if the
+//! idle time kept increasing in the meantime, meaning the user wasn't
+//! active. This is used to implement `--persist-episode` in the
+//! xidlehook example application.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use xidlehook_core::{Episode, Module, Timer, Xidlehook};
+
+/// How much the idle time may fall behind the time that passed since
+/// the episode was saved, and still count as the same episode
+const TOLERANCE: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Saved {
+    /// The number of timers, which must match for the episode to fit
+    timers: usize,
+    next_index: usize,
+    base_idle_time: Duration,
+    /// The idle time when this was saved
+    idle: Duration,
+    /// When this was saved, since the Unix epoch
+    time: Duration,
+}
+
+/// The file the episode is saved to, which is specific to the X
+/// display. Returns `None` if `$XDG_RUNTIME_DIR` isn't set.
+pub fn path() -> Option<PathBuf> {
+    let display: String = env::var("DISPLAY")
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let dir = env::var_os("XDG_RUNTIME_DIR")?;
+    Some(PathBuf::from(dir).join(format!("xidlehook-episode{display}.json")))
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Save the current episode, if any timers are active. Otherwise,
+/// remove any episode saved before.
+pub fn save<T: Timer, M: Module>(
+    path: &Path,
+    xidlehook: &Xidlehook<T, M>,
+    idle: Duration,
+) -> xidlehook_core::Result<()> {
+    let Some(episode) = xidlehook.episode() else {
+        return match fs::remove_file(path) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result.map_err(Into::into),
+        };
+    };
+    let saved = Saved {
+        timers: xidlehook.timers().len(),
+        next_index: episode.next_index,
+        base_idle_time: episode.base_idle_time,
+        idle,
+        time: now(),
+    };
+    debug!("Saving {:?} to {}", saved, path.display());
+    fs::write(path, serde_json::to_vec(&saved)?)?;
+    Ok(())
+}
+
+/// Continue the episode saved to this file, if the user hasn't been
+/// active since. The file is removed either way, as it's only good
+/// for one restart.
+pub fn restore<T: Timer, M: Module>(
+    path: &Path,
+    xidlehook: &mut Xidlehook<T, M>,
+    idle: Duration,
+) -> xidlehook_core::Result<()> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    fs::remove_file(path)?;
+    let saved: Saved = serde_json::from_slice(&data)?;
+
+    let Some(passed) = now().checked_sub(saved.time) else {
+        debug!("The episode was saved in the future, not continuing it");
+        return Ok(());
+    };
+    let expected = saved
+        .idle
+        .saturating_add(passed)
+        .checked_sub(TOLERANCE)
+        .unwrap_or_default();
+    if idle < expected {
+        debug!("Idle for {idle:?}, but expected at least {expected:?}, the user was active since");
+        return Ok(());
+    }
+    if saved.timers != xidlehook.timers().len() {
+        debug!("The timers changed since, not continuing the episode");
+        return Ok(());
+    }
+
+    let episode = Episode {
+        next_index: saved.next_index,
+        base_idle_time: saved.base_idle_time,
+    };
+    if xidlehook.resume_episode(episode, idle)? {
+        info!(
+            "Continuing where the previous instance left off, at timer {}",
+            saved.next_index
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,285 @@
+//! Expands each `--timer-group`, `--escalation` and `--timer-range`
+//! into plain `--timer`s, before the arguments are parsed. This way,
+//! the expanded timers are just like any other, and flags that apply to
+//! the timer right after them apply to the first expanded timer. This
+//! is used to implement `--timer-group`, `--escalation` and
+//! `--timer-range` in the xidlehook example application.
+
+use crate::duration;
+
+use std::{ffi::OsString, iter::Peekable, time::Duration};
+
+const FLAG: &str = "--timer-group";
+const ESCALATION_FLAG: &str = "--escalation";
+const RANGE_FLAG: &str = "--timer-range";
+/// Ends the steps of an escalation, when it's not followed by a flag
+const TERMINATOR: &str = ";";
+/// Replaced by the argument of each stage
+const PLACEHOLDER: &str = "{}";
+
+/// The `--timer` arguments for every stage of a group
+fn expand_group(stages: &str, command: &str, canceller: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for (i, stage) in stages.split(',').enumerate() {
+        let mut parts = stage.splitn(2, '=');
+        let duration = parts.next().unwrap_or_default().trim();
+        if duration.is_empty() {
+            return Err(format!(
+                "invalid {FLAG} stages {stages:?} (try something like 60=0.8,30=0.5)"
+            ));
+        }
+        let arg = parts
+            .next()
+            .map_or_else(|| i.saturating_add(1).to_string(), String::from);
+        args.push(String::from("--timer"));
+        args.push(duration.to_string());
+        args.push(command.replace(PLACEHOLDER, &arg));
+        args.push(canceller.replace(PLACEHOLDER, &arg));
+    }
+    Ok(args)
+}
+
+/// Format a duration so that `duration::parse` reads it back
+fn format_duration(duration: Duration) -> String {
+    format!("{}.{:03}", duration.as_secs(), duration.subsec_millis())
+}
+
+/// The `--timer` arguments for every step of an escalation. Each step
+/// gets the same canceller: Only the step that went off last is
+/// aborted when the user returns, so the canceller runs exactly once.
+fn expand_escalation<I>(args: &mut Peekable<I>) -> Result<Vec<OsString>, String>
+where
+    I: Iterator<Item = OsString>,
+{
+    let canceller = args
+        .next()
+        .ok_or_else(|| format!("{ESCALATION_FLAG} is missing its canceller"))?;
+
+    let mut expanded = Vec::new();
+    let mut previous = Duration::default();
+    loop {
+        let offset = match args.peek() {
+            Some(next) if next == TERMINATOR => {
+                args.next();
+                break;
+            },
+            Some(next) if !next.to_string_lossy().starts_with("--") => args.next().unwrap(),
+            _ => break,
+        };
+        let offset_str = offset.to_string_lossy();
+        let command = args.next().ok_or_else(|| {
+            format!("{ESCALATION_FLAG} step at {offset_str} is missing its command")
+        })?;
+        let offset_duration = duration::parse(&offset_str)
+            .map_err(|err| format!("invalid {ESCALATION_FLAG} offset: {err}"))?;
+        if !expanded.is_empty() && offset_duration <= previous {
+            return Err(format!(
+                "{} offsets count from when the user became idle, so they must increase, but {} \
+                 doesn't come after {}",
+                ESCALATION_FLAG,
+                offset_str,
+                format_duration(previous)
+            ));
+        }
+
+        expanded.push(OsString::from("--timer"));
+        expanded.push(OsString::from(format_duration(
+            offset_duration.saturating_sub(previous),
+        )));
+        expanded.push(command);
+        expanded.push(canceller.clone());
+        previous = offset_duration;
+    }
+
+    if expanded.is_empty() {
+        return Err(format!(
+            "{ESCALATION_FLAG} needs at least one step (try something like --escalation '' 5m warn 6m lock)"
+        ));
+    }
+    Ok(expanded)
+}
+
+/// Replace every `--timer-group`, `--escalation` and `--timer-range` in
+/// the command line arguments. Incomplete groups and ranges are left
+/// alone, for the argument parser to report.
+pub fn args<I: IntoIterator<Item = OsString>>(args: I) -> Result<Vec<OsString>, String> {
+    let mut args = args.into_iter().peekable();
+    let mut expanded = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            expanded.push(arg);
+            expanded.extend(args);
+            break;
+        }
+        if arg == ESCALATION_FLAG {
+            expanded.extend(expand_escalation(&mut args)?);
+            continue;
+        }
+        if arg == RANGE_FLAG {
+            let values: Vec<OsString> = args.by_ref().take(4).collect();
+            if let [min, max, command, canceller] = &values[..] {
+                expanded.extend(vec![
+                    OsString::from("--timer-up-to"),
+                    max.clone(),
+                    OsString::from("--timer"),
+                    min.clone(),
+                    command.clone(),
+                    canceller.clone(),
+                ]);
+            } else {
+                expanded.push(arg);
+                expanded.extend(values);
+            }
+            continue;
+        }
+        if arg != FLAG {
+            expanded.push(arg);
+            continue;
+        }
+
+        let values: Vec<OsString> = args.by_ref().take(3).collect();
+        let strings: Option<Vec<&str>> = values.iter().map(|value| value.to_str()).collect();
+        if let Some(&[stages, command, canceller]) = strings.as_deref() {
+            expanded.extend(
+                expand_group(stages, command, canceller)?
+                    .into_iter()
+                    .map(OsString::from),
+            );
+        } else {
+            expanded.push(arg);
+            expanded.extend(values);
+        }
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_strs(args: &[&str]) -> Result<Vec<OsString>, String> {
+        super::args(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(
+            expand_strs(&[
+                "xidlehook",
+                "--timer-group",
+                "60=0.8,30=.5,1m",
+                "dim {}",
+                "dim 1",
+                "--timer",
+                "10",
+                "lock",
+                "",
+            ])
+            .unwrap(),
+            expand_strs(&[
+                "xidlehook",
+                "--timer",
+                "60",
+                "dim 0.8",
+                "dim 1",
+                "--timer",
+                "30",
+                "dim .5",
+                "dim 1",
+                "--timer",
+                "1m",
+                "dim 3",
+                "dim 1",
+                "--timer",
+                "10",
+                "lock",
+                "",
+            ])
+            .unwrap()
+        );
+        assert!(expand_strs(&["xidlehook", "--timer-group", "60,,30", "dim", ""]).is_err());
+
+        // Incomplete groups are left for the argument parser to complain about
+        assert_eq!(
+            expand_strs(&["xidlehook", "--timer-group", "60"]).unwrap(),
+            expand_strs(&["xidlehook", "--timer-group", "60"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            expand_strs(&[
+                "xidlehook",
+                "--timer-range",
+                "5m",
+                "10m",
+                "notify-send Break",
+                "",
+                "--once",
+            ])
+            .unwrap(),
+            expand_strs(&[
+                "xidlehook",
+                "--timer-up-to",
+                "10m",
+                "--timer",
+                "5m",
+                "notify-send Break",
+                "",
+                "--once",
+            ])
+            .unwrap()
+        );
+        assert_eq!(
+            expand_strs(&["xidlehook", "--timer-range", "5m", "10m"]).unwrap(),
+            expand_strs(&["xidlehook", "--timer-range", "5m", "10m"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn escalations() {
+        assert_eq!(
+            expand_strs(&[
+                "xidlehook",
+                "--escalation",
+                "undo",
+                "5m",
+                "warn",
+                "6m",
+                "lock",
+                "1h",
+                "suspend",
+                "--not-when-audio",
+            ])
+            .unwrap(),
+            expand_strs(&[
+                "xidlehook",
+                "--timer",
+                "300.000",
+                "warn",
+                "undo",
+                "--timer",
+                "60.000",
+                "lock",
+                "undo",
+                "--timer",
+                "3240.000",
+                "suspend",
+                "undo",
+                "--not-when-audio",
+            ])
+            .unwrap()
+        );
+        assert_eq!(
+            expand_strs(&["xidlehook", "--escalation", "", "1.5", "warn", ";", "x"]).unwrap(),
+            expand_strs(&["xidlehook", "--timer", "1.500", "warn", "", "x"]).unwrap()
+        );
+
+        // Offsets must increase, every step needs a command, and there must be a step
+        assert!(expand_strs(&["xidlehook", "--escalation", "", "6m", "a", "5m", "b"]).is_err());
+        assert!(expand_strs(&["xidlehook", "--escalation", "", "6m", "a", "5m"]).is_err());
+        assert!(expand_strs(&["xidlehook", "--escalation", "", ";"]).is_err());
+        assert!(expand_strs(&["xidlehook", "--escalation"]).is_err());
+    }
+}
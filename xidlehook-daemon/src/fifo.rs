@@ -0,0 +1,77 @@
+//! Writes the idle time to a named pipe, so that status bars and
+//! scripts can read it without talking to the socket. Writes never
+//! block: when nobody is reading, or the reader falls behind, the
+//! value is dropped. This is used to implement `--idle-fifo` in the
+//! xidlehook example application.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{FileTypeExt, OpenOptionsExt},
+    path::PathBuf,
+    time::Duration,
+};
+
+use log::{debug, warn};
+use nix::{libc, sys::stat::Mode, unistd};
+
+/// How often the idle time is written, at the least
+pub const INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct IdleFifo {
+    path: PathBuf,
+    /// The open pipe, while there's a reader
+    file: Option<File>,
+}
+impl IdleFifo {
+    /// Use the FIFO at this path, creating it if it doesn't exist
+    pub fn create<P: Into<PathBuf>>(path: P) -> xidlehook_core::Result<Self> {
+        let path = path.into();
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_fifo() => (),
+            Ok(_) => return Err(format!("{} exists and is not a FIFO", path.display()).into()),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                unistd::mkfifo(&path, Mode::S_IRUSR | Mode::S_IWUSR)?;
+            },
+            Err(err) => return Err(err.into()),
+        }
+        Ok(Self { path, file: None })
+    }
+
+    /// Write the idle time in whole seconds, on a line of its own
+    pub fn write(&mut self, idle: Duration) {
+        if self.file.is_none() {
+            self.file = match OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(&self.path)
+            {
+                Ok(file) => Some(file),
+                // Nobody is reading
+                Err(ref err) if err.raw_os_error() == Some(libc::ENXIO) => return,
+                Err(err) => {
+                    warn!("Failed to open {}: {}", self.path.display(), err);
+                    return;
+                },
+            };
+            debug!("A reader connected to {}", self.path.display());
+        }
+
+        let line = format!("{}\n", idle.as_secs());
+        // A line this short is written either completely or not at all
+        let result = self.file.as_mut().map(|file| file.write(line.as_bytes()));
+        match result {
+            Some(Err(ref err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                // The reader hasn't caught up, drop this value
+            },
+            Some(Err(err)) => {
+                if err.kind() != io::ErrorKind::BrokenPipe {
+                    warn!("Failed to write to {}: {}", self.path.display(), err);
+                }
+                debug!("The reader of {} disconnected", self.path.display());
+                self.file = None;
+            },
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Keeps the most recent events in memory, so that a client that
+//! connects to the socket later can find out what happened before.
+//! Activations are also written to the `--audit-log`, if any.
+
+#[cfg(feature = "dbus")]
+use std::sync::mpsc;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use xidlehook_core::{modules::battery::Power, Error, Module, Result};
+
+use crate::{
+    audit::AuditLog,
+    socket::{Event, EventKind},
+};
+
+/// How many events are kept, older ones are dropped
+const CAPACITY: usize = 100;
+
+/// Clones share the same events. This is also a module, which records
+/// the warnings reported to modules.
+#[derive(Clone, Default)]
+pub struct History {
+    events: Rc<RefCell<VecDeque<Event>>>,
+    audit: Option<Rc<RefCell<AuditLog>>>,
+    /// Every event is also sent to these, for D-Bus signals
+    #[cfg(feature = "dbus")]
+    listeners: Rc<RefCell<Vec<mpsc::Sender<Event>>>>,
+}
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also write activations to this audit log
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(Rc::new(RefCell::new(audit)));
+        self
+    }
+
+    /// Receive every event that's recorded from now on, in another
+    /// thread
+    #[cfg(feature = "dbus")]
+    pub fn subscribe(&self) -> mpsc::Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        self.listeners.borrow_mut().push(tx);
+        rx
+    }
+
+    pub fn record(&self, kind: EventKind, timer: Option<&str>, message: Option<String>) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let event = Event {
+            time,
+            kind,
+            timer: timer.map(String::from),
+            message,
+        };
+        if let (EventKind::Activated, Some(audit)) = (kind, &self.audit) {
+            audit.borrow_mut().record(&event);
+        }
+        #[cfg(feature = "dbus")]
+        self.listeners
+            .borrow_mut()
+            .retain(|listener| listener.send(event.clone()).is_ok());
+
+        let mut events = self.events.borrow_mut();
+        if events.len() >= CAPACITY {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// A callback for `NotWhenBattery::on_change`, which records whenever
+    /// the module named `module` starts or stops holding timers back
+    pub fn power_changes(&self, module: &'static str) -> impl FnMut(bool, Power) {
+        let history = self.clone();
+        move |aborting, power| {
+            let decision = if aborting {
+                "holding timers back"
+            } else {
+                "letting timers run"
+            };
+            let source = if power.on_ac { "on AC" } else { "on battery" };
+            let charge = power
+                .battery
+                .map_or_else(String::new, |charge| format!(", {charge:.0}%"));
+            let message = format!("{module}: {decision} ({source}{charge})");
+            history.record(EventKind::PowerChanged, None, Some(message));
+        }
+    }
+
+    /// The most recent events, at most `limit` of them, oldest first
+    pub fn recent(&self, limit: Option<usize>) -> Vec<Event> {
+        let events = self.events.borrow();
+        let skip = limit.map_or(0, |limit| events.len().saturating_sub(limit));
+        events.iter().skip(skip).cloned().collect()
+    }
+}
+impl Module for History {
+    fn warning(&mut self, error: &Error) -> Result<()> {
+        self.record(EventKind::Warning, None, Some(error.to_string()));
+        Ok(())
+    }
+}
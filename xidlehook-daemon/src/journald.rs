@@ -0,0 +1,117 @@
+//! A logger that sends records straight to the systemd journal using
+//! its native protocol, instead of writing text to stderr. This keeps
+//! the priority of each message, and structured fields such as the
+//! index of the timer an event is about.
+
+use std::{io, os::unix::net::UnixDatagram};
+
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    Level, LevelFilter, Log, Metadata, Record,
+};
+
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+const IDENTIFIER: &str = "xidlehook";
+
+pub struct JournalLogger {
+    socket: UnixDatagram,
+    /// Reuse `env_logger`'s filtering, so `RUST_LOG` works just like
+    /// with the text logger
+    filter: env_logger::Logger,
+}
+impl JournalLogger {
+    /// Connect to the journal. This fails if systemd isn't running.
+    pub fn connect() -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(JOURNAL_SOCKET)?;
+        Ok(Self {
+            socket,
+            filter: env_logger::Builder::from_default_env().build(),
+        })
+    }
+
+    /// The most verbose level this logs
+    pub fn filter(&self) -> LevelFilter {
+        self.filter.filter()
+    }
+}
+
+/// Append a field in the journal's native format. Values containing
+/// newlines have to be length-prefixed.
+fn add_field(buf: &mut Vec<u8>, key: &str, value: &[u8]) {
+    buf.extend_from_slice(key.as_bytes());
+    if value.contains(&b'\n') {
+        buf.push(b'\n');
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend_from_slice(value);
+    buf.push(b'\n');
+}
+
+fn priority(level: Level) -> &'static str {
+    match level {
+        Level::Error => "3",
+        Level::Warn => "4",
+        Level::Info => "6",
+        Level::Debug | Level::Trace => "7",
+    }
+}
+
+/// Adds the key-value pairs of a record as journal fields. Journal
+/// field names may only contain uppercase letters, digits and
+/// underscores.
+struct Fields<'a>(&'a mut Vec<u8>);
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let key: String = key
+            .as_str()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        add_field(
+            self.0,
+            &format!("XIDLEHOOK_{key}"),
+            value.to_string().as_bytes(),
+        );
+        Ok(())
+    }
+}
+
+impl Log for JournalLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.filter.matches(record) {
+            return;
+        }
+
+        let mut buf = Vec::new();
+        add_field(&mut buf, "MESSAGE", record.args().to_string().as_bytes());
+        add_field(&mut buf, "PRIORITY", priority(record.level()).as_bytes());
+        add_field(&mut buf, "SYSLOG_IDENTIFIER", IDENTIFIER.as_bytes());
+        add_field(&mut buf, "TARGET", record.target().as_bytes());
+        if let Some(file) = record.file() {
+            add_field(&mut buf, "CODE_FILE", file.as_bytes());
+        }
+        if let Some(line) = record.line() {
+            add_field(&mut buf, "CODE_LINE", line.to_string().as_bytes());
+        }
+        // Can only fail if the visitor fails, which it doesn't
+        let _ = record.key_values().visit(&mut Fields(&mut buf));
+
+        // There's nowhere left to report logging errors to
+        let _ = self.socket.send(&buf);
+    }
+
+    fn flush(&self) {}
+}
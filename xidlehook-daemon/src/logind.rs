@@ -0,0 +1,96 @@
+//! Runs a timer right before the system suspends, by listening for
+//! systemd-logind's `PrepareForSleep` signal on the system bus. To
+//! give the timer a chance to run (such as to lock the screen),
+//! suspending is delayed with an inhibitor lock until it has been
+//! activated, or until logind stops waiting (see `InhibitDelayMaxSec`
+//! in logind.conf).
+
+use std::{
+    fs::File,
+    os::unix::io::{FromRawFd, IntoRawFd},
+    thread::{self, JoinHandle},
+};
+
+use async_std::{sync, task};
+use log::{trace, warn};
+use zbus::{fdo::DBusProxy, zvariant::OwnedFd, Connection};
+
+const LOGIND: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_MANAGER: &str = "org.freedesktop.login1.Manager";
+const MATCH_RULE: &str = "type='signal',sender='org.freedesktop.login1',\
+                          interface='org.freedesktop.login1.Manager',member='PrepareForSleep'";
+
+pub enum Sleep {
+    /// The system is about to suspend. Suspending is delayed until
+    /// the inhibitor lock is dropped, if one could be taken.
+    Suspending(Option<File>),
+    /// The system resumed from suspend
+    Resumed,
+}
+
+/// Take a delay inhibitor lock for sleep, which is released when the
+/// returned file is closed
+fn inhibit(conn: &Connection) -> Option<File> {
+    let result = conn
+        .call_method(
+            Some(LOGIND),
+            LOGIND_PATH,
+            Some(LOGIND_MANAGER),
+            "Inhibit",
+            &(
+                "sleep",
+                "xidlehook",
+                "Running the timer set to run before suspending",
+                "delay",
+            ),
+        )
+        .and_then(|reply| reply.body::<OwnedFd>().map_err(zbus::Error::from));
+    match result {
+        Ok(fd) => Some(unsafe { File::from_raw_fd(fd.into_raw_fd()) }),
+        Err(err) => {
+            warn!("logind: can't delay suspending, the timer might run too late: {err}");
+            None
+        },
+    }
+}
+
+/// Whether this message is `PrepareForSleep`, and if so, its argument
+fn prepare_for_sleep(msg: &zbus::Message) -> Option<bool> {
+    let header = msg.header().ok()?;
+    if header.interface().ok()?? != LOGIND_MANAGER || header.member().ok()?? != "PrepareForSleep" {
+        return None;
+    }
+    msg.body::<bool>().ok()
+}
+
+/// Connect to the system bus, and notify `tx` in a separate thread
+/// whenever the system is about to suspend or has resumed
+pub fn watch_sleep(tx: sync::Sender<Sleep>) -> xidlehook_core::Result<JoinHandle<()>> {
+    let conn = Connection::new_system()?;
+    DBusProxy::new(&conn)?.add_match(MATCH_RULE)?;
+    let mut lock = inhibit(&conn);
+
+    Ok(thread::spawn(move || loop {
+        let msg = match conn.receive_message() {
+            Ok(msg) => msg,
+            Err(err) => {
+                warn!("logind: lost the connection to the system bus: {err}");
+                return;
+            },
+        };
+        match prepare_for_sleep(&msg) {
+            Some(true) => {
+                trace!("logind: preparing for sleep");
+                task::block_on(tx.send(Sleep::Suspending(lock.take())));
+            },
+            Some(false) => {
+                trace!("logind: resumed from sleep");
+                // Arm the lock again for the next suspend
+                lock = inhibit(&conn);
+                task::block_on(tx.send(Sleep::Resumed));
+            },
+            None => (),
+        }
+    }))
+}
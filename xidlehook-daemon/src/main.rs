@@ -15,22 +15,80 @@
     clippy::pedantic,
 )]
 
-use std::{fs, rc::Rc, time::Duration};
+use std::{
+    convert::TryInto,
+    env, fs,
+    io::{self, Write},
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+    process,
+    rc::Rc,
+    time::Duration,
+};
 
 use async_std::{future, prelude::*, sync, task};
-use log::{trace, warn};
-use nix::{libc, sys::signal::Signal};
-use structopt::StructOpt;
+use futures::stream::{LocalBoxStream, SelectAll};
+use log::{debug, info, trace, warn, LevelFilter, Log};
+use nix::{
+    errno::Errno,
+    libc,
+    sys::{
+        signal::{self, Signal},
+        stat::{self, Mode},
+        wait::WaitStatus,
+    },
+    unistd::{self, Pid},
+};
+use structopt::{
+    clap::{arg_enum, ArgMatches},
+    StructOpt,
+};
 use xidlehook_core::{
-    modules::{StopAt, Xcb},
-    Module, Xidlehook,
+    modules::{
+        ExecModule, IdleSource, InputClasses, NotWhenBattery, NotWhenLoadAbove, OnResume, OnStop,
+        Quiet, StartupGrace, StopAfter, StopAt, Toggle, WarningSummary, Xcb,
+    },
+    timers::TimeWindow,
+    Action, Module, Xidlehook,
 };
 
+mod audit;
+mod backend;
+#[cfg(feature = "backlight")]
+mod backlight;
+mod check;
+mod config;
+#[cfg(feature = "dbus")]
+mod dbus;
+mod decisions;
+mod duration;
+mod episode;
+mod expand;
+mod fifo;
+mod history;
+#[cfg(feature = "journald")]
+mod journald;
+#[cfg(feature = "logind")]
+mod logind;
+mod notify;
+mod outputs;
+mod replace;
+mod session;
+mod signal_action;
 mod signal_handler;
 mod socket;
 mod timers;
 
-use self::timers::CmdTimer;
+use self::{
+    audit::AuditLog,
+    backend::Backend,
+    decisions::DecisionLogger,
+    fifo::IdleFifo,
+    history::History,
+    outputs::{OutputCondition, Outputs},
+    signal_action::SignalAction,
+    timers::{CmdTimer, FailureAction, Failures},
+};
 
 struct Defer<F: FnMut()>(F);
 impl<F: FnMut()> Drop for Defer<F> {
@@ -39,184 +97,1466 @@ impl<F: FnMut()> Drop for Defer<F> {
     }
 }
 
+arg_enum! {
+    #[derive(Debug)]
+    pub enum FailurePolicy {
+        Exit,
+        Ignore,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    pub enum MaxFailuresPolicy {
+        Exit,
+        Disable,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    pub enum IdleSourceOpt {
+        Auto,
+        ScreenSaver,
+        Sync,
+    }
+}
+
+#[cfg(feature = "journald")]
+arg_enum! {
+    #[derive(Debug)]
+    pub enum LogFormat {
+        Text,
+        Journald,
+    }
+}
+
 #[derive(StructOpt, Debug)]
 pub struct Opt {
+    /// Read timers and flags from this TOML file, see the README for
+    /// its format. Flags given in the command line override the ones
+    /// in the file, and any --timer replaces all of the file's timers.
+    /// The file is read again on SIGHUP.
+    #[structopt(long, value_name = "path")]
+    pub config: Option<PathBuf>,
     /// Print the idle time to standard output. This is similar to xprintidle.
     #[structopt(long)]
     pub print: bool,
+    /// Like --print, but keep printing the idle time, once every
+    /// --poll-interval, until interrupted. Useful for finding out what
+    /// resets the idle time.
+    #[structopt(long, conflicts_with("print"))]
+    pub print_idle_loop: bool,
+    /// How often --print-idle-loop prints the idle time, such as "1s"
+    /// or "0.1"
+    #[structopt(long, default_value = "1s", requires("print-idle-loop"), parse(try_from_str = duration::parse))]
+    pub poll_interval: Duration,
+    /// Print the X server's own screensaver timeout and cycle, in
+    /// seconds, to standard output. This is similar to `xset q`.
+    #[structopt(long, conflicts_with("print"))]
+    pub print_screensaver: bool,
+    /// The X display to connect to, such as ":1". Defaults to the one
+    /// in $DISPLAY. Timer commands are run with this display too.
+    #[structopt(long)]
+    pub display: Option<String>,
+    /// Count switching the active window as activity, even if it
+    /// wasn't done with the keyboard or mouse, such as by a script
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]))]
+    pub active_window_activity: bool,
+    /// Where to read the idle time from: The MIT-SCREEN-SAVER
+    /// extension ("screensaver"), or the SYNC extension's IDLETIME
+    /// counter ("sync"). Try the other one if the idle time seems to
+    /// be under-reported. By default ("auto"), the first one that
+    /// works is used, in that order.
+    #[structopt(long, default_value = "auto", possible_values = &IdleSourceOpt::variants(), case_insensitive = true)]
+    pub idle_source: IdleSourceOpt,
+    /// Only count these kinds of input as activity, separated by
+    /// commas: "keyboard", "button" (including scrolling) and
+    /// "motion". For example, "keyboard,button" ignores a drifting
+    /// trackpoint. This reads the X server's raw input events instead
+    /// of the --idle-source, and falls back to the --idle-source with a
+    /// warning if the X server doesn't support them at all.
+    #[structopt(long, value_name = "classes", conflicts_with_all(&["print", "print-screensaver"]))]
+    pub reset_on: Option<InputClasses>,
+    /// Start out as if the user had already been idle for this long,
+    /// such as "50m", until they are active. Useful for trying out
+    /// long timers without waiting for them.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), parse(try_from_str = duration::parse))]
+    pub initial_idle: Option<Duration>,
+    /// Don't run any timer for this long after xidlehook starts, such
+    /// as "30s". At login, the idle time may already be high from
+    /// before xidlehook was started, which would otherwise lock the
+    /// screen right away.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), parse(try_from_str = duration::parse))]
+    pub startup_grace: Option<Duration>,
+    /// The longest xidlehook sleeps before checking the idle time
+    /// again, such as "5m", so that a sleep which outlasts a suspend
+    /// of the system is cut short. Without a cap, it would sleep right
+    /// until the next timer is due.
+    #[structopt(long, default_value = "1m", conflicts_with_all(&["print", "print-screensaver"]), parse(try_from_str = duration::parse))]
+    pub max_sleep: Duration,
     /// Exit after the whole chain of timer commands have been invoked
     /// once
     #[structopt(long, conflicts_with("print"))]
     pub once: bool,
-    /// Don't invoke the timer when the current application is
-    /// fullscreen. Useful for preventing a lockscreen when watching
-    /// videos.
+    /// Run this command when --once is done, right before exiting.
+    /// xidlehook waits for it to finish, but kills it if it's still
+    /// running after 30 seconds. It's passed through the
+    /// --command-shell, unless --no-shell is used.
+    #[structopt(long, requires("once"))]
+    pub once_command: Option<String>,
+    /// Exit after the whole chain of timer commands have been invoked
+    /// this many times, such as to nag three times and then give up.
+    /// Chains that are cut short, by the user being back or by a flag
+    /// like --not-when-audio, don't count. Disabled timers at the end
+    /// are left out of the chain.
+    #[structopt(long, conflicts_with("print"), value_name = "n")]
+    pub stop_after: Option<usize>,
+    /// Run this command once the user returns after any timer went
+    /// off, such as to turn the monitor back on. Unlike a canceller,
+    /// it doesn't matter which timer went off last. It's passed
+    /// through the --command-shell, unless --no-shell is used.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]))]
+    pub on_resume: Option<String>,
+    /// Don't invoke the timer when an application on the visible
+    /// desktop is fullscreen, unless it's minimized. Useful for
+    /// preventing a lockscreen when watching videos.
     #[structopt(long, conflicts_with("print"))]
     pub not_when_fullscreen: bool,
+    /// Keep treating the application as fullscreen for this long
+    /// after it leaves fullscreen, such as "2s". Avoids flapping when
+    /// fullscreen is toggled rapidly, like when seeking in a video.
+    #[structopt(long, requires("not-when-fullscreen"), parse(try_from_str = duration::parse))]
+    pub fullscreen_grace: Option<Duration>,
+    /// Also count a picture-in-picture video window, like browsers
+    /// open, as fullscreen, as the video keeps playing in it
+    #[structopt(long, requires("not-when-fullscreen"))]
+    pub fullscreen_pip: bool,
+    /// Don't count fullscreen windows of this class, as shown by
+    /// xprop, ignoring case. Use this when the lock screen
+    /// goes fullscreen, so that timers after locking, such as one to
+    /// suspend, still run. Can be given multiple times.
+    #[structopt(long, requires("not-when-fullscreen"), number_of_values = 1)]
+    pub fullscreen_ignore_class: Vec<String>,
 
-    /// The duration is the number of seconds of inactivity which
-    /// should trigger this timer.
+    /// The duration is the amount of inactivity which should trigger
+    /// this timer, such as "90s", "5m" or "1h30m". A plain number is
+    /// the number of seconds, and may have decimals such as "0.5".
     ///
     /// The command is what is invoked when the idle duration is
-    /// reached. It's passed through \"/bin/sh -c\".
+    /// reached. It's passed through the --command-shell.
     ///
     /// The canceller is what is invoked when the user becomes active
     /// after the timer has gone off, but before the next timer (if
     /// any). Pass an empty string to not have one.
-    #[structopt(long, conflicts_with("print"), required_unless("print"), value_names = &["duration", "command", "canceller"])]
+    ///
+    /// Timers may only be left out when using --socket or
+    /// --stdin-commands, in which case xidlehook waits for timers to
+    /// be added that way.
+    #[structopt(long, conflicts_with_all(&["print", "print-idle-loop", "print-screensaver"]), required_unless_one(&["print", "print-idle-loop", "print-screensaver", "socket", "stdin-commands"]), value_names = &["duration", "command", "canceller"])]
     pub timer: Vec<String>,
+    /// Name the --timer right after this, to tell timers apart in logs
+    /// and socket queries. Timers are otherwise named after their
+    /// command.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), number_of_values = 1)]
+    pub timer_name: Vec<String>,
+    /// Kill the command of the --timer right after this if it's still
+    /// running after this long, such as a locker that hangs. It's sent
+    /// SIGTERM, and SIGKILL if it hasn't exited 5 seconds later. Not
+    /// for commands that run until the user is back, like "i3lock -n".
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), number_of_values = 1, parse(try_from_str = duration::parse))]
+    pub timer_timeout: Vec<Duration>,
+    /// Only let the --timer right after this go off within this time
+    /// of the day, in local time, such as "23:00" to lock the screen
+    /// if idle past 23:00, until midnight. The end may be given too,
+    /// such as "23:00-06:00". The timer's duration is still the idle
+    /// time it takes, so that it doesn't go off the moment the user is
+    /// active within the window.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), number_of_values = 1)]
+    pub timer_at: Vec<TimeWindow>,
+    /// Let the --timer right after this take a random duration, from
+    /// its own up to this one, drawn anew whenever the chain starts
+    /// over
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), number_of_values = 1, parse(try_from_str = duration::parse))]
+    pub timer_up_to: Vec<Duration>,
+    /// A timer that takes a random duration between min and max, drawn
+    /// anew whenever the chain starts over, such as for break
+    /// reminders that shouldn't be predictable. This is the same as
+    /// --timer-up-to max --timer min command canceller.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), value_names = &["min", "max", "command", "canceller"])]
+    pub timer_range: Vec<String>,
+    /// Several timers in a row that run the same command with a
+    /// different argument, such as to dim the screen in steps. The
+    /// stages are a comma-separated list of durations, each optionally
+    /// followed by "=" and an argument, such as "60=0.8,30=0.5". Any
+    /// "{}" in the command and canceller is replaced by the stage's
+    /// argument, or by its number (starting at 1) if it has none. This
+    /// is the same as one --timer for every stage.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), value_names = &["stages", "command", "canceller"])]
+    pub timer_group: Vec<String>,
+    /// A whole escalation in one go, such as to warn, then lock, then
+    /// suspend: The canceller, followed by any number of steps, each
+    /// an offset and a command. Offsets count from when the user
+    /// became idle, not from the previous step, such as "5m" to warn
+    /// and "6m" to lock. The steps end at the next flag or a lone ";".
+    /// The canceller is run once when the user returns, whichever step
+    /// went off last. This is the same as one --timer for every step.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), min_values = 3, value_terminator = ";", value_names = &["canceller", "offset", "command"])]
+    pub escalation: Vec<String>,
+    /// Skip the --timer right after this while these outputs
+    /// (monitors) are connected: Either a number of outputs that must
+    /// at least be connected, or a comma-separated list of output
+    /// names, as shown by xrandr, that must all be connected. For
+    /// example, "--skip-when-outputs 2" before a timer that dims the
+    /// screen doesn't dim while docked to an external monitor.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), number_of_values = 1)]
+    pub skip_when_outputs: Vec<String>,
+    /// Like --skip-when-outputs, but skip the --timer right after this
+    /// unless these outputs are connected
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), number_of_values = 1)]
+    pub only_when_outputs: Vec<String>,
+    /// Make the --timer right after this wait for its command to exit
+    /// before any later timer can go off, like "i3lock -n" does until
+    /// the screen is unlocked. Other timers keep running their
+    /// commands in the background. While waiting, no timers are
+    /// checked at all, so the timer is only aborted once the command
+    /// has exited. There's no time limit on how long this waits.
+    #[structopt(long, parse(from_occurrences), conflicts_with_all(&["print", "print-screensaver"]))]
+    pub wait: u64,
+    /// Treat the command of the --timer right after this exiting as
+    /// activity, and start over from the first timer right away. This
+    /// is best for lockers that block until the screen is unlocked,
+    /// such as "i3lock -n", as the idle time may take a moment to
+    /// reset after unlocking.
+    #[structopt(long, parse(from_occurrences), conflicts_with_all(&["print", "print-screensaver"]))]
+    pub reset_on_exit: u64,
+    /// Send a signal to a process when the --timer right after this
+    /// goes off, such as "polybar USR1", without going through a
+    /// shell. The process is a PID or a name, and if several processes
+    /// have that name, the one with the lowest PID gets it. If there's
+    /// no such process, that's only logged. The timer's command
+    /// still runs too, so leave it empty if that's all it should do.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), number_of_values = 2, value_names = &["process", "signal"])]
+    pub signal: Vec<String>,
+    /// Show a desktop notification with this summary and body when the
+    /// --timer right after this goes off, and close it once you're
+    /// back. "{}" is replaced by the timer's index. Use it on the timer
+    /// before the locker to warn that the screen is about to lock.
+    #[cfg(feature = "notify")]
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]), number_of_values = 2, value_names = &["summary", "body"])]
+    pub notify: Vec<String>,
+
+    /// Never run any canceller, regardless of what the timers
+    /// specify. Timers still reset when the user becomes active, and
+    /// modules are still reset, only the commands are skipped.
+    #[structopt(long, conflicts_with("print"))]
+    pub no_cancel: bool,
+
+    /// Don't run any command, only log the commands that would run,
+    /// and when. Signals aren't sent and the backlight isn't dimmed
+    /// either, but modules work as usual, so it's safe to try out a
+    /// chain that ends in "systemctl suspend".
+    #[structopt(long, conflicts_with("print"))]
+    pub dry_run: bool,
+
+    /// Hold back a timer that went off this many times within
+    /// --max-activations-window, until the first of those is that long
+    /// ago, with a loud warning. This protects against runaway loops,
+    /// like a lock command that exits right away.
+    #[structopt(long, conflicts_with("print"), value_name = "n")]
+    pub max_activations: Option<u32>,
+    /// See --max-activations
+    #[structopt(long, default_value = "1m", conflicts_with("print"), parse(try_from_str = duration::parse))]
+    pub max_activations_window: Duration,
+
+    /// Exit successfully when the X session ends, instead of failing
+    /// because the X server went away. This is the default when
+    /// started by a session manager.
+    #[structopt(long, conflicts_with("print"))]
+    pub exit_with_session: bool,
+
+    /// Show a desktop notification (using notify-send) when a timer's
+    /// command can't be run, or exits unsuccessfully
+    #[structopt(long, conflicts_with("print"))]
+    pub notify_on_failure: bool,
+
+    /// Give up after this many timer commands in a row couldn't be run
+    /// or exited unsuccessfully, across all timers. See
+    /// --on-max-failures for what giving up means.
+    #[structopt(long, conflicts_with("print"))]
+    pub max_failures: Option<u32>,
+    /// What to do after --max-failures: Exit with an error (so a
+    /// supervisor can restart xidlehook), or disable the timer whose
+    /// command failed last.
+    #[structopt(long, default_value = "exit", requires("max-failures"), possible_values = &MaxFailuresPolicy::variants(), case_insensitive = true)]
+    pub on_max_failures: MaxFailuresPolicy,
+
+    /// Don't pass timer commands through the --command-shell. Instead,
+    /// split them into arguments like a shell would, but without any
+    /// variable expansion, globbing or other interpolation, and run
+    /// the program directly. Quote arguments containing spaces.
+    #[structopt(long, conflicts_with("print"))]
+    pub no_shell: bool,
+    /// The shell to pass timer commands, the --fallback and the
+    /// --exec-module through, with "-c" and the command appended. May
+    /// include arguments, such as "/usr/bin/env bash".
+    #[structopt(long, default_value = timers::DEFAULT_SHELL, conflicts_with_all(&["print", "no-shell"]))]
+    pub command_shell: String,
+
+    /// Measure the first timer in session time instead of idle time:
+    /// It goes off once its duration has passed since xidlehook
+    /// started (or since it last went off), whether or not the user
+    /// was idle. Useful for reminders to take a break. Like any
+    /// timer, it won't go off again until the user has been active.
+    #[structopt(long, conflicts_with("print"))]
+    pub first_timer_session: bool,
+
+    /// Dim the backlight to this percentage of the maximum brightness
+    /// when the timer with this index goes off, and restore it when
+    /// the user becomes active. The brightness is changed gradually,
+    /// see --backlight-ramp.
+    #[cfg(feature = "backlight")]
+    #[structopt(long, conflicts_with("print"), value_names = &["timer", "percent"])]
+    pub backlight_dim: Vec<u32>,
+    /// How long dimming the backlight takes
+    #[cfg(feature = "backlight")]
+    #[structopt(long, default_value = "1s", conflicts_with("print"), parse(try_from_str = duration::parse))]
+    pub backlight_ramp: Duration,
+
+    /// Activate the timer with this index right before the system
+    /// suspends, regardless of the idle time, such as to lock the
+    /// screen. Suspending is delayed until it has been activated. Its
+    /// canceller runs as usual once the user is active after resuming.
+    /// Requires systemd-logind.
+    #[cfg(feature = "logind")]
+    #[structopt(long, conflicts_with("print"), value_name = "timer")]
+    pub lock_on_suspend: Option<usize>,
+
+    /// Warn about timers that go off less than this long after the
+    /// previous one, as that's most likely a mistake. Timers with a
+    /// duration of zero are always warned about.
+    #[structopt(long, default_value = "1s", conflicts_with("print"), parse(try_from_str = duration::parse))]
+    pub min_timer_gap: Duration,
+    /// Treat problems found when checking the timers as errors
+    /// instead of warnings
+    #[structopt(long, conflicts_with_all(&["print", "no-timer-check"]))]
+    pub strict: bool,
+    /// Don't check the timers for likely mistakes at all
+    #[structopt(long, conflicts_with("print"))]
+    pub no_timer_check: bool,
+
+    /// What to do when a timer's command fails to run or exits
+    /// unsuccessfully: Exit with an error (so a supervisor can restart
+    /// xidlehook), or ignore it with a warning. By default, xidlehook
+    /// exits if a command can't be run, but only warns if it exits
+    /// unsuccessfully.
+    #[structopt(long, conflicts_with("print"), possible_values = &FailurePolicy::variants(), case_insensitive = true)]
+    pub on_failure: Option<FailurePolicy>,
+    /// When a timer's command fails to run or exits unsuccessfully,
+    /// log a warning and run this command instead. It's passed through
+    /// the --command-shell.
+    #[structopt(long, conflicts_with_all(&["print", "on-failure"]))]
+    pub fallback: Option<String>,
 
     /// Don't invoke the timer when any audio is playing (PulseAudio specific)
     #[cfg(feature = "pulse")]
     #[structopt(long, conflicts_with("print"))]
     pub not_when_audio: bool,
+    /// Like --not-when-audio, but also don't invoke the timer when
+    /// any audio is being recorded, such as when a microphone is in
+    /// use (`PulseAudio` specific)
+    #[cfg(feature = "pulse")]
+    #[structopt(long, conflicts_with("print"))]
+    pub not_when_recording: bool,
+
+    /// Don't invoke the timer when any process is using a video
+    /// device, such as a webcam during a video call
+    #[cfg(feature = "camera")]
+    #[structopt(long, conflicts_with("print"))]
+    pub not_when_camera: bool,
+
+    /// Don't invoke the timer while the screen is being shared, such
+    /// as in a video call, as reported by pw-dump
+    #[cfg(feature = "screencast")]
+    #[structopt(long, conflicts_with("print"))]
+    pub not_when_screencasting: bool,
+
+    /// Don't invoke the timer while the 1-minute load average is above
+    /// this, such as "4" during a long build on a 4-core machine
+    #[structopt(long, conflicts_with("print"))]
+    pub not_when_load_above: Option<f64>,
+
+    /// Don't invoke the timer while on AC power, such as to only
+    /// suspend when running on battery
+    #[structopt(long, conflicts_with("print"))]
+    pub not_when_on_ac: bool,
+    /// Don't invoke the timer while the battery is charged less than
+    /// this many percent
+    #[structopt(long, conflicts_with("print"))]
+    pub not_when_battery_below: Option<f64>,
+
+    /// Don't invoke the timer when any media player reports that it's
+    /// playing over MPRIS, even if it's muted
+    #[cfg(feature = "mpris")]
+    #[structopt(long, conflicts_with("print"))]
+    pub not_when_playing: bool,
+
+    /// Don't invoke the timer while an application holds a
+    /// systemd-logind inhibitor lock of the --inhibit-types, such as a
+    /// video player or presentation tool
+    #[cfg(feature = "logind")]
+    #[structopt(long, conflicts_with("print"))]
+    pub not_when_inhibited: bool,
+    /// Which inhibitor lock types --not-when-inhibited honors,
+    /// separated by commas, such as "idle,sleep"
+    #[cfg(feature = "logind")]
+    #[structopt(
+        long,
+        default_value = "idle",
+        requires("not-when-inhibited"),
+        use_delimiter = true
+    )]
+    pub inhibit_types: Vec<String>,
+
+    /// Ask this command whether to run each timer. It's passed
+    /// through the --command-shell. Exiting with 0 lets the timer run, 1
+    /// aborts the chain until the user is active again, 2 stops
+    /// xidlehook, and 3 runs no more timers until the user is active
+    /// again, but without aborting the last one. The command's output
+    /// is sent to stderr.
+    #[structopt(long, conflicts_with("print"))]
+    pub exec_module: Option<String>,
+    /// Kill --exec-module if it doesn't exit within this long, and run
+    /// the timer
+    #[structopt(long, default_value = "5s", conflicts_with("print"), parse(try_from_str = duration::parse))]
+    pub exec_module_timeout: Duration,
 
     /// Listen to a unix socket at this address for events.
     /// Each event is one line of JSON data.
     #[structopt(long, conflicts_with("print"))]
     pub socket: Option<String>,
+    /// Also read socket messages from standard input, and print the
+    /// replies to standard output. Useful for testing.
+    #[structopt(long, conflicts_with("print"))]
+    pub stdin_commands: bool,
+    /// How many times to retry binding the unix socket if it fails
+    #[structopt(long, default_value = "3", conflicts_with("print"))]
+    pub socket_retries: u32,
+    /// How many milliseconds to wait before the first retry of
+    /// binding the unix socket. This doubles for every attempt.
+    #[structopt(long, default_value = "250", conflicts_with("print"))]
+    pub socket_backoff: u64,
+    /// Also accept the same commands over D-Bus, as
+    /// com.github.xidlehook on the session bus, and emit a signal
+    /// whenever a timer activates or is aborted
+    #[cfg(feature = "dbus")]
+    #[structopt(long, conflicts_with("print"))]
+    pub dbus: bool,
+    /// Use the system bus for --dbus instead of the session bus
+    #[cfg(feature = "dbus")]
+    #[structopt(long, requires("dbus"))]
+    pub dbus_system: bool,
+
+    /// Shut down the instance of xidlehook that's already running with
+    /// the same --socket or --pidfile, if there is one, and take over
+    /// once it has exited
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]))]
+    pub replace: bool,
+    /// Check on the instance of xidlehook that's listening on --socket
+    /// and exit, for monitoring. Prints one line of status, and exits
+    /// with 0 if the user is active, 1 if the user has been idle past
+    /// the first timer, and 2 on errors.
+    #[structopt(long, requires("socket"), conflicts_with_all(&["print", "print-screensaver", "replace"]))]
+    pub check: bool,
+    /// Write the process ID to this file, and remove it on exit.
+    /// xidlehook never forks into the background, so this is the PID
+    /// of the process you started.
+    #[structopt(long, conflicts_with("print"))]
+    pub pidfile: Option<String>,
+    /// Set the umask, in octal such as "077", so the socket, pidfile
+    /// and any other files xidlehook creates get the same permissions
+    /// no matter how it was started. Like any umask, it also applies
+    /// to the commands xidlehook runs.
+    #[structopt(long, parse(try_from_str = parse_umask))]
+    pub umask: Option<Mode>,
+    /// Write the idle time in seconds, one line at a time, to this
+    /// named pipe (FIFO), creating it if needed. Values are dropped
+    /// while nobody is reading. The idle time is then checked at
+    /// least once a second.
+    #[structopt(long, conflicts_with("print"))]
+    pub idle_fifo: Option<String>,
+    /// Remember which timers went off when exiting, in a file in the
+    /// user's runtime directory, and continue from there when started
+    /// again, unless the user was active in between. This avoids
+    /// running the same timers twice when restarting xidlehook.
+    #[structopt(long, conflicts_with("print"))]
+    pub persist_episode: bool,
+    /// Don't log warnings about errors xidlehook recovers from, such
+    /// as a module failing to check whether to run a timer, nor the
+    /// summary of them on exit. Other log messages are unaffected.
+    #[structopt(long, conflicts_with("print"))]
+    pub quiet: bool,
+    /// Append a line of JSON to this file every time a timer
+    /// activates, with the time and the timer's name, such as to prove
+    /// that the screen locked. Each line is synced to disk right away.
+    /// The file is opened again when it's rotated.
+    #[structopt(long, conflicts_with("print"))]
+    pub audit_log: Option<String>,
+
+    /// Where to send log messages. "journald" sends them straight to
+    /// the systemd journal with their priority and extra fields, and
+    /// falls back to "text" on stderr when systemd isn't running.
+    #[cfg(feature = "journald")]
+    #[structopt(long, default_value = "text", possible_values = &LogFormat::variants(), case_insensitive = true)]
+    pub log_format: LogFormat,
+    /// Print a structured trace of every decision xidlehook makes to
+    /// stderr, as one line of JSON per record: The idle time and
+    /// resulting action of every poll, and what each module said
+    /// before and after a timer. Useful for finding out why a timer
+    /// did or didn't go off.
+    #[structopt(long, conflicts_with_all(&["print", "print-screensaver"]))]
+    pub trace_decisions: bool,
+}
+
+fn text_logger() -> (Box<dyn Log>, LevelFilter) {
+    let logger = env_logger::Builder::from_default_env().build();
+    let filter = logger.filter();
+    (Box::new(logger), filter)
+}
+
+fn init_logger(opt: &Opt) -> xidlehook_core::Result<()> {
+    #[cfg(feature = "journald")]
+    let (logger, filter, journal_err) = match opt.log_format {
+        LogFormat::Journald => match journald::JournalLogger::connect() {
+            Ok(logger) => {
+                let filter = logger.filter();
+                (Box::new(logger) as Box<dyn Log>, filter, None)
+            },
+            Err(err) => {
+                let (logger, filter) = text_logger();
+                (logger, filter, Some(err))
+            },
+        },
+        LogFormat::Text => {
+            let (logger, filter) = text_logger();
+            (logger, filter, None)
+        },
+    };
+    #[cfg(not(feature = "journald"))]
+    let (logger, filter) = text_logger();
+
+    if opt.trace_decisions {
+        log::set_max_level(LevelFilter::Trace);
+        log::set_boxed_logger(Box::new(DecisionLogger::new(logger)))?;
+    } else {
+        log::set_max_level(filter);
+        log::set_boxed_logger(logger)?;
+    }
+
+    #[cfg(feature = "journald")]
+    {
+        if let Some(err) = journal_err {
+            warn!("can't connect to the journal, logging to stderr: {err}");
+        }
+    }
+    Ok(())
 }
 
 fn main() -> xidlehook_core::Result<()> {
-    env_logger::init();
+    let args = match config::args(env::args_os()).and_then(expand::args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return Ok(());
+        },
+    };
+    let matches = Opt::clap().get_matches_from(args);
+    let opt = Opt::from_clap(&matches);
+
+    init_logger(&opt)?;
+
+    if let Some(mask) = opt.umask {
+        // Files are created all throughout, such as when the audit log is rotated, so this
+        // can't be undone afterwards
+        let previous = stat::umask(mask);
+        debug!("Changed the umask from {previous:03o} to {mask:03o}");
+    }
+
+    if opt.check {
+        // `requires` ensures there's a socket
+        process::exit(check::check(opt.socket.as_deref().unwrap()));
+    }
+
+    if opt.replace {
+        replace::replace(opt.socket.as_deref(), opt.pidfile.as_deref())?;
+    }
+
+    if let Some(ref display) = opt.display {
+        // Timer commands should use the same display
+        env::set_var("DISPLAY", display);
+    }
+    let backend = Rc::new(build_backend(&opt)?);
+
+    if print(&opt, &backend)? {
+        return Ok(());
+    }
+
+    let command_shell = match parse_command_shell(&opt.command_shell) {
+        Ok(command_shell) => command_shell,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return Ok(());
+        },
+    };
+    let mut timers = match build_timers(&opt, &matches, &backend, &command_shell) {
+        Ok(timers) => timers,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return Ok(());
+        },
+    };
+
+    let mut history = History::new();
+    if let Some(ref path) = opt.audit_log {
+        history = history.with_audit_log(AuditLog::open(path)?);
+    }
+    let failures = opt.max_failures.map(|max| {
+        let disable = matches!(opt.on_max_failures, MaxFailuresPolicy::Disable);
+        Rc::new(Failures::new(max, disable))
+    });
+    for timer in &mut timers {
+        timer.set_history(Some(history.clone()));
+        timer.set_backend(Some(Rc::clone(&backend)));
+        timer.set_failures(failures.clone());
+    }
+
+    if timers.is_empty() {
+        info!("No timers were given, waiting for timers to be added over the socket");
+    }
 
-    let opt = Opt::from_args();
+    let warnings = WarningSummary::new();
+    let hooks = match HookCommands::parse(&opt, &command_shell) {
+        Ok(hooks) => hooks,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return Ok(());
+        },
+    };
+    let modules = build_modules(&opt, &backend, &history, &warnings, &command_shell, hooks)?;
 
-    let xcb = Rc::new(Xcb::new()?);
+    let idle_fifo = opt.idle_fifo.as_ref().map(IdleFifo::create).transpose()?;
 
+    let logger: Box<dyn Module> = if opt.quiet {
+        Box::new(Quiet)
+    } else {
+        Box::new(())
+    };
+    let mut xidlehook = Xidlehook::new(timers)
+        .with_max_sleep(opt.max_sleep)
+        .with_module(logger)
+        .register(modules);
+    let episode_path = restore_episode(&opt, &backend, &mut xidlehook)?;
+
+    let quiet = opt.quiet;
+    let result = App {
+        opt,
+        backend,
+        xidlehook,
+        paused: false,
+        failures,
+        history,
+        waiting: None,
+        idle_fifo,
+        episode_path,
+        idle_failures: 0,
+    }
+    .main_loop();
+
+    if !quiet {
+        warnings.log();
+    }
+    result
+}
+
+/// Handle --print, --print-idle-loop and --print-screensaver, and
+/// return whether one of them was used, which means there's nothing
+/// left to do
+fn print(opt: &Opt, backend: &Backend) -> xidlehook_core::Result<bool> {
     if opt.print {
-        let idle = xcb.get_idle()?;
+        let idle = backend.get_idle()?;
         println!("{}", idle.as_millis());
-        return Ok(());
+    } else if opt.print_idle_loop {
+        print_idle_loop(backend, opt.poll_interval)?;
+    } else if opt.print_screensaver {
+        let screensaver = backend.xcb("--print-screensaver")?.get_screensaver()?;
+        println!("timeout: {}", screensaver.timeout.as_secs());
+        println!("cycle: {}", screensaver.interval.as_secs());
+    } else {
+        return Ok(false);
     }
+    Ok(true)
+}
 
-    let mut timers = Vec::new();
-    let mut iter = opt.timer.iter().peekable();
-    while iter.peek().is_some() {
-        // clap-rs will ensure there are always a multiple of 3
-        let duration: u64 = match iter.next().unwrap().parse() {
-            Ok(duration) => duration,
-            Err(err) => {
-                eprintln!("error: failed to parse duration as number: {}", err);
-                return Ok(());
-            },
+/// Continue the episode of the previous instance for
+/// --persist-episode, if enabled, and return where to save it on exit
+fn restore_episode<M: Module>(
+    opt: &Opt,
+    backend: &Backend,
+    xidlehook: &mut Xidlehook<CmdTimer, M>,
+) -> xidlehook_core::Result<Option<PathBuf>> {
+    if !opt.persist_episode {
+        return Ok(None);
+    }
+    let Some(path) = episode::path() else {
+        warn!("$XDG_RUNTIME_DIR is not set, not using --persist-episode");
+        return Ok(None);
+    };
+    if let Err(err) = episode::restore(&path, xidlehook, backend.get_idle()?) {
+        warn!(
+            "Failed to restore the episode from {}: {}",
+            path.display(),
+            err
+        );
+    }
+    Ok(Some(path))
+}
+
+/// Connect to the Wayland compositor or the X server, as configured
+fn build_backend(opt: &Opt) -> xidlehook_core::Result<Backend> {
+    Ok(if backend::use_wayland(opt.display.as_deref()) {
+        let x11_only = [
+            (
+                "--idle-source",
+                !matches!(opt.idle_source, IdleSourceOpt::Auto),
+            ),
+            ("--active-window-activity", opt.active_window_activity),
+            ("--reset-on", opt.reset_on.is_some()),
+            ("--initial-idle", opt.initial_idle.is_some()),
+        ];
+        if let Some(&(flag, _)) = x11_only.iter().find(|&&(_, used)| used) {
+            return Err(backend::x11_only(flag));
+        }
+        info!("Reading idle time from the Wayland compositor");
+        Backend::wayland()?
+    } else {
+        let mut xcb = Xcb::with_display(opt.display.as_deref())?;
+        match opt.idle_source {
+            IdleSourceOpt::Auto => (),
+            IdleSourceOpt::ScreenSaver => xcb.set_idle_source(IdleSource::ScreenSaver)?,
+            IdleSourceOpt::Sync => xcb.set_idle_source(IdleSource::Sync)?,
+        }
+        if let Some(classes) = opt.reset_on {
+            if let Err(err) = xcb.set_idle_source(IdleSource::XInput(classes)) {
+                warn!("Can't read raw input events, counting any input as activity: {err}");
+            }
+        }
+        info!("Reading idle time from {:?}", xcb.idle_source());
+        if opt.active_window_activity {
+            xcb.watch_active_window()?;
+        }
+        if let Some(offset) = opt.initial_idle {
+            xcb.set_initial_idle(offset);
+        }
+        Backend::Xcb(Rc::new(xcb))
+    })
+}
+
+/// The commands of --once-command and --on-resume, split like timer
+/// commands are
+struct HookCommands {
+    once_command: Option<Vec<String>>,
+    on_resume: Option<Vec<String>>,
+}
+impl HookCommands {
+    fn parse(opt: &Opt, command_shell: &[String]) -> Result<Self, String> {
+        let split = |flag: &str, command: Option<&str>| {
+            command
+                .map(|command| {
+                    if opt.no_shell {
+                        shell_words::split(command).map_err(|err| format!("invalid {flag}: {err}"))
+                    } else {
+                        Ok(timers::shell(command_shell, command.to_owned()))
+                    }
+                })
+                .transpose()
         };
-        timers.push(CmdTimer::from_shell(
-            Duration::from_secs(duration),
-            iter.next().unwrap().into(),
-            iter.next().unwrap().into(),
-            String::new(),
-        ));
+        Ok(Self {
+            once_command: split("--once-command", opt.once_command.as_deref())?,
+            on_resume: split("--on-resume", opt.on_resume.as_deref())?,
+        })
     }
+}
+
+/// Create the modules of the flags that enable one, in the order
+/// they're registered in
+fn build_modules(
+    opt: &Opt,
+    backend: &Backend,
+    history: &History,
+    warnings: &WarningSummary,
+    command_shell: &[String],
+    hooks: HookCommands,
+) -> xidlehook_core::Result<Modules> {
+    // Every module is named after its flag, so it can be disabled over the socket
+    let mut modules: Modules = Vec::new();
 
-    let mut modules: Vec<Box<dyn Module>> = Vec::new();
+    // Count warnings first, in case another module turns one into an error
+    modules.push(Toggle::new("warning-summary", Box::new(warnings.clone())));
+    modules.push(Toggle::new("history", Box::new(history.clone())));
+
+    if let Some(grace) = opt.startup_grace {
+        modules.push(Toggle::new(
+            "startup-grace",
+            Box::new(StartupGrace::new(grace)),
+        ));
+    }
 
     if opt.once {
-        modules.push(Box::new(StopAt::completion()));
+        let once: Box<dyn Module> = match hooks.once_command {
+            Some(command) => Box::new(OnStop::new(StopAt::completion(), command)),
+            None => Box::new(StopAt::completion()),
+        };
+        modules.push(Toggle::new("once", once));
+    }
+    if let Some(times) = opt.stop_after {
+        modules.push(Toggle::new("stop-after", Box::new(StopAfter::times(times))));
+    }
+    if let Some(command) = hooks.on_resume {
+        modules.push(Toggle::new("on-resume", Box::new(OnResume::new(command))));
     }
+    add_inhibitors(&mut modules, opt, backend, history)?;
+    if let Some(command) = opt.exec_module.clone() {
+        modules.push(Toggle::new(
+            "exec-module",
+            Box::new(
+                ExecModule::new(command, opt.exec_module_timeout)
+                    .with_shell(command_shell.to_vec()),
+            ),
+        ));
+    }
+    Ok(modules)
+}
+
+/// Add the modules that hold timers back while something is going on,
+/// such as --not-when-fullscreen
+fn add_inhibitors(
+    modules: &mut Modules,
+    opt: &Opt,
+    backend: &Backend,
+    history: &History,
+) -> xidlehook_core::Result<()> {
     if opt.not_when_fullscreen {
-        modules.push(Box::new(Rc::clone(&xcb).not_when_fullscreen()));
+        let mut not_when_fullscreen =
+            Rc::clone(backend.xcb("--not-when-fullscreen")?).not_when_fullscreen();
+        if let Some(grace) = opt.fullscreen_grace {
+            not_when_fullscreen = not_when_fullscreen.with_grace(grace);
+        }
+        not_when_fullscreen = not_when_fullscreen.with_pip(opt.fullscreen_pip);
+        for class in &opt.fullscreen_ignore_class {
+            not_when_fullscreen = not_when_fullscreen.with_ignored_class(class.clone());
+        }
+        modules.push(Toggle::new(
+            "not-when-fullscreen",
+            Box::new(not_when_fullscreen),
+        ));
     }
     #[cfg(feature = "pulse")]
     {
-        if opt.not_when_audio {
-            modules.push(Box::new(xidlehook_core::modules::NotWhenAudio::new()?))
+        if opt.not_when_audio || opt.not_when_recording {
+            modules.push(Toggle::new(
+                "not-when-audio",
+                Box::new(xidlehook_core::modules::NotWhenAudio::new_with_recording(
+                    opt.not_when_recording,
+                )?),
+            ));
         }
     }
+    #[cfg(feature = "camera")]
+    {
+        if opt.not_when_camera {
+            modules.push(Toggle::new(
+                "not-when-camera",
+                Box::new(xidlehook_core::modules::NotWhenCamera::new()),
+            ));
+        }
+    }
+    #[cfg(feature = "screencast")]
+    {
+        if opt.not_when_screencasting {
+            modules.push(Toggle::new(
+                "not-when-screencasting",
+                Box::new(xidlehook_core::modules::NotWhenScreencast::new()),
+            ));
+        }
+    }
+    if let Some(threshold) = opt.not_when_load_above {
+        modules.push(Toggle::new(
+            "not-when-load-above",
+            Box::new(NotWhenLoadAbove::new(threshold)),
+        ));
+    }
+    if opt.not_when_on_ac {
+        modules.push(Toggle::new(
+            "not-when-on-ac",
+            Box::new(NotWhenBattery::on_ac().on_change(history.power_changes("not-when-on-ac"))),
+        ));
+    }
+    if let Some(percent) = opt.not_when_battery_below {
+        modules.push(Toggle::new(
+            "not-when-battery-below",
+            Box::new(
+                NotWhenBattery::battery_below(percent)
+                    .on_change(history.power_changes("not-when-battery-below")),
+            ),
+        ));
+    }
+    #[cfg(feature = "mpris")]
+    {
+        if opt.not_when_playing {
+            modules.push(Toggle::new(
+                "not-when-playing",
+                Box::new(xidlehook_core::modules::NotWhenPlaying::new()?),
+            ));
+        }
+    }
+    #[cfg(feature = "logind")]
+    {
+        if opt.not_when_inhibited {
+            modules.push(Toggle::new(
+                "not-when-inhibited",
+                Box::new(xidlehook_core::modules::NotWhenInhibited::new(
+                    opt.inhibit_types.clone(),
+                )?),
+            ));
+        }
+    }
+    Ok(())
+}
 
-    let xidlehook = Xidlehook::new(timers).register(modules);
-    App {
-        opt,
-        xcb,
-        xidlehook,
+/// Split the --command-shell into the program and its arguments
+fn parse_command_shell(command_shell: &str) -> xidlehook_core::Result<Vec<String>> {
+    let command_shell = match shell_words::split(command_shell) {
+        Ok(parts) if !parts.is_empty() => parts,
+        Ok(_) => return Err("--command-shell is empty".into()),
+        Err(err) => return Err(format!("invalid --command-shell: {err}").into()),
+    };
+    if !program_exists(&command_shell[0]) {
+        warn!(
+            "The command shell {:?} doesn't exist, timer commands will fail to run",
+            command_shell[0]
+        );
+    }
+    Ok(command_shell)
+}
+
+/// Build the timers from the command line, along with everything
+/// about them that's configured there. This is how they're reloaded on
+/// SIGHUP as well.
+fn build_timers(
+    opt: &Opt,
+    matches: &ArgMatches,
+    backend: &Backend,
+    command_shell: &[String],
+) -> xidlehook_core::Result<Vec<CmdTimer>> {
+    let mut timers = parse_timers(opt, command_shell)?;
+
+    apply_per_timer_flags(&mut timers, opt, matches)?;
+    apply_signals(&mut timers, matches)?;
+    #[cfg(feature = "notify")]
+    apply_notify(&mut timers, opt, matches)?;
+    apply_outputs(&mut timers, matches, backend)?;
+
+    apply_shared_flags(&mut timers, opt, command_shell);
+
+    #[cfg(feature = "backlight")]
+    apply_backlight_dim(&mut timers, opt)?;
+
+    #[cfg(feature = "logind")]
+    {
+        if let Some(index) = opt.lock_on_suspend {
+            if index >= timers.len() {
+                return Err(format!("--lock-on-suspend: timer {index} does not exist").into());
+            }
+        }
+    }
+
+    if !opt.no_timer_check {
+        let problems = check_timers(&timers, opt.min_timer_gap);
+        for problem in &problems {
+            warn!("{problem}");
+        }
+        if opt.strict && !problems.is_empty() {
+            return Err(format!(
+                "{} problem(s) with the timers, see the warnings above",
+                problems.len()
+            )
+            .into());
+        }
+    }
+
+    if opt.first_timer_session {
+        if let Some(first) = timers.first_mut() {
+            first.set_session(true);
+        }
     }
-    .main_loop()
+
+    Ok(timers)
+}
+
+/// Create a timer for each --timer, in order
+fn parse_timers(opt: &Opt, command_shell: &[String]) -> xidlehook_core::Result<Vec<CmdTimer>> {
+    let mut timers = Vec::new();
+    let mut iter = opt.timer.iter().peekable();
+    while iter.peek().is_some() {
+        // clap-rs will ensure there are always a multiple of 3
+        let duration = duration::parse(iter.next().unwrap())?;
+        let activation = iter.next().unwrap();
+        let abortion = iter.next().unwrap();
+        if opt.no_shell {
+            let (activation, abortion) = (
+                shell_words::split(activation)?,
+                shell_words::split(abortion)?,
+            );
+            timers.push(CmdTimer::from_parts(
+                duration,
+                activation,
+                abortion,
+                Vec::new(),
+            ));
+        } else {
+            timers.push(CmdTimer::from_shell(
+                duration,
+                activation.into(),
+                abortion.into(),
+                String::new(),
+                command_shell,
+            ));
+        }
+    }
+    Ok(timers)
+}
+
+/// Apply the flags that configure the --timer that follows them, such
+/// as --timer-name
+fn apply_per_timer_flags(
+    timers: &mut [CmdTimer],
+    opt: &Opt,
+    matches: &ArgMatches,
+) -> xidlehook_core::Result<()> {
+    if let Some(names) = matches.values_of("timer-name") {
+        for (name, index) in names.zip(following_timers(matches, "timer-name")) {
+            match index {
+                Some(index) => timers[index].set_name(name.to_string()),
+                None => warn!("--timer-name {name:?} isn't followed by a --timer"),
+            }
+        }
+    }
+    for (&timeout, index) in opt
+        .timer_timeout
+        .iter()
+        .zip(following_timers(matches, "timer-timeout"))
+    {
+        match index {
+            Some(index) => timers[index].set_timeout(Some(timeout)),
+            None => warn!("--timer-timeout {timeout:?} isn't followed by a --timer"),
+        }
+    }
+    for (&window, index) in opt
+        .timer_at
+        .iter()
+        .zip(following_timers(matches, "timer-at"))
+    {
+        match index {
+            Some(index) => timers[index].set_at(Some(window)),
+            None => warn!("--timer-at {window} isn't followed by a --timer"),
+        }
+    }
+    for (&max, index) in opt
+        .timer_up_to
+        .iter()
+        .zip(following_timers(matches, "timer-up-to"))
+    {
+        match index {
+            Some(index) => timers[index].set_range(max)?,
+            None => warn!("--timer-up-to {max:?} isn't followed by a --timer"),
+        }
+    }
+    for index in following_timers(matches, "wait") {
+        match index {
+            Some(index) => timers[index].set_wait(true),
+            None => warn!("--wait isn't followed by a --timer"),
+        }
+    }
+    for index in following_timers(matches, "reset-on-exit") {
+        match index {
+            Some(index) => timers[index].set_reset_on_exit(true),
+            None => warn!("--reset-on-exit isn't followed by a --timer"),
+        }
+    }
+    Ok(())
+}
+
+/// Apply --signal to the --timer that follows each of them
+fn apply_signals(timers: &mut [CmdTimer], matches: &ArgMatches) -> xidlehook_core::Result<()> {
+    if let Some(values) = matches.values_of("signal") {
+        let values: Vec<&str> = values.collect();
+        // Both values of each --signal are followed by the same timer
+        let indices = following_timers(matches, "signal").into_iter().step_by(2);
+        for (pair, index) in values.chunks(2).zip(indices) {
+            let action = SignalAction::parse(pair[0], pair[1])
+                .map_err(|err| format!("invalid --signal: {err}"))?;
+            match index {
+                Some(index) => timers[index].set_signal(Some(action)),
+                None => warn!("--signal {action} isn't followed by a --timer"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Apply --notify to the --timer that follows each of them
+#[cfg(feature = "notify")]
+fn apply_notify(
+    timers: &mut [CmdTimer],
+    opt: &Opt,
+    matches: &ArgMatches,
+) -> xidlehook_core::Result<()> {
+    // Both values of each --notify are followed by the same timer
+    let indices = following_timers(matches, "notify").into_iter().step_by(2);
+    for (pair, index) in opt.notify.chunks(2).zip(indices) {
+        let (summary, body) = (pair[0].clone(), pair[1].clone());
+        match index {
+            Some(index) => timers[index].add_notify(summary, body)?,
+            None => warn!("--notify {summary:?} isn't followed by a --timer"),
+        }
+    }
+    Ok(())
+}
+
+/// Apply --skip-when-outputs and --only-when-outputs to the --timer
+/// that follows each of them
+fn apply_outputs(
+    timers: &mut [CmdTimer],
+    matches: &ArgMatches,
+    backend: &Backend,
+) -> xidlehook_core::Result<()> {
+    for &(flag, skip_when) in &[("skip-when-outputs", true), ("only-when-outputs", false)] {
+        let Some(specs) = matches.values_of(flag) else {
+            continue;
+        };
+        for (spec, index) in specs.zip(following_timers(matches, flag)) {
+            let outputs = Outputs::parse(spec)?;
+            let xcb = Rc::clone(backend.xcb(&format!("--{flag}"))?);
+            let condition = if skip_when {
+                OutputCondition::skip_when(xcb, outputs)
+            } else {
+                OutputCondition::only_when(xcb, outputs)
+            };
+            match index {
+                Some(index) => timers[index].set_outputs(Some(condition)),
+                None => warn!("--{flag} {spec:?} isn't followed by a --timer"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Apply the flags that configure every timer, such as --dry-run
+fn apply_shared_flags(timers: &mut [CmdTimer], opt: &Opt, command_shell: &[String]) {
+    let on_failure = match (&opt.fallback, &opt.on_failure) {
+        (Some(fallback), _) => Some(FailureAction::Fallback(timers::shell(
+            command_shell,
+            fallback.clone(),
+        ))),
+        (None, Some(FailurePolicy::Ignore)) => Some(FailureAction::Ignore),
+        (None, Some(FailurePolicy::Exit)) => Some(FailureAction::Exit),
+        (None, None) => None,
+    };
+    for timer in &mut *timers {
+        timer.set_on_failure(on_failure.clone());
+    }
+
+    if opt.no_cancel {
+        for timer in &mut *timers {
+            timer.set_no_cancel(true);
+        }
+    }
+
+    if opt.dry_run {
+        for timer in &mut *timers {
+            timer.set_dry_run(true);
+        }
+    }
+
+    if opt.notify_on_failure {
+        for timer in &mut *timers {
+            timer.set_notify_on_failure(true);
+        }
+    }
+
+    if let Some(max) = opt.max_activations {
+        for timer in &mut *timers {
+            timer.set_rate_limit(max, opt.max_activations_window);
+        }
+    }
+}
+
+/// Apply --backlight-dim to the timers it names
+#[cfg(feature = "backlight")]
+fn apply_backlight_dim(timers: &mut [CmdTimer], opt: &Opt) -> xidlehook_core::Result<()> {
+    for pair in opt.backlight_dim.chunks(2) {
+        // clap-rs will ensure there are always a multiple of 2
+        let (index, percent) = (pair[0], pair[1]);
+        let Some(timer) = index.try_into().ok().and_then(|i: usize| timers.get_mut(i)) else {
+            return Err(format!("--backlight-dim: timer {index} does not exist").into());
+        };
+        match backlight::Dim::new(percent, opt.backlight_ramp) {
+            Ok(dim) => timer.set_dim(Some(dim)),
+            Err(err) => warn!("Not dimming the backlight: {err}"),
+        }
+    }
+    Ok(())
+}
+
+/// Print the idle time every `interval` until interrupted, or until
+/// standard output is closed
+fn print_idle_loop(backend: &Backend, interval: Duration) -> xidlehook_core::Result<()> {
+    let (signal_tx, signal_rx) = sync::channel(1);
+    let signal_thread = signal_handler::handle_signals(signal_tx, None)?;
+
+    let stdout = io::stdout();
+    loop {
+        let idle = backend.get_idle()?;
+        if writeln!(stdout.lock(), "{}", idle.as_millis()).is_err() {
+            break;
+        }
+        if task::block_on(future::timeout(interval, signal_rx.recv())).is_ok() {
+            break;
+        }
+    }
+
+    // Pretend there's a signal, to make the thread exit
+    signal_handler::handler(Signal::SIGINT as i32 as libc::c_int);
+    signal_thread.join().unwrap()?;
+    Ok(())
+}
+
+/// Whether a program can be found, either at its path, or in `$PATH`
+/// if it's only a name
+fn program_exists(program: &str) -> bool {
+    if program.contains('/') {
+        return Path::new(program).exists();
+    }
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).exists()))
+}
+
+/// Match each occurrence of a per-timer flag, such as --timer-name,
+/// with the index of the --timer right after it, if there is one. As
+/// the parsed options only keep the values of each flag, this goes by
+/// their positions on the command line.
+fn following_timers(matches: &ArgMatches, flag: &str) -> Vec<Option<usize>> {
+    // Where each timer starts, as every timer takes 3 values
+    let timers: Vec<usize> = matches
+        .indices_of("timer")
+        .map_or_else(Vec::new, |indices| indices.step_by(3).collect());
+
+    matches.indices_of(flag).map_or_else(Vec::new, |indices| {
+        indices
+            .map(|index| timers.iter().position(|&timer| timer > index))
+            .collect()
+    })
+}
+
+/// Look for timers that are most likely misconfigured: Ones that go
+/// off immediately, or so soon after the previous one that they
+/// effectively overlap.
+fn check_timers(timers: &[CmdTimer], min_gap: Duration) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (i, timer) in timers.iter().enumerate() {
+        let time = timer.get_time();
+        if time == Duration::default() {
+            problems.push(format!("timer {i} has a duration of zero"));
+        } else if i > 0 && time < min_gap {
+            problems.push(format!(
+                "timer {} goes off only {:?} after timer {}, which is less than the minimum gap \
+                 of {:?}",
+                i,
+                time,
+                i.saturating_sub(1),
+                min_gap
+            ));
+        }
+    }
+    problems
+}
+
+/// Parse an octal umask, such as "077" or "0022"
+fn parse_umask(input: &str) -> Result<Mode, String> {
+    u32::from_str_radix(input, 8)
+        .ok()
+        .and_then(|bits| Mode::from_bits(bits as libc::mode_t))
+        .ok_or_else(|| format!("invalid umask {input:?} (try something like 077)"))
+}
+
+/// Write the current process ID to `path`. A PID file left behind by
+/// a process that is no longer running is overwritten, but one
+/// belonging to a running process is an error.
+fn write_pidfile(path: &str) -> xidlehook_core::Result<()> {
+    if let Some(pid) = running_pid(path) {
+        return Err(
+            format!("pidfile {path} belongs to process {pid}, which is still running").into(),
+        );
+    }
+    if Path::new(path).exists() {
+        warn!("Overwriting stale pidfile {path}");
+    }
+    fs::write(path, format!("{}\n", unistd::getpid()))?;
+    Ok(())
+}
+
+/// The process in this pidfile, if it's still running
+fn running_pid(path: &str) -> Option<Pid> {
+    let pid = Pid::from_raw(fs::read_to_string(path).ok()?.trim().parse().ok()?);
+    // Signal "0" doesn't send anything, it only checks whether the process exists
+    match signal::kill(pid, None) {
+        Err(nix::Error::Sys(Errno::ESRCH)) => None,
+        _ => Some(pid),
+    }
+}
+
+/// The modules that can be enabled and disabled at runtime
+type Modules = Vec<Toggle<Box<dyn Module>>>;
+
+/// How many times in a row reading the idle time may fail, while the
+/// connection to the X server still works, before giving up
+const MAX_IDLE_FAILURES: u32 = 5;
+/// How long to wait before reading the idle time again after it failed
+const IDLE_RETRY: Duration = Duration::from_secs(1);
+
+/// Something the main loop was waiting for
+enum Selected {
+    Socket(socket::Message, sync::Sender<socket::Reply>),
+    Signal(Signal),
+    Child(WaitStatus),
+    SessionEnded,
+    #[cfg(feature = "logind")]
+    Sleep(logind::Sleep),
+    /// `App::run_timers` returned
+    Exit(xidlehook_core::Result<Option<u32>>),
 }
 
 struct App {
     opt: Opt,
-    xcb: Rc<Xcb>,
-    xidlehook: Xidlehook<CmdTimer, ((), Vec<Box<dyn Module>>)>,
+    backend: Rc<Backend>,
+    /// The first module logs warnings, unless --quiet is used
+    xidlehook: Xidlehook<CmdTimer, (Box<dyn Module>, Modules)>,
+    /// Whether the timers were all paused over the socket, so that
+    /// timers added or reloaded meanwhile are paused too
+    paused: bool,
+    /// Shared with every timer, for --max-failures
+    failures: Option<Rc<Failures>>,
+    /// Shared with every timer, for querying over the socket
+    history: History,
+    /// The activation command of a --wait timer that's still running,
+    /// if any. No timers are checked until it exits.
+    waiting: Option<u32>,
+    /// Where to write the idle time to, for --idle-fifo
+    idle_fifo: Option<IdleFifo>,
+    /// Where to save the episode on exit, for --persist-episode
+    episode_path: Option<PathBuf>,
+    /// How many times in a row reading the idle time failed
+    idle_failures: u32,
 }
 impl App {
     fn main_loop(&mut self) -> xidlehook_core::Result<()> {
-        let (socket_tx, socket_rx) = sync::channel(4);
-        let _scope = if let Some(address) = self.opt.socket.clone() {
-            {
-                let address = address.clone();
-                task::spawn(async move {
-                    if let Err(err) = socket::main_loop(&address, socket_tx).await {
-                        warn!("Socket handling errored: {}", err);
-                    }
-                });
-            }
+        let _pidfile = if let Some(path) = self.opt.pidfile.clone() {
+            write_pidfile(&path)?;
             Some(Defer(move || {
-                trace!("Removing unix socket {}", address);
-                let _ = fs::remove_file(&address);
+                trace!("Removing pidfile {path}");
+                let _ = fs::remove_file(&path);
             }))
         } else {
             None
         };
 
+        let (socket_tx, socket_rx) = sync::channel(4);
+        let _scope = self.serve(socket_tx);
+
         let (signal_tx, signal_rx) = sync::channel(1);
-        let signal_thread = signal_handler::handle_signals(signal_tx)?;
+        let (children_tx, children_rx) = sync::channel(4);
+        // Timers added over the socket may wait as well
+        let may_wait = self.opt.wait > 0
+            || self.opt.reset_on_exit > 0
+            || self.opt.socket.is_some()
+            || self.opt.stdin_commands;
+        #[cfg(feature = "dbus")]
+        let may_wait = may_wait || self.opt.dbus;
+        let watch_children = self.opt.notify_on_failure
+            || self.failures.is_some()
+            || self.opt.on_failure.is_some()
+            || self.opt.fallback.is_some()
+            || may_wait;
+        let children_tx = if watch_children {
+            Some(children_tx)
+        } else {
+            None
+        };
+        let signal_thread = signal_handler::handle_signals(signal_tx, children_tx)?;
+
+        // Every source of events is merged into one stream. A source drops out of it once its
+        // channel is closed, so new sources only need to be pushed here.
+        let mut events: SelectAll<LocalBoxStream<'static, Selected>> = SelectAll::new();
+        events.push(Box::pin(
+            socket_rx.map(|(msg, reply)| Selected::Socket(msg, reply)),
+        ));
+        events.push(Box::pin(signal_rx.map(Selected::Signal)));
+        if watch_children {
+            events.push(Box::pin(children_rx.map(Selected::Child)));
+        }
 
-        let mut socket_rx = Some(socket_rx);
-        let mut signal_rx = Some(signal_rx);
+        let exit_with_session =
+            self.opt.exit_with_session || env::var_os(session::SESSION_MANAGER_ENV).is_some();
+        if exit_with_session {
+            let (session_tx, session_rx) = sync::channel(1);
+            // Never joined, it's fine for it to be killed on exit
+            session::watch_disconnect(self.backend.as_raw_fd(), session_tx);
+            events.push(Box::pin(session_rx.map(|()| Selected::SessionEnded)));
+        }
 
-        loop {
-            enum Selected {
-                Socket(Option<(socket::Message, sync::Sender<socket::Reply>)>),
-                Signal(Option<Signal>),
-                Exit(xidlehook_core::Result<()>),
+        #[cfg(feature = "logind")]
+        {
+            if self.opt.lock_on_suspend.is_some() {
+                let (sleep_tx, sleep_rx) = sync::channel(1);
+                match logind::watch_sleep(sleep_tx) {
+                    // Never joined, it's fine for it to be killed on exit
+                    Ok(_) => events.push(Box::pin(sleep_rx.map(Selected::Sleep))),
+                    Err(err) => {
+                        warn!("Can't watch for suspend, not using --lock-on-suspend: {err}");
+                    },
+                }
             }
+        }
 
-            let a = async {
-                if let Some(ref rx) = socket_rx {
-                    Selected::Socket(rx.recv().await)
-                } else {
-                    future::pending().await
+        loop {
+            let event = async {
+                match events.next().await {
+                    Some(event) => event,
+                    // Every source is gone, only the timers are left
+                    None => future::pending().await,
                 }
             };
-            let b = async {
-                if let Some(ref rx) = signal_rx {
-                    Selected::Signal(rx.recv().await)
-                } else {
+            let timers = async {
+                if self.waiting.is_some() {
                     future::pending().await
+                } else {
+                    Selected::Exit(self.run_timers().await)
                 }
             };
 
-            let c = async {
-                let status = self.xidlehook.main_async(&self.xcb).await;
-                Selected::Exit(status)
-            };
-            let res = task::block_on(a.race(b).race(c));
-
-            match res {
-                Selected::Socket(data) => {
-                    if let Some((msg, reply)) = data {
-                        trace!("Got command over socket: {:#?}", msg);
-                        let response = match self.handle_socket(msg)? {
-                            Some(response) => response,
-                            None => break,
-                        };
-                        task::block_on(reply.send(response));
-                    } else {
-                        socket_rx = None;
-                    }
-                },
-                Selected::Signal(sig) => {
-                    if let Some(sig) = sig {
-                        trace!("Signal received: {}", sig);
-                        break;
-                    } else {
-                        signal_rx = None;
-                    }
-                },
-                Selected::Exit(res) => {
-                    res?;
-                    break;
-                },
+            let event = task::block_on(event.race(timers));
+            if self.handle_event(event, exit_with_session)? {
+                break;
             }
         }
 
@@ -226,6 +1566,276 @@ impl App {
 
         signal_thread.join().unwrap()?;
 
+        self.save_episode();
+
+        Ok(())
+    }
+
+    /// Start listening for commands on standard input, D-Bus and the
+    /// socket, as enabled. The returned guard removes the socket.
+    fn serve(
+        &self,
+        socket_tx: sync::Sender<(socket::Message, sync::Sender<socket::Reply>)>,
+    ) -> Option<Defer<impl FnMut()>> {
+        if self.opt.stdin_commands {
+            task::spawn(socket::stdin_loop(socket_tx.clone()));
+        }
+        #[cfg(feature = "dbus")]
+        {
+            if self.opt.dbus {
+                let events = self.history.subscribe();
+                // Never joined, it's fine for it to be killed on exit
+                if let Err(err) = dbus::serve(self.opt.dbus_system, socket_tx.clone(), events) {
+                    warn!("Can't serve {} on D-Bus: {}", dbus::NAME, err);
+                }
+            }
+        }
+        if let Some(address) = self.opt.socket.clone() {
+            {
+                let address = address.clone();
+                let retries = self.opt.socket_retries;
+                let backoff = Duration::from_millis(self.opt.socket_backoff);
+                task::spawn(async move {
+                    if let Err(err) = socket::main_loop(&address, retries, backoff, socket_tx).await
+                    {
+                        warn!("Socket handling errored: {err}");
+                    }
+                });
+            }
+            Some(Defer(move || {
+                trace!("Removing unix socket {address}");
+                let _ = fs::remove_file(&address);
+            }))
+        } else {
+            None
+        }
+    }
+
+    /// Handle something the main loop was waiting for, and return
+    /// whether to exit
+    fn handle_event(
+        &mut self,
+        event: Selected,
+        exit_with_session: bool,
+    ) -> xidlehook_core::Result<bool> {
+        match event {
+            Selected::Socket(msg, reply) => {
+                trace!("Got command over socket: {msg:#?}");
+                let shutdown = matches!(msg, socket::Message::Shutdown);
+                let Some(response) = self.handle_socket(msg)? else {
+                    return Ok(true);
+                };
+                task::block_on(reply.send(response));
+                if shutdown {
+                    info!("Shutting down as requested over the socket");
+                    return Ok(true);
+                }
+            },
+            Selected::Signal(Signal::SIGHUP) => self.reload()?,
+            Selected::Signal(sig) => {
+                trace!("Signal received: {sig}");
+                return Ok(true);
+            },
+            Selected::Child(status) => self.handle_child(status)?,
+            Selected::SessionEnded => {
+                info!("The X session ended, exiting");
+                return Ok(true);
+            },
+            #[cfg(feature = "logind")]
+            Selected::Sleep(logind::Sleep::Suspending(lock)) => {
+                let stop = self.activate_before_sleep()?;
+                // Let the system suspend
+                drop(lock);
+                if stop {
+                    return Ok(true);
+                }
+            },
+            #[cfg(feature = "logind")]
+            Selected::Sleep(logind::Sleep::Resumed) => {
+                trace!("Resumed from suspend");
+            },
+            Selected::Exit(Ok(Some(pid))) => {
+                debug!("Waiting for activation command {pid} to exit");
+                self.waiting = Some(pid);
+            },
+            Selected::Exit(res) => {
+                if res.is_err() && exit_with_session && !self.backend.is_connected() {
+                    info!("The X session ended, exiting");
+                    return Ok(true);
+                }
+                res?;
+                return Ok(true);
+            },
+        }
+        Ok(false)
+    }
+
+    /// Save the episode for --persist-episode, if enabled
+    fn save_episode(&self) {
+        if let Some(ref path) = self.episode_path {
+            // The X server may already be gone, then there's no episode to continue anyway
+            if let Ok(idle) = self.backend.get_idle() {
+                if let Err(err) = episode::save(path, &self.xidlehook, idle) {
+                    warn!("Failed to save the episode to {}: {}", path.display(), err);
+                }
+            }
+        }
+    }
+
+    /// Step through the timers like `Xidlehook::main_async`, but return
+    /// early with the process ID of the activation command, if a
+    /// --wait timer's command was started. Otherwise, returns once a
+    /// module stops xidlehook.
+    async fn run_timers(&mut self) -> xidlehook_core::Result<Option<u32>> {
+        loop {
+            let idle = match self.backend.get_idle() {
+                Ok(idle) => idle,
+                // A broken connection won't recover, but a single request might fail on its own
+                Err(err)
+                    if self.backend.is_connected() && self.idle_failures < MAX_IDLE_FAILURES =>
+                {
+                    self.idle_failures = self.idle_failures.saturating_add(1);
+                    self.xidlehook
+                        .module_mut()
+                        .warning(&format!("can't read the idle time: {err}").into())?;
+                    task::sleep(IDLE_RETRY).await;
+                    continue;
+                },
+                Err(err) => return Err(err),
+            };
+            self.idle_failures = 0;
+            if let Some(ref mut fifo) = self.idle_fifo {
+                fifo.write(idle);
+            }
+            let action = self.xidlehook.step(idle)?;
+            if let Action::Activated { index, .. } = action {
+                if let Some(pid) = self.xidlehook.timers()[index].waiting_for() {
+                    return Ok(Some(pid));
+                }
+            }
+            let Some(mut delay) = action.sleep() else {
+                return Ok(None);
+            };
+            if self.idle_fifo.is_some() {
+                delay = delay.min(fifo::INTERVAL);
+            }
+
+            trace!("Sleeping for {delay:?}");
+            task::sleep(delay).await;
+        }
+    }
+
+    /// Activate the timer given to --lock-on-suspend. Returns whether a
+    /// module requested that xidlehook stops.
+    #[cfg(feature = "logind")]
+    fn activate_before_sleep(&mut self) -> xidlehook_core::Result<bool> {
+        let Some(index) = self.opt.lock_on_suspend else {
+            return Ok(false);
+        };
+        if index >= self.xidlehook.timers().len() {
+            warn!("Not activating timer {index} before suspending, it no longer exists");
+            return Ok(false);
+        }
+        info!("Suspending, activating timer {index}");
+        let progress = self
+            .xidlehook
+            .trigger(index, self.backend.get_idle()?, true)?;
+        Ok(progress == xidlehook_core::Progress::Stop)
+    }
+
+    /// Build the timers from the command line again, and replace the
+    /// current ones with them. The chain carries on if the timers that
+    /// already went off are unchanged, see `Xidlehook::replace_timers`.
+    /// Timers added over the socket are dropped, with a warning. If the
+    /// new timers are invalid, the old ones keep running, and the error
+    /// is reported as a warning.
+    fn reload(&mut self) -> xidlehook_core::Result<()> {
+        info!("Reloading the timers");
+        let result = config::args(env::args_os())
+            .and_then(expand::args)
+            .map_err(xidlehook_core::Error::from)
+            .and_then(|args| Ok(Opt::clap().get_matches_from_safe(args)?))
+            .and_then(|matches| {
+                let opt = Opt::from_clap(&matches);
+                let command_shell = parse_command_shell(&opt.command_shell)?;
+                build_timers(&opt, &matches, &self.backend, &command_shell)
+            });
+        let mut timers = match result {
+            Ok(timers) => timers,
+            Err(err) => {
+                let err = format!("Not reloading the timers: {err}").into();
+                return self.xidlehook.module_mut().warning(&err);
+            },
+        };
+
+        for timer in &mut timers {
+            timer.set_history(Some(self.history.clone()));
+            timer.set_backend(Some(Rc::clone(&self.backend)));
+            timer.set_failures(self.failures.clone());
+            // Paused timers stay paused until they're all resumed
+            timer.set_paused(self.paused);
+        }
+
+        let added = self
+            .xidlehook
+            .timers()
+            .iter()
+            .filter(|timer| timer.get_added())
+            .count();
+        if added > 0 {
+            warn!("Dropping {added} timer(s) that were added over the socket");
+        }
+
+        let count = timers.len();
+        if self.xidlehook.replace_timers(timers, CmdTimer::same_as)? {
+            info!("Reloaded {count} timers");
+        } else {
+            info!("Reloaded {count} timers, starting over");
+        }
+        Ok(())
+    }
+
+    /// If the child was a timer's activation command, let the timer
+    /// know how it went, stop waiting for it, and reset the chain if
+    /// the timer asks for it
+    fn handle_child(&mut self, status: WaitStatus) -> xidlehook_core::Result<()> {
+        let (pid, failure) = match status {
+            WaitStatus::Exited(pid, 0) => (pid, None),
+            WaitStatus::Exited(pid, code) => {
+                (pid, Some(format!("The command exited with code {code}")))
+            },
+            WaitStatus::Signaled(pid, signal, _) => {
+                (pid, Some(format!("The command was killed by {signal}")))
+            },
+            _ => return Ok(()),
+        };
+        let Ok(raw_pid) = pid.as_raw().try_into() else {
+            return Ok(());
+        };
+        if self.waiting == Some(raw_pid) {
+            debug!("Activation command {pid} exited, no longer waiting");
+            self.waiting = None;
+        }
+        let Some(timer) = self
+            .xidlehook
+            .timers()
+            .iter()
+            .find(|timer| timer.is_activation(raw_pid))
+        else {
+            return Ok(());
+        };
+        if let Some(reason) = failure {
+            trace!("Child {pid} failed: {reason}");
+            timer.activation_exited(&reason)?;
+        } else {
+            timer.activation_succeeded();
+        }
+
+        if timer.get_reset_on_exit() {
+            debug!("Activation command {pid} exited, starting over");
+            let idle = self.backend.get_idle()?;
+            self.xidlehook.reset_activity(idle)?;
+        }
         Ok(())
     }
 }
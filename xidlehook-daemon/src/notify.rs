@@ -0,0 +1,16 @@
+//! Desktop notifications, sent over `DBus` by `notify-send`
+
+use std::process::Command;
+
+use log::warn;
+
+/// Show a notification that something went wrong. This never fails,
+/// as there's nowhere better to report it than the log.
+pub fn failure(summary: &str, body: &str) {
+    let result = Command::new("notify-send")
+        .args(["--app-name=xidlehook", "--urgency=critical", summary, body])
+        .spawn();
+    if let Err(err) = result {
+        warn!("Failed to send notification with notify-send: {err}");
+    }
+}
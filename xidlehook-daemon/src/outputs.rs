@@ -0,0 +1,124 @@
+//! Conditions on which outputs (monitors) are connected, to treat
+//! timers differently when docked. This is used to implement
+//! `--skip-when-outputs` and `--only-when-outputs` in the xidlehook
+//! example application.
+
+use std::{cell::Cell, rc::Rc};
+
+use log::{debug, warn};
+use xidlehook_core::modules::Xcb;
+
+/// Which outputs must be connected
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outputs {
+    /// At least this many outputs, of any name
+    AtLeast(usize),
+    /// All of these outputs, by name
+    All(Vec<String>),
+}
+impl Outputs {
+    /// Parse either a number of outputs, such as `2`, or a
+    /// comma-separated list of names, such as `eDP-1,HDMI-1`
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        if let Ok(count) = input.parse() {
+            return Ok(Self::AtLeast(count));
+        }
+        let names: Vec<String> = input
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect();
+        if names.is_empty() {
+            return Err(format!(
+                "invalid outputs {input:?} (try a number like 2, or names like eDP-1,HDMI-1)"
+            ));
+        }
+        Ok(Self::All(names))
+    }
+
+    pub fn matches(&self, connected: &[String]) -> bool {
+        match *self {
+            Self::AtLeast(count) => connected.len() >= count,
+            Self::All(ref names) => names.iter().all(|name| connected.contains(name)),
+        }
+    }
+}
+
+/// Decides whether a timer should be skipped, going by the connected
+/// outputs at the time
+pub struct OutputCondition {
+    xcb: Rc<Xcb>,
+    outputs: Outputs,
+    /// Whether to skip when the outputs match, instead of when they
+    /// don't
+    skip_on_match: bool,
+    /// Whether reading the outputs failed before, so it's only
+    /// reported once
+    warned: Cell<bool>,
+}
+impl OutputCondition {
+    /// Skip the timer while these outputs are connected
+    pub fn skip_when(xcb: Rc<Xcb>, outputs: Outputs) -> Self {
+        Self {
+            xcb,
+            outputs,
+            skip_on_match: true,
+            warned: Cell::new(false),
+        }
+    }
+    /// Skip the timer unless these outputs are connected
+    pub fn only_when(xcb: Rc<Xcb>, outputs: Outputs) -> Self {
+        Self {
+            skip_on_match: false,
+            ..Self::skip_when(xcb, outputs)
+        }
+    }
+
+    /// Whether the timer should be skipped right now. If the outputs
+    /// can't be read, it isn't.
+    pub fn skip(&self) -> bool {
+        let connected = match self.xcb.get_connected_outputs() {
+            Ok(connected) => connected,
+            Err(err) => {
+                if !self.warned.replace(true) {
+                    warn!("Can't tell which outputs are connected: {err}");
+                }
+                return false;
+            },
+        };
+        let skip = self.outputs.matches(&connected) == self.skip_on_match;
+        if skip {
+            debug!("Skipping timer, as the connected outputs are {connected:?}");
+        }
+        skip
+    }
+}
+impl PartialEq for OutputCondition {
+    fn eq(&self, other: &Self) -> bool {
+        self.outputs == other.outputs && self.skip_on_match == other.skip_on_match
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching() {
+        let connected = vec![String::from("eDP-1"), String::from("HDMI-1")];
+
+        assert_eq!(Outputs::parse("2"), Ok(Outputs::AtLeast(2)));
+        assert!(Outputs::parse("2").unwrap().matches(&connected));
+        assert!(!Outputs::parse("3").unwrap().matches(&connected));
+
+        assert_eq!(
+            Outputs::parse("HDMI-1, eDP-1"),
+            Ok(Outputs::All(vec!["HDMI-1".into(), "eDP-1".into()]))
+        );
+        assert!(Outputs::parse("HDMI-1").unwrap().matches(&connected));
+        assert!(!Outputs::parse("HDMI-1,DP-2").unwrap().matches(&connected));
+        assert!(Outputs::parse(" , ").is_err());
+    }
+}
@@ -0,0 +1,79 @@
+//! Takes over from an instance of xidlehook that's already running,
+//! for `--replace`. The running instance is asked to shut down over
+//! its socket, or sent `SIGINT` if it's only known by its pidfile,
+//! and then this waits for it to exit and clean up after itself.
+
+use std::{
+    io::{prelude::*, BufReader},
+    os::unix::net::UnixStream,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{info, trace, warn};
+use nix::sys::signal::{self, Signal};
+
+use crate::{running_pid, socket::Message};
+
+/// How long the running instance gets to exit
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// How often to check whether it has exited
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Send a shutdown message over the socket, and wait for the reply
+fn request_shutdown(mut stream: &UnixStream) -> xidlehook_core::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    serde_json::to_writer(stream, &Message::Shutdown)?;
+    stream.write_all(b"\n")?;
+    stream.flush()?;
+
+    // The instance may well exit before replying, so the reply isn't required
+    if let Some(Ok(line)) = BufReader::new(stream).lines().next() {
+        trace!("The running instance replied: {line}");
+    }
+    Ok(())
+}
+
+/// Shut down the instance using this socket or pidfile, if there is
+/// one. Returns an error if it doesn't exit in time.
+pub fn replace(socket: Option<&str>, pidfile: Option<&str>) -> xidlehook_core::Result<()> {
+    let pid = pidfile.and_then(running_pid);
+    let mut socket_in_use = None;
+
+    if let Some(path) = socket.filter(|path| Path::new(path).exists()) {
+        match UnixStream::connect(path) {
+            Ok(stream) => {
+                info!("Asking the instance listening on {path} to shut down");
+                request_shutdown(&stream)?;
+                socket_in_use = Some(path);
+            },
+            Err(err) => {
+                // Nobody's listening, so it was left behind by an instance that crashed
+                warn!("Removing stale socket {path} ({err})");
+                let _ = std::fs::remove_file(path);
+            },
+        }
+    }
+    if let (None, Some(pid)) = (socket_in_use, pid) {
+        info!("Asking process {pid} to shut down");
+        signal::kill(pid, Signal::SIGINT)?;
+    }
+    if socket_in_use.is_none() && pid.is_none() {
+        trace!("There's no running instance to replace");
+        return Ok(());
+    }
+
+    // The instance removes its socket and pidfile before exiting
+    let start = Instant::now();
+    while socket_in_use.is_some_and(|path| Path::new(path).exists())
+        || pidfile.and_then(running_pid).is_some()
+    {
+        if start.elapsed() > TIMEOUT {
+            return Err(format!("the running instance didn't shut down within {TIMEOUT:?}").into());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    info!("The running instance has shut down, taking over");
+    Ok(())
+}
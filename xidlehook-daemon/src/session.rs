@@ -0,0 +1,48 @@
+//! Notices when the X session ends, by waiting for the X server to
+//! close its end of the connection
+
+use std::{
+    os::unix::io::RawFd,
+    thread::{self, JoinHandle},
+};
+
+use async_std::{sync, task};
+use log::trace;
+use nix::{errno::Errno, libc};
+
+/// Environment variable set by X session managers for the programs
+/// they start
+pub const SESSION_MANAGER_ENV: &str = "SESSION_MANAGER";
+
+/// The peer closed its end of the connection. Linux specific, and not
+/// yet in the libc crate version used.
+const POLLRDHUP: libc::c_short = 0x2000;
+
+/// Wait in a separate thread for the X connection with this file
+/// descriptor to be closed, and notify `tx` when it is. This never
+/// reads from the connection, so it doesn't interfere with it.
+pub fn watch_disconnect(fd: RawFd, tx: sync::Sender<()>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: POLLRDHUP,
+            revents: 0,
+        };
+        loop {
+            // POLLHUP and POLLERR are always reported, regardless of `events`
+            let ret = unsafe { libc::poll(&raw mut pollfd, 1, -1) };
+            if ret < 0 {
+                if Errno::last() == Errno::EINTR {
+                    continue;
+                }
+                // Nothing to watch, so just assume the session lives on forever
+                trace!("Can't watch the X connection: {}", Errno::last());
+                return;
+            }
+            if pollfd.revents & (POLLRDHUP | libc::POLLHUP | libc::POLLERR) != 0 {
+                task::block_on(tx.send(()));
+                return;
+            }
+        }
+    })
+}
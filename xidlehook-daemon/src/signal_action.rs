@@ -0,0 +1,164 @@
+//! Sends a Unix signal to another process when a timer goes off, such
+//! as to put a status bar in "away" mode, without starting a shell to
+//! run `kill`. This is used to implement `--signal` in the xidlehook
+//! example application.
+
+use std::{fmt, fs, path::Path};
+
+use log::{debug, warn};
+use nix::{
+    errno::Errno,
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+
+/// Which process to signal
+#[derive(Debug, PartialEq, Eq)]
+pub enum Target {
+    Pid(Pid),
+    /// The process with this name, as in `/proc/<pid>/comm` or the
+    /// file name of the program. If there are several, the one with
+    /// the lowest PID.
+    Name(String),
+}
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Pid(pid) => write!(f, "process {pid}"),
+            Self::Name(ref name) => write!(f, "{name:?}"),
+        }
+    }
+}
+
+/// Whether the process with this `/proc` directory has this name
+fn has_name(dir: &Path, name: &str) -> bool {
+    if let Ok(comm) = fs::read_to_string(dir.join("comm")) {
+        if comm.trim_end_matches('\n') == name {
+            return true;
+        }
+    }
+    // The comm name is cut off after 15 characters, and may have been changed by the process
+    fs::read(dir.join("cmdline")).is_ok_and(|cmdline| {
+        let program = cmdline.split(|&b| b == 0).next().unwrap_or_default();
+        Path::new(&*String::from_utf8_lossy(program))
+            .file_name()
+            .is_some_and(|file| file == name)
+    })
+}
+
+/// Find the process with this name that has the lowest PID
+fn find_process(name: &str) -> Option<Pid> {
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!("Can't look for process {name:?}: {err}");
+            return None;
+        },
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let pid: i32 = entry.file_name().to_str()?.parse().ok()?;
+            Some((pid, entry.path()))
+        })
+        .filter(|(_, dir)| has_name(dir, name))
+        .map(|(pid, _)| pid)
+        .min()
+        .map(Pid::from_raw)
+}
+
+/// See the module-level documentation
+#[derive(Debug, PartialEq, Eq)]
+pub struct SignalAction {
+    pub target: Target,
+    pub signal: Signal,
+}
+impl SignalAction {
+    /// Parse a process, either a PID or a name, and a signal, either a
+    /// name such as `USR1` or `SIGUSR1`, or a number
+    pub fn parse(target: &str, signal: &str) -> Result<Self, String> {
+        let target = match target.parse() {
+            Ok(pid) if pid > 0 => Target::Pid(Pid::from_raw(pid)),
+            Ok(_) => return Err(format!("invalid process ID {target}")),
+            Err(_) if target.is_empty() => return Err(String::from("empty process name")),
+            Err(_) => Target::Name(target.to_string()),
+        };
+
+        let upper = signal.trim().to_ascii_uppercase();
+        let parsed = match upper.parse() {
+            Ok(number) => Signal::from_c_int(number).ok(),
+            Err(_) if upper.starts_with("SIG") => upper.parse().ok(),
+            Err(_) => format!("SIG{upper}").parse().ok(),
+        };
+        let signal = parsed.ok_or_else(|| {
+            format!("invalid signal {signal:?} (try a name like USR1, or a number)")
+        })?;
+
+        Ok(Self { target, signal })
+    }
+
+    /// Send the signal. It's not an error if the process doesn't exist,
+    /// as it may just not be running right now, so that's only logged.
+    pub fn send(&self) {
+        let pid = match self.target {
+            Target::Pid(pid) => pid,
+            Target::Name(ref name) => {
+                if let Some(pid) = find_process(name) {
+                    pid
+                } else {
+                    warn!(
+                        "Not sending {}, no process is named {:?}",
+                        self.signal, name
+                    );
+                    return;
+                }
+            },
+        };
+        debug!("Sending {} to process {}", self.signal, pid);
+        match signal::kill(pid, self.signal) {
+            Ok(()) => (),
+            Err(err) if err.as_errno() == Some(Errno::ESRCH) => {
+                warn!("Not sending {}, process {} doesn't exist", self.signal, pid);
+            },
+            Err(err) => warn!("Failed to send {} to process {}: {}", self.signal, pid, err),
+        }
+    }
+}
+impl fmt::Display for SignalAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} to {}", self.signal, self.target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            SignalAction::parse("polybar", "usr1"),
+            Ok(SignalAction {
+                target: Target::Name(String::from("polybar")),
+                signal: Signal::SIGUSR1,
+            })
+        );
+        assert_eq!(
+            SignalAction::parse("1234", "SIGTERM"),
+            Ok(SignalAction {
+                target: Target::Pid(Pid::from_raw(1234)),
+                signal: Signal::SIGTERM,
+            })
+        );
+        assert_eq!(
+            SignalAction::parse("bar", "10")
+                .map(|action| action.signal)
+                .ok(),
+            Signal::from_c_int(10).ok()
+        );
+        assert!(SignalAction::parse("0", "USR1").is_err());
+        assert!(SignalAction::parse("", "USR1").is_err());
+        assert!(SignalAction::parse("bar", "NOPE").is_err());
+        assert!(SignalAction::parse("bar", "1000").is_err());
+    }
+}
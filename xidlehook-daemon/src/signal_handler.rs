@@ -9,7 +9,7 @@ use nix::{
     libc,
     sys::{
         signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal},
-        wait,
+        wait::{self, WaitPidFlag, WaitStatus},
     },
     unistd,
 };
@@ -20,8 +20,13 @@ pub extern "C" fn handler(sig: libc::c_int) {
     let _ = unistd::write(unsafe { SIGNAL_PIPE.1 }, &sig.to_ne_bytes());
 }
 
+/// Handle signals in a separate thread. Signals that should stop
+/// xidlehook are sent to `tx`, and so is SIGHUP, which asks for a
+/// reload. Children are reaped, and if `children_tx` is set, how they
+/// exited is sent to it.
 pub fn handle_signals(
     tx: sync::Sender<Signal>,
+    children_tx: Option<sync::Sender<WaitStatus>>,
 ) -> xidlehook_core::Result<JoinHandle<nix::Result<()>>> {
     // Signal handling with async-std *sucks* currently (at 0.99.8)
 
@@ -29,7 +34,7 @@ pub fn handle_signals(
         SIGNAL_PIPE = unistd::pipe()?;
     }
 
-    for &sig in &[Signal::SIGINT, Signal::SIGCHLD] {
+    for &sig in &[Signal::SIGINT, Signal::SIGHUP, Signal::SIGCHLD] {
         unsafe {
             signal::sigaction(
                 sig,
@@ -51,12 +56,23 @@ pub fn handle_signals(
 
             match signal {
                 Signal::SIGCHLD => {
-                    let _ = wait::waitpid(None, Some(wait::WaitPidFlag::WNOHANG));
+                    // Signals may be merged, so reap all children that have exited
+                    loop {
+                        let status = match wait::waitpid(None, Some(WaitPidFlag::WNOHANG)) {
+                            Ok(WaitStatus::StillAlive) | Err(_) => break,
+                            Ok(status) => status,
+                        };
+                        if let Some(ref children_tx) = children_tx {
+                            task::block_on(children_tx.send(status));
+                        }
+                    }
                 },
                 Signal::SIGINT => {
                     task::block_on(tx.send(signal));
                     break;
                 },
+                // Asks for a reload rather than to stop, so keep going
+                Signal::SIGHUP => task::block_on(tx.send(signal)),
                 _ => (),
             }
         }
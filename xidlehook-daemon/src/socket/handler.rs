@@ -1,101 +1,286 @@
 use super::models::*;
 use crate::{timers::CmdTimer, App};
 
-use std::convert::TryInto;
+use std::{convert::TryInto, rc::Rc, time::Duration};
 
-use xidlehook_core::Progress;
+use xidlehook_core::{modules::Toggle, Progress, Timer};
+
+impl From<xidlehook_core::TimerState> for TimerState {
+    fn from(state: xidlehook_core::TimerState) -> Self {
+        match state {
+            xidlehook_core::TimerState::Armed => Self::Armed,
+            xidlehook_core::TimerState::Active => Self::Active,
+            xidlehook_core::TimerState::Deactivated => Self::Deactivated,
+            xidlehook_core::TimerState::Aborted => Self::Aborted,
+            xidlehook_core::TimerState::Skipped => Self::Skipped,
+        }
+    }
+}
 
 impl App {
-    pub fn handle_socket(&mut self, msg: Message) -> xidlehook_core::Result<Option<Reply>> {
-        match msg {
-            Message::Add(add) => {
-                let timers = self.xidlehook.timers_mut()?;
+    fn module_states(&self) -> Vec<ModuleState> {
+        (self.xidlehook.module().1)
+            .iter()
+            .map(|module| ModuleState {
+                name: module.name().to_string(),
+                enabled: module.enabled,
+            })
+            .collect()
+    }
+
+    /// Add a timer at runtime, set up like the ones from the command line
+    fn add_timer(&mut self, add: Add) -> xidlehook_core::Result<Option<Reply>> {
+        let len = self.xidlehook.timers().len();
+        let index = add.index.map_or(len, usize::from);
+        if index > len {
+            return Ok(Some(Reply::error(format!(
+                "index {index} is out of range, there are only {len} timers"
+            ))));
+        }
+        let mut timer =
+            CmdTimer::from_parts(add.time, add.activation, add.abortion, add.deactivation);
+        if let Some(name) = add.name {
+            timer.set_name(name);
+        }
+        timer.set_added(true);
+        timer.set_paused(self.paused);
+        timer.set_no_cancel(self.opt.no_cancel);
+        timer.set_dry_run(self.opt.dry_run);
+        timer.set_wait(add.wait);
+        timer.set_reset_on_exit(add.reset_on_exit);
+        timer.set_failures(self.failures.clone());
+        timer.set_history(Some(self.history.clone()));
+        timer.set_backend(Some(Rc::clone(&self.backend)));
+        if let Some(max) = self.opt.max_activations {
+            timer.set_rate_limit(max, self.opt.max_activations_window);
+        }
+        // Unless it goes before a timer that already went off, the chain carries on
+        self.xidlehook.insert_timer(index, timer)?;
 
-                let index = add.index.map_or_else(|| timers.len(), usize::from);
-                if index > timers.len() {
-                    return Ok(Some(Reply::Error(String::from("index > length"))));
+        Ok(Some(Reply::empty()))
+    }
+
+    /// Apply the action to the selected timers, if every one of them exists
+    fn control_timers(&mut self, control: &Control) -> xidlehook_core::Result<Option<Reply>> {
+        let len = self.xidlehook.timers().len();
+
+        // Validate everything before touching anything, so a bad request doesn't get
+        // half-applied
+        for id in control.timer.iter(
+            len.try_into()
+                .expect("xidlehook does not yet handle this many timers"),
+        ) {
+            let Some(timer) = self.xidlehook.timers().get(usize::from(id)) else {
+                return Ok(Some(Reply::error(format!(
+                    "timer {id} does not exist, there are only {len} timers"
+                ))));
+            };
+            if let Action::Trigger = control.action {
+                if timer.get_disabled() {
+                    return Ok(Some(Reply::error(format!(
+                        "timer {id} is disabled and can't be triggered"
+                    ))));
                 }
-                timers.insert(
-                    index,
-                    CmdTimer::from_parts(add.time, add.activation, add.abortion, add.deactivation),
-                );
+                if timer.get_paused() {
+                    return Ok(Some(Reply::error(format!(
+                        "timer {id} is paused and can't be triggered"
+                    ))));
+                }
+            }
+        }
 
-                Ok(Some(Reply::Empty))
-            },
-            Message::Control(control) => {
-                let len = self.xidlehook.timers().len();
+        let mut removed = 0;
+        for id in control.timer.iter(
+            len.try_into()
+                .expect("xidlehook does not yet handle this many timers"),
+        ) {
+            let timers = self.xidlehook.timers_mut()?;
+
+            let id = match id.checked_sub(removed) {
+                Some(res) => usize::from(res),
+                None => continue,
+            };
+            if id >= timers.len() {
+                continue;
+            }
 
-                let mut removed = 0;
-                for id in control.timer.iter(
-                    len.try_into()
-                        .expect("xidlehook does not yet handle this many timers"),
-                ) {
-                    let timers = self.xidlehook.timers_mut()?;
-
-                    let id = match id.checked_sub(removed) {
-                        Some(res) => usize::from(res),
-                        None => continue,
-                    };
-                    if id >= timers.len() {
-                        continue;
+            match control.action {
+                Action::Disable => {
+                    timers[id].set_disabled(true);
+                },
+                Action::Enable => {
+                    timers[id].set_disabled(false);
+                },
+                Action::Trigger => {
+                    if self.xidlehook.trigger(id, self.backend.get_idle()?, true)? == Progress::Stop
+                    {
+                        return Ok(None);
                     }
+                },
+                Action::Delete => {
+                    // TODO: Probably want to use `retain` to optimize this...
+                    timers.remove(id);
 
-                    match control.action {
-                        Action::Disable => {
-                            timers[id].set_disabled(true);
-                        },
-                        Action::Enable => {
-                            timers[id].set_disabled(false);
-                        },
-                        Action::Trigger => {
-                            if self.xidlehook.trigger(id, self.xcb.get_idle()?, true)?
-                                == Progress::Stop
-                            {
-                                return Ok(None);
-                            }
-                        },
-                        Action::Delete => {
-                            // TODO: Probably want to use `retain` to optimize this...
-                            timers.remove(id);
-
-                            // Working with this large indices pointing to an allocated object... I
-                            // think we're fine
-                            #[allow(clippy::integer_arithmetic)]
-                            {
-                                removed += 1;
-                            }
-                        },
+                    // Working with this large indices pointing to an allocated object... I
+                    // think we're fine
+                    #[allow(clippy::integer_arithmetic)]
+                    {
+                        removed += 1;
                     }
+                },
+            }
+        }
+
+        Ok(Some(Reply::empty()))
+    }
+
+    /// Describe the selected timers
+    fn query_timers(&self, query: &Query) -> Reply {
+        let timers = self.xidlehook.timers();
+        let mut output = Vec::new();
+
+        for id in query.timer.iter(
+            timers
+                .len()
+                .try_into()
+                .expect("xidlehook does not yet handle this many timers"),
+        ) {
+            let Some(timer) = timers.get(usize::from(id)) else {
+                return Reply::error(format!(
+                    "timer {id} does not exist, there are only {} timers",
+                    timers.len()
+                ));
+            };
+            output.push(QueryResult {
+                timer: id,
+                name: timer.name().map(String::from),
+                time: timer.get_time(),
+                activation: timer.activation().to_vec(),
+                abortion: timer.abortion().to_vec(),
+                deactivation: timer.deactivation().to_vec(),
+                disabled: timer.get_disabled(),
+                paused: timer.get_paused(),
+                session: timer.get_session(),
+                wait: timer.get_wait(),
+                reset_on_exit: timer.get_reset_on_exit(),
+                state: self.xidlehook.timer_state(usize::from(id)).into(),
+            });
+        }
+
+        Reply::success(ReplyData::QueryResult { timers: output })
+    }
+
+    /// Pause or resume every timer, for `PauseAll` and `ResumeAll`
+    fn pause_all(&mut self, paused: bool) -> xidlehook_core::Result<Option<Reply>> {
+        if self.paused == paused {
+            return Ok(Some(Reply::error(if paused {
+                "timers are already paused"
+            } else {
+                "timers are not paused"
+            })));
+        }
+
+        // Timers that are disabled on their own stay disabled
+        let timers = self.xidlehook.timers_mut()?;
+        let affected = timers.iter().filter(|timer| !timer.get_disabled()).count();
+        for timer in timers {
+            timer.set_paused(paused);
+        }
+        self.paused = paused;
+
+        Ok(Some(Reply::success(ReplyData::Affected { affected })))
+    }
+
+    pub fn handle_socket(&mut self, msg: Message) -> xidlehook_core::Result<Option<Reply>> {
+        match msg {
+            Message::Add(add) => self.add_timer(add),
+            Message::RemoveTimer { index } => {
+                let len = self.xidlehook.timers().len();
+                if usize::from(index) >= len {
+                    return Ok(Some(Reply::error(format!(
+                        "timer {index} does not exist, there are only {len} timers"
+                    ))));
                 }
+                // Unless it already went off, the chain carries on
+                self.xidlehook.remove_timer(usize::from(index))?;
 
-                Ok(Some(Reply::Empty))
+                Ok(Some(Reply::empty()))
             },
-            Message::Query(query) => {
-                let timers = self.xidlehook.timers();
-                let mut output = Vec::new();
-
-                for id in query.timer.iter(
-                    timers
-                        .len()
-                        .try_into()
-                        .expect("xidlehook does not yet handle this many timers"),
-                ) {
-                    let timer = match timers.get(usize::from(id)) {
-                        Some(timer) => timer,
-                        None => continue,
-                    };
-                    output.push(QueryResult {
-                        timer: id,
-                        time: timer.get_time(),
-                        activation: timer.activation().to_vec(),
-                        abortion: timer.abortion().to_vec(),
-                        deactivation: timer.deactivation().to_vec(),
-                        disabled: timer.get_disabled(),
-                    });
+            Message::Control(control) => self.control_timers(&control),
+            Message::Query(query) => Ok(Some(self.query_timers(&query))),
+            Message::PauseAll => self.pause_all(true),
+            Message::Pause | Message::Resume => {
+                let pause = matches!(msg, Message::Pause);
+                if self.xidlehook.is_paused() == pause {
+                    return Ok(Some(Reply::error(if pause {
+                        "timers are already paused"
+                    } else {
+                        "timers are not paused"
+                    })));
                 }
+                self.xidlehook.set_paused(pause)?;
+                Ok(Some(Reply::empty()))
+            },
+            Message::ControlModule(control) => {
+                let modules = &mut self.xidlehook.module_mut().1;
+                let Some(module) = modules.iter_mut().find(|m| m.name() == control.name) else {
+                    let names: Vec<&str> = modules.iter().map(Toggle::name).collect();
+                    return Ok(Some(Reply::error(format!(
+                        "module {:?} is not in use, the modules in use are: {}",
+                        control.name,
+                        names.join(", ")
+                    ))));
+                };
+                module.enabled = match control.action {
+                    ModuleAction::Enable => true,
+                    ModuleAction::Disable => false,
+                    ModuleAction::Toggle => !module.enabled,
+                };
 
-                Ok(Some(Reply::QueryResult(output)))
+                Ok(Some(Reply::success(ReplyData::Modules {
+                    modules: self.module_states(),
+                })))
+            },
+            Message::QueryModules => Ok(Some(Reply::success(ReplyData::Modules {
+                modules: self.module_states(),
+            }))),
+            // The main loop exits once this is replied to
+            Message::Shutdown => Ok(Some(Reply::empty())),
+            Message::History { limit } => Ok(Some(Reply::success(ReplyData::History {
+                events: self.history.recent(limit),
+            }))),
+            Message::Snooze { secs } => {
+                if self.xidlehook.snooze(Duration::from_secs(secs)) {
+                    Ok(Some(Reply::empty()))
+                } else {
+                    Ok(Some(Reply::error(
+                        "there is no timer left to snooze until the user is active again",
+                    )))
+                }
+            },
+            Message::NextTimer => {
+                let next = self.xidlehook.next_activation(self.backend.get_idle()?)?;
+                Ok(Some(Reply::success(ReplyData::NextTimer {
+                    timer: next.and_then(|(index, _)| index.try_into().ok()),
+                    remaining: next.map(|(_, remaining)| remaining),
+                })))
+            },
+            Message::Idle => {
+                let idle = self.backend.get_idle()?;
+                Ok(Some(Reply::success(ReplyData::Idle {
+                    idle_ms: idle.as_millis().try_into().unwrap_or(u64::MAX),
+                })))
+            },
+            Message::Ping => match self.backend.get_idle() {
+                Ok(idle) => Ok(Some(Reply::success(ReplyData::Health {
+                    xcb_ok: true,
+                    idle,
+                }))),
+                Err(err) => Ok(Some(Reply::error(format!(
+                    "can't read the idle time from the X server: {err}"
+                )))),
             },
+            Message::ResumeAll => self.pause_all(false),
         }
     }
 }
@@ -1,7 +1,10 @@
-use std::convert::Infallible;
+use std::{
+    convert::{Infallible, TryFrom},
+    time::Duration,
+};
 
 use async_std::{
-    io::{BufReader, BufWriter},
+    io::{self, BufReader, BufWriter},
     os::unix::net::UnixListener,
     prelude::*,
     sync, task,
@@ -13,11 +16,33 @@ pub mod models;
 
 pub use self::models::*;
 
+/// The longest message accepted, in bytes. Without a limit, a client
+/// that never sends a newline would make xidlehook buffer forever.
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
 pub async fn main_loop(
     address: &str,
+    retries: u32,
+    backoff: Duration,
     socket_tx: sync::Sender<(Message, sync::Sender<Reply>)>,
 ) -> xidlehook_core::Result<Infallible> {
-    let listener = UnixListener::bind(address).await?;
+    let mut retries_left = retries;
+    let mut delay = backoff;
+    let listener = loop {
+        match UnixListener::bind(address).await {
+            Ok(listener) => break listener,
+            Err(err) if retries_left > 0 => {
+                warn!(
+                    "Couldn't bind unix listener on address {address:?} ({err}), retrying in {delay:?}"
+                );
+                task::sleep(delay).await;
+
+                retries_left = retries_left.saturating_sub(1);
+                delay = delay.checked_mul(2).unwrap_or(delay);
+            },
+            Err(err) => return Err(err.into()),
+        }
+    };
     trace!("Bound unix listener on address {:?}", address);
 
     loop {
@@ -25,40 +50,228 @@ pub async fn main_loop(
         trace!("Connection from {:?}", addr);
 
         let socket_tx = socket_tx.clone();
-        task::spawn(async move {
-            let reader = BufReader::new(&stream);
-            let mut writer = BufWriter::new(&stream);
-            let mut lines = reader.lines();
-            while let Some(msg) = lines.next().await {
-                let res = msg
-                    .map_err(|err| err.to_string())
-                    .and_then(|msg| serde_json::from_str(&msg).map_err(|err| err.to_string()));
-
-                let msg: Message = match res {
-                    Ok(json) => json,
-                    Err(err) => {
-                        warn!("couldn't interpret message: {}", err);
-                        continue;
-                    },
-                };
+        task::spawn(async move { serve(&stream, &stream, socket_tx).await });
+    }
+}
+
+/// Read messages from standard input and write replies to standard
+/// output, just like a socket connection. Returns when standard input
+/// is closed.
+pub async fn stdin_loop(socket_tx: sync::Sender<(Message, sync::Sender<Reply>)>) {
+    serve(io::stdin(), io::stdout(), socket_tx).await;
+    trace!("Standard input was closed");
+}
+
+#[derive(Debug, PartialEq)]
+enum Frame {
+    Line(Vec<u8>),
+    /// A line longer than the limit, which was skipped
+    TooLong,
+}
 
+/// Read one line, no matter how many reads it arrives in. The last
+/// line doesn't need to end with a newline. Lines longer than `limit`
+/// are skipped up to the next newline, so the lines after them can
+/// still be read. Returns `None` at the end of the stream.
+async fn read_frame<R>(reader: &mut R, limit: usize) -> io::Result<Option<Frame>>
+where
+    R: io::BufRead + Unpin,
+{
+    let bound = u64::try_from(limit).unwrap_or(u64::MAX).saturating_add(1);
+
+    let mut line = Vec::new();
+    if (&mut *reader)
+        .take(bound)
+        .read_until(b'\n', &mut line)
+        .await?
+        == 0
+    {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+        return Ok(Some(Frame::Line(line)));
+    }
+    if line.len() <= limit {
+        // End of stream without a trailing newline
+        return Ok(Some(Frame::Line(line)));
+    }
+
+    loop {
+        line.clear();
+        let read = (&mut *reader)
+            .take(bound)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if read == 0 || line.last() == Some(&b'\n') {
+            return Ok(Some(Frame::TooLong));
+        }
+    }
+}
+
+/// Handle one line of JSON per message from `reader`, and reply to
+/// each on `writer`
+async fn serve<R, W>(reader: R, writer: W, socket_tx: sync::Sender<(Message, sync::Sender<Reply>)>)
+where
+    R: io::Read + Unpin,
+    W: io::Write + Unpin,
+{
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+    loop {
+        let res = match read_frame(&mut reader, MAX_MESSAGE_LEN).await {
+            Ok(None) => break,
+            Ok(Some(Frame::Line(line))) => {
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                serde_json::from_slice(&line).map_err(|err| err.to_string())
+            },
+            Ok(Some(Frame::TooLong)) => {
+                Err(format!("message is longer than {MAX_MESSAGE_LEN} bytes"))
+            },
+            Err(err) => {
+                warn!("couldn't read message: {err}");
+                break;
+            },
+        };
+
+        let reply = match res {
+            Ok(msg) => {
                 let (reply_tx, reply_rx) = sync::channel(1);
                 socket_tx.send((msg, reply_tx)).await;
 
-                let reply = reply_rx.recv().await;
+                reply_rx.recv().await
+            },
+            Err(err) => {
+                warn!("couldn't interpret message: {err}");
+                Some(Reply::error(format!("couldn't interpret message: {err}")))
+            },
+        };
 
-                let res = async {
-                    let msg = serde_json::to_vec(&reply)?;
-                    writer.write_all(&msg).await?;
-                    writer.write_all(&[b'\n']).await?;
-                    writer.flush().await?;
-                    Ok::<(), std::io::Error>(())
-                };
+        let res = async {
+            let msg = serde_json::to_vec(&reply)?;
+            writer.write_all(&msg).await?;
+            writer.write_all(b"\n").await?;
+            writer.flush().await?;
+            Ok::<(), std::io::Error>(())
+        };
 
-                if let Err(err) = res.await {
-                    warn!("couldn't send reply: {}", err);
-                }
+        if let Err(err) = res.await {
+            warn!("couldn't send reply: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{collections::VecDeque, pin::Pin};
+
+    use async_std::task::{Context, Poll};
+
+    /// A reader that returns the data in the given pieces, like a
+    /// client that writes a message in several parts
+    struct Pieces(VecDeque<&'static [u8]>);
+    impl io::Read for Pieces {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let Some(piece) = self.0.pop_front() else {
+                return Poll::Ready(Ok(0));
+            };
+            let len = piece.len().min(buf.len());
+            buf[..len].copy_from_slice(&piece[..len]);
+            if len < piece.len() {
+                self.0.push_front(&piece[len..]);
             }
-        });
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    fn frames(pieces: &[&'static [u8]], limit: usize) -> Vec<Frame> {
+        let mut reader = BufReader::new(Pieces(pieces.iter().copied().collect()));
+        task::block_on(async {
+            let mut frames = Vec::new();
+            while let Some(frame) = read_frame(&mut reader, limit).await.unwrap() {
+                frames.push(frame);
+            }
+            frames
+        })
+    }
+
+    fn line(line: &str) -> Frame {
+        Frame::Line(line.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn split_reads() {
+        assert_eq!(
+            frames(
+                &[
+                    b"{\"type\":",
+                    b"\"ping\"}",
+                    b"\n{\"type\"",
+                    b":\"query\"}\n"
+                ],
+                100
+            ),
+            vec![line("{\"type\":\"ping\"}"), line("{\"type\":\"query\"}")]
+        );
+        assert_eq!(
+            frames(&[b"{\"ty", b"pe\":\"ping\"}\n"], 100),
+            vec![line("{\"type\":\"ping\"}")]
+        );
+        let msg: Message = serde_json::from_slice(br#"{"type":"ping"}"#).unwrap();
+        assert!(matches!(msg, Message::Ping));
+    }
+
+    #[test]
+    fn no_trailing_newline() {
+        assert_eq!(
+            frames(&[b"{\"type\":\"ping\"}\n{\"type\":", b"\"ping\"}"], 100),
+            vec![line("{\"type\":\"ping\"}"), line("{\"type\":\"ping\"}")]
+        );
+    }
+
+    #[test]
+    fn oversized_lines() {
+        assert_eq!(
+            frames(&[b"0123456789", b"0123456789\nshort\n"], 10),
+            vec![Frame::TooLong, line("short")]
+        );
+        // Exactly at the limit is fine
+        assert_eq!(frames(&[b"0123456789\n"], 10), vec![line("0123456789")]);
+        assert_eq!(frames(&[b"01234567890"], 10), vec![Frame::TooLong]);
+    }
+
+    #[test]
+    fn list_timers() {
+        match serde_json::from_str(r#"{"type":"listTimers"}"#).unwrap() {
+            Message::Query(Query { timer: Filter::All }) => (),
+            msg => panic!("parsed as {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn idle_reply() {
+        let reply = Reply::success(ReplyData::Idle { idle_ms: 1234 });
+        let json = serde_json::to_string(&reply).unwrap();
+        assert_eq!(json, "{\"ok\":true,\"idle_ms\":1234}");
+
+        // No other kind of reply may swallow it when parsed back
+        match serde_json::from_str::<Reply>(&json).unwrap().data {
+            ReplyData::Idle { idle_ms } => assert_eq!(idle_ms, 1234),
+            data => panic!("parsed as {:?}", data),
+        }
+
+        // Nor may it swallow an empty reply
+        match serde_json::from_str::<Reply>("{\"ok\":true}").unwrap().data {
+            ReplyData::Empty {} => (),
+            data => panic!("parsed as {:?}", data),
+        }
     }
 }
@@ -38,10 +38,18 @@ pub enum Action {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Add {
     pub index: Option<TimerId>,
+    #[serde(default)]
+    pub name: Option<String>,
     pub time: Duration,
     pub activation: Vec<String>,
     pub abortion: Vec<String>,
     pub deactivation: Vec<String>,
+    /// Whether later timers wait for the activation command to exit
+    #[serde(default)]
+    pub wait: bool,
+    /// Whether the activation command exiting counts as activity
+    #[serde(default)]
+    pub reset_on_exit: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -57,27 +65,206 @@ pub struct Query {
     pub timer: Filter,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ModuleAction {
+    Disable,
+    Enable,
+    /// Disable the module if it's enabled, and enable it otherwise
+    Toggle,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ControlModule {
+    /// The module's name, which is the flag that enabled it, such as
+    /// "not-when-fullscreen"
+    pub name: String,
+    pub action: ModuleAction,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum Message {
     Add(Add),
+    /// Remove one timer. Unlike deleting timers with `Control`, this
+    /// doesn't abort the chain unless the timer already went off in
+    /// it.
+    RemoveTimer {
+        index: TimerId,
+    },
     Control(Control),
+    /// Return every timer's index, duration, commands, whether it's
+    /// disabled or paused, and its state in the current chain, which shows the
+    /// one that went off last as active. `listTimers` is the same as a
+    /// query for all timers.
+    #[serde(alias = "listTimers")]
     Query(Query),
+    /// Hold back every timer, including ones added or reloaded later,
+    /// until `ResumeAll`. Which timers are disabled is left alone.
+    PauseAll,
+    ResumeAll,
+    /// Stop running timers until `Resume`, treating the user as active
+    /// meanwhile, without touching which timers are disabled. Aborts
+    /// the chain if a timer went off.
+    Pause,
+    /// Run timers again after `Pause`, counting the idle time from now
+    Resume,
+    ControlModule(ControlModule),
+    QueryModules,
+    /// Exit, like on `SIGINT`. This is what `--replace` uses.
+    Shutdown,
+    /// Check that xidlehook is responsive and can read the idle time
+    /// from the X server. Fails if it can't.
+    Ping,
+    /// Return the most recent events, oldest first
+    History {
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Push back the timer that's up next by this many seconds, until
+    /// it activates or the user is active
+    Snooze {
+        secs: u64,
+    },
+    /// Return which timer is up next, and how long until it activates.
+    /// Both are null if it wouldn't, such as because a module is
+    /// holding it back.
+    NextTimer,
+    /// Return the user's current idle time, so status bars can poll
+    /// this instead of starting `xidlehook --print` every time
+    Idle,
+}
+
+/// Where a timer is in the current chain, see
+/// `xidlehook_core::TimerState`
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TimerState {
+    #[default]
+    Armed,
+    Active,
+    Deactivated,
+    Aborted,
+    Skipped,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[allow(clippy::struct_excessive_bools)] // part of the wire format
 pub struct QueryResult {
     pub timer: TimerId,
+    #[serde(default)]
+    pub name: Option<String>,
     pub time: Duration,
     pub activation: Vec<String>,
     pub abortion: Vec<String>,
     pub deactivation: Vec<String>,
     pub disabled: bool,
+    /// Whether it's held back by `PauseAll`, which is separate from
+    /// being disabled
+    #[serde(default)]
+    pub paused: bool,
+    #[serde(default)]
+    pub session: bool,
+    #[serde(default)]
+    pub wait: bool,
+    #[serde(default)]
+    pub reset_on_exit: bool,
+    #[serde(default)]
+    pub state: TimerState,
 }
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EventKind {
+    Activated,
+    Aborted,
+    Deactivated,
+    Warning,
+    /// A power module started or stopped holding timers back, such as
+    /// when the charger was plugged in
+    PowerChanged,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Event {
+    /// When this happened, in seconds since the Unix epoch
+    pub time: u64,
+    pub kind: EventKind,
+    /// The name of the timer this is about, if any
+    #[serde(default)]
+    pub timer: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ModuleState {
+    pub name: String,
+    pub enabled: bool,
+}
+
+/// The payload of a reply. This is flattened into the reply object,
+/// so every variant needs to be a struct (or nothing at all).
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
-pub enum Reply {
-    Empty,
-    Error(String),
-    QueryResult(Vec<QueryResult>),
+pub enum ReplyData {
+    QueryResult {
+        timers: Vec<QueryResult>,
+    },
+    Affected {
+        affected: usize,
+    },
+    Modules {
+        modules: Vec<ModuleState>,
+    },
+    Health {
+        xcb_ok: bool,
+        idle: Duration,
+    },
+    History {
+        events: Vec<Event>,
+    },
+    Idle {
+        idle_ms: u64,
+    },
+    NextTimer {
+        // Always present, but may be null, so an empty reply isn't taken for this
+        #[serde(deserialize_with = "Option::deserialize")]
+        timer: Option<TimerId>,
+        #[serde(deserialize_with = "Option::deserialize")]
+        remaining: Option<Duration>,
+    },
+    // Must be last, as it matches anything
+    Empty {},
+}
+
+/// A reply is always an object with an `ok` field. On failure, `ok`
+/// is false and `error` contains a human-readable message:
+/// `{"ok":false,"error":"..."}`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Reply {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub data: ReplyData,
+}
+impl Reply {
+    pub fn success(data: ReplyData) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            data,
+        }
+    }
+    pub fn empty() -> Self {
+        Self::success(ReplyData::Empty {})
+    }
+    pub fn error<S: Into<String>>(error: S) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.into()),
+            data: ReplyData::Empty {},
+        }
+    }
 }
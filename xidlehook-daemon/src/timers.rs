@@ -1,9 +1,250 @@
-use std::{process::Command, time::Duration};
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    mem,
+    process::Command,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
-use xidlehook_core::{timers::CmdTimer as Inner, Result, Timer};
+use log::{error, info, warn};
 
+#[cfg(feature = "backlight")]
+use crate::backlight::Dim;
+use crate::{
+    backend::Backend, history::History, notify, outputs::OutputCondition,
+    signal_action::SignalAction, socket::EventKind,
+};
+
+#[cfg(feature = "notify")]
+use xidlehook_core::modules::Notify;
+use xidlehook_core::{
+    timers::{AbsoluteTimer, CmdTimer as Inner, OnFailure, SessionTimer, TimeWindow},
+    Error, Module, Result, Timer, TimerInfo,
+};
+
+/// The shell timer commands are passed through, unless
+/// `--command-shell` says otherwise
+pub const DEFAULT_SHELL: &str = "/bin/sh";
+
+/// Tells commands how long the user has been idle, in milliseconds
+const ENV_IDLE_MS: &str = "XIDLEHOOK_IDLE_MS";
+/// Tells commands the index of the timer that runs them
+const ENV_TIMER_INDEX: &str = "XIDLEHOOK_TIMER_INDEX";
+/// Tells commands whether they run on "activation", "abortion" or
+/// "deactivation"
+const ENV_EVENT: &str = "XIDLEHOOK_EVENT";
+/// Tells commands the index of the timer the chain got to, which for a
+/// deactivation is the timer that took over
+const ENV_REACHED_INDEX: &str = "XIDLEHOOK_REACHED_INDEX";
+
+/// Build the arguments for running a command through the shell, which
+/// is the program and any arguments to run before `-c`
+pub fn shell(program: &[String], cmd: String) -> Vec<String> {
+    let mut parts = program.to_vec();
+    parts.push("-c".into());
+    parts.push(cmd);
+    parts
+}
+
+/// Build a command out of its arguments, the first one being the
+/// program
+pub fn command(parts: &[String]) -> Command {
+    let mut cmd = Command::new(&parts[0]);
+    cmd.args(&parts[1..]);
+    cmd
+}
+
+/// What a timer does when its activation command fails to run or exits
+/// unsuccessfully, see `CmdTimer::set_on_failure`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FailureAction {
+    /// Log a warning and carry on
+    Ignore,
+    /// Log a warning and run this command instead, the first element
+    /// being the program
+    Fallback(Vec<String>),
+    /// Exit with an error
+    Exit,
+}
+
+/// Counts consecutive failures of activation commands across all
+/// timers, and gives up once there were too many in a row. This is
+/// used to implement `--max-failures`.
+pub struct Failures {
+    consecutive: Cell<u32>,
+    max: u32,
+    /// Whether to disable the failing timer, instead of exiting
+    disable: bool,
+}
+impl Failures {
+    pub fn new(max: u32, disable: bool) -> Self {
+        Self {
+            consecutive: Cell::new(0),
+            max,
+            disable,
+        }
+    }
+
+    /// An activation command exited successfully
+    pub fn success(&self) {
+        self.consecutive.set(0);
+    }
+
+    /// The activation command of this timer failed. Returns an error
+    /// if there were too many failures in a row and xidlehook should
+    /// exit.
+    pub fn failure(&self, timer: &CmdTimer) -> Result<()> {
+        let consecutive = self.consecutive.get().saturating_add(1);
+        if consecutive < self.max {
+            self.consecutive.set(consecutive);
+            return Ok(());
+        }
+        self.consecutive.set(0);
+
+        let message = format!("{consecutive} timer commands failed in a row");
+        if self.disable {
+            error!(
+                "!!! {}, disabling timer {:?}",
+                message,
+                timer.name.as_deref().unwrap_or("")
+            );
+            timer.gave_up.set(true);
+            Ok(())
+        } else {
+            Err(message.into())
+        }
+    }
+}
+
+/// The core timer, wrapped in whichever core timers change how its
+/// time is measured
+enum Wrapped {
+    Idle(Inner),
+    Session(SessionTimer<Inner>),
+    IdleAt(AbsoluteTimer<Inner>),
+    SessionAt(AbsoluteTimer<SessionTimer<Inner>>),
+}
+impl Wrapped {
+    fn new(inner: Inner, session: bool, at: Option<TimeWindow>) -> Self {
+        match (session, at) {
+            (false, None) => Self::Idle(inner),
+            (true, None) => Self::Session(SessionTimer::new(inner)),
+            (false, Some(at)) => Self::IdleAt(AbsoluteTimer::new(inner, at)),
+            (true, Some(at)) => Self::SessionAt(AbsoluteTimer::new(SessionTimer::new(inner), at)),
+        }
+    }
+    fn into_inner(self) -> Inner {
+        match self {
+            Self::Idle(inner) => inner,
+            Self::Session(timer) => timer.into_inner(),
+            Self::IdleAt(timer) => timer.into_inner(),
+            Self::SessionAt(timer) => timer.into_inner().into_inner(),
+        }
+    }
+    fn get(&self) -> &Inner {
+        match self {
+            Self::Idle(inner) => inner,
+            Self::Session(timer) => timer.inner(),
+            Self::IdleAt(timer) => timer.inner(),
+            Self::SessionAt(timer) => timer.inner().inner(),
+        }
+    }
+    fn get_mut(&mut self) -> &mut Inner {
+        match self {
+            Self::Idle(inner) => inner,
+            Self::Session(timer) => timer.inner_mut(),
+            Self::IdleAt(timer) => timer.inner_mut(),
+            Self::SessionAt(timer) => timer.inner_mut().inner_mut(),
+        }
+    }
+    fn timer(&mut self) -> &mut dyn Timer {
+        match self {
+            Self::Idle(inner) => inner,
+            Self::Session(timer) => timer,
+            Self::IdleAt(timer) => timer,
+            Self::SessionAt(timer) => timer,
+        }
+    }
+    fn session(&self) -> bool {
+        matches!(self, Self::Session(_) | Self::SessionAt(_))
+    }
+    fn at(&self) -> Option<TimeWindow> {
+        match self {
+            Self::Idle(_) | Self::Session(_) => None,
+            Self::IdleAt(timer) => Some(timer.window()),
+            Self::SessionAt(timer) => Some(timer.window()),
+        }
+    }
+    /// Wrap the core timer anew, as the wrappers can't be changed in
+    /// place
+    fn rewrap(&mut self, session: bool, at: Option<TimeWindow>) {
+        let inner = mem::take(self).into_inner();
+        *self = Self::new(inner, session, at);
+    }
+}
+impl Default for Wrapped {
+    fn default() -> Self {
+        Self::Idle(Inner::default())
+    }
+}
+
+#[allow(clippy::struct_excessive_bools)] // independent per-timer flags
 pub struct CmdTimer {
-    inner: Inner,
+    inner: Wrapped,
+
+    name: Option<String>,
+    /// What to do when the activation command fails, if anything
+    /// other than the default, see `set_on_failure`
+    on_failure: Option<FailureAction>,
+    /// Whether to show a desktop notification when the activation
+    /// command fails
+    notify_on_failure: bool,
+    /// Whether to skip the abortion and deactivation commands
+    no_cancel: bool,
+    /// Whether later timers wait for the activation command to exit
+    wait: bool,
+    /// Whether the activation command exiting counts as activity
+    reset_on_exit: bool,
+    /// Hold the timer back once it activated this many times within
+    /// this window, see `set_rate_limit`
+    rate_limit: Option<(u32, Duration)>,
+    /// When the timer recently activated, oldest first
+    activations: VecDeque<Instant>,
+    /// Whether the rate limit is holding the timer back right now, so
+    /// that it's only warned about once
+    held_back: bool,
+    /// Where to count failures of the activation command, if anywhere
+    failures: Option<Rc<Failures>>,
+    /// Whether this timer was disabled because of too many failures
+    gave_up: Cell<bool>,
+    /// Whether every timer was paused over the socket. This is kept
+    /// apart from `disabled`, so resuming leaves that as it was.
+    paused: bool,
+    /// Where to record what this timer does, if anywhere
+    history: Option<History>,
+    /// Skip this timer depending on the connected outputs
+    outputs: Option<OutputCondition>,
+    /// Dim the backlight on activation, and restore it on abortion or
+    /// deactivation
+    #[cfg(feature = "backlight")]
+    dim: Option<Dim>,
+    /// The summary and body of every desktop notification shown before
+    /// this timer runs, see `add_notify`
+    #[cfg(feature = "notify")]
+    notifications: Vec<(String, String)>,
+    /// Send a signal on activation, before running the command
+    signal: Option<SignalAction>,
+    /// Where to read the idle time from to pass on to commands
+    backend: Option<Rc<Backend>>,
+    /// Whether this timer was added over the socket, instead of on the
+    /// command line
+    added: bool,
+    /// Where this timer is in the list as of when it last ran
+    index: Option<usize>,
+    /// How far the chain got as of when this timer last ran, see
+    /// `TimerInfo::reached`
+    reached: Option<usize>,
 
     activation: Option<Vec<String>>,
     abortion: Option<Vec<String>>,
@@ -17,10 +258,33 @@ impl CmdTimer {
         deactivation: Vec<String>,
     ) -> Self {
         let mut me = Self {
-            inner: Inner {
+            inner: Wrapped::Idle(Inner {
                 time,
                 ..Inner::default()
-            },
+            }),
+            name: Some(activation.join(" ")).filter(|s| !s.is_empty()),
+            on_failure: None,
+            notify_on_failure: false,
+            no_cancel: false,
+            wait: false,
+            reset_on_exit: false,
+            rate_limit: None,
+            activations: VecDeque::new(),
+            held_back: false,
+            failures: None,
+            gave_up: Cell::new(false),
+            paused: false,
+            history: None,
+            outputs: None,
+            #[cfg(feature = "backlight")]
+            dim: None,
+            #[cfg(feature = "notify")]
+            notifications: Vec::new(),
+            signal: None,
+            backend: None,
+            added: false,
+            index: None,
+            reached: None,
             activation: Some(activation).filter(|v| !v.is_empty()),
             abortion: Some(abortion).filter(|v| !v.is_empty()),
             deactivation: Some(deactivation).filter(|v| !v.is_empty()),
@@ -29,43 +293,400 @@ impl CmdTimer {
         me
     }
 
+    /// Like `from_parts`, but pass each command through the shell
+    /// (see `shell`)
     pub fn from_shell(
         time: Duration,
         activation: String,
         abortion: String,
         deactivation: String,
+        program: &[String],
     ) -> Self {
         let mut me = Self {
-            inner: Inner {
+            inner: Wrapped::Idle(Inner {
                 time,
                 ..Inner::default()
-            },
+            }),
+            name: Some(activation.clone()).filter(|s| !s.is_empty()),
+            on_failure: None,
+            notify_on_failure: false,
+            no_cancel: false,
+            wait: false,
+            reset_on_exit: false,
+            rate_limit: None,
+            activations: VecDeque::new(),
+            held_back: false,
+            failures: None,
+            gave_up: Cell::new(false),
+            paused: false,
+            history: None,
+            outputs: None,
+            #[cfg(feature = "backlight")]
+            dim: None,
+            #[cfg(feature = "notify")]
+            notifications: Vec::new(),
+            signal: None,
+            backend: None,
+            added: false,
+            index: None,
+            reached: None,
             activation: Some(activation)
                 .filter(|s| !s.is_empty())
-                .map(|s| vec!["/bin/sh".into(), "-c".into(), s]),
+                .map(|cmd| shell(program, cmd)),
             abortion: Some(abortion)
                 .filter(|s| !s.is_empty())
-                .map(|s| vec!["/bin/sh".into(), "-c".into(), s]),
+                .map(|cmd| shell(program, cmd)),
             deactivation: Some(deactivation)
                 .filter(|s| !s.is_empty())
-                .map(|s| vec!["/bin/sh".into(), "-c".into(), s]),
+                .map(|cmd| shell(program, cmd)),
         };
         me.sync();
         me
     }
 
     pub fn set_disabled(&mut self, val: bool) {
-        self.inner.disabled = val;
+        self.inner.get_mut().disabled = val;
+        self.gave_up.set(false);
     }
     pub fn get_disabled(&self) -> bool {
-        self.inner.disabled
+        self.inner.get().disabled || self.gave_up.get()
+    }
+
+    /// Hold this timer back along with every other one, see
+    /// `Message::PauseAll`
+    pub fn set_paused(&mut self, val: bool) {
+        self.paused = val;
+    }
+    pub fn get_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Record what this timer does here
+    pub fn set_history(&mut self, history: Option<History>) {
+        self.history = history;
+    }
+    fn record(&self, kind: EventKind) {
+        if let Some(ref history) = self.history {
+            history.record(kind, self.name.as_deref(), None);
+        }
+    }
+
+    /// Read the idle time from here when a command runs, to pass it on
+    /// to the command
+    pub fn set_backend(&mut self, backend: Option<Rc<Backend>>) {
+        self.backend = backend;
+    }
+    /// The environment variables that tell a command running for this
+    /// event what's going on. The idle time is read right now.
+    fn env(&self, event: &str) -> Vec<(&'static str, String)> {
+        let mut vars = vec![(ENV_EVENT, event.to_string())];
+        if let Some(index) = self.index {
+            vars.push((ENV_TIMER_INDEX, index.to_string()));
+        }
+        if let Some(reached) = self.reached {
+            vars.push((ENV_REACHED_INDEX, reached.to_string()));
+        }
+        if let Some(idle) = self
+            .backend
+            .as_ref()
+            .and_then(|backend| backend.get_idle().ok())
+        {
+            vars.push((ENV_IDLE_MS, idle.as_millis().to_string()));
+        }
+        vars
+    }
+
+    /// Mark this timer as added over the socket, so it's not part of
+    /// the configuration
+    pub fn set_added(&mut self, val: bool) {
+        self.added = val;
+    }
+    pub fn get_added(&self) -> bool {
+        self.added
+    }
+
+    /// Count failures of the activation command here
+    pub fn set_failures(&mut self, failures: Option<Rc<Failures>>) {
+        self.failures = failures;
+    }
+
+    /// Measure time in elapsed session time (since now, or since the
+    /// last activation) instead of in idle time
+    pub fn set_session(&mut self, val: bool) {
+        let at = self.inner.at();
+        self.inner.rewrap(val, at);
+    }
+    pub fn get_session(&self) -> bool {
+        self.inner.session()
+    }
+
+    /// Only activate within this time of the day
+    pub fn set_at(&mut self, at: Option<TimeWindow>) {
+        let session = self.inner.session();
+        self.inner.rewrap(session, at);
+    }
+
+    /// Take a random duration from the current one up to `max`, drawn
+    /// anew whenever the chain starts over
+    pub fn set_range(&mut self, max: Duration) -> Result<()> {
+        let min = self
+            .inner
+            .get()
+            .range
+            .map_or(self.inner.get().time, |(min, _)| min);
+        if max < min {
+            return Err(format!(
+                "a random duration up to {max:?} can't be shorter than the timer's {min:?}"
+            )
+            .into());
+        }
+        self.inner.get_mut().range = Some((min, max));
+        self.inner.get_mut().randomize();
+        Ok(())
+    }
+
+    /// Set what happens when the activation command fails to run or
+    /// exits unsuccessfully. By default, xidlehook exits if it can't be
+    /// run, but carries on if it exits unsuccessfully.
+    pub fn set_on_failure(&mut self, on_failure: Option<FailureAction>) {
+        self.inner.get_mut().on_failure = match on_failure {
+            Some(FailureAction::Ignore) => OnFailure::Ignore,
+            Some(FailureAction::Fallback(ref fallback)) => OnFailure::Fallback(command(fallback)),
+            Some(FailureAction::Exit) | None => OnFailure::Exit,
+        };
+        self.on_failure = on_failure;
+    }
+
+    /// Show a desktop notification when the activation command can't
+    /// be run, or exits unsuccessfully (see `notify_exit`)
+    pub fn set_notify_on_failure(&mut self, val: bool) {
+        self.notify_on_failure = val;
+    }
+
+    /// Never run the abortion or deactivation commands, only the
+    /// activation command
+    pub fn set_no_cancel(&mut self, val: bool) {
+        self.no_cancel = val;
+    }
+
+    /// Make later timers wait for the activation command to exit
+    pub fn set_wait(&mut self, val: bool) {
+        self.wait = val;
+    }
+    pub fn get_wait(&self) -> bool {
+        self.wait
+    }
+
+    /// Start over from the first timer once the activation command
+    /// exits, as if the user was active
+    pub fn set_reset_on_exit(&mut self, val: bool) {
+        self.reset_on_exit = val;
+    }
+    pub fn get_reset_on_exit(&self) -> bool {
+        self.reset_on_exit
+    }
+
+    /// Skip this timer depending on which outputs are connected. This
+    /// is checked whenever the timer is up next.
+    pub fn set_outputs(&mut self, outputs: Option<OutputCondition>) {
+        self.outputs = outputs;
+    }
+
+    /// Send a signal whenever this timer activates. Timers without a
+    /// command are named after the signal instead.
+    pub fn set_signal(&mut self, signal: Option<SignalAction>) {
+        if self.name.is_none() {
+            self.name = signal.as_ref().map(ToString::to_string);
+        }
+        self.signal = signal;
+    }
+
+    /// The process ID of the activation command that was just started,
+    /// if later timers should wait for it to exit
+    pub fn waiting_for(&self) -> Option<u32> {
+        self.inner.get().activation_pid.filter(|_| self.wait)
+    }
+
+    /// Only log what this timer would do: The commands it would run and
+    /// the signal it would send. The backlight isn't dimmed either.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.inner.get_mut().dry_run = dry_run;
+    }
+
+    /// Show a desktop notification whenever this timer is about to
+    /// run, see `xidlehook_core::modules::Notify`
+    #[cfg(feature = "notify")]
+    pub fn add_notify(&mut self, summary: String, body: String) -> Result<()> {
+        let notify = Notify::new(summary.clone(), body.clone())?;
+        self.inner.get_mut().modules.push(Box::new(notify));
+        self.notifications.push((summary, body));
+        Ok(())
+    }
+
+    /// Kill the activation command if it's still running after this
+    /// long, see `xidlehook_core::timers::CmdTimer::timeout`
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.get_mut().timeout = timeout;
+    }
+
+    /// Once this timer activated `max` times within `window`, hold it
+    /// back until the oldest of those activations is `window` ago. This
+    /// guards against runaway loops, such as a lock command that exits
+    /// immediately without the user noticing, over and over again,
+    /// without ever turning the timer off for good.
+    pub fn set_rate_limit(&mut self, max: u32, window: Duration) {
+        self.rate_limit = Some((max, window));
+    }
+
+    /// How long the rate limit holds this timer back, if at all
+    fn rate_limited(&mut self) -> Option<Duration> {
+        let (max, window) = self.rate_limit?;
+        let now = Instant::now();
+        while self
+            .activations
+            .front()
+            .is_some_and(|time| now.saturating_duration_since(*time) >= window)
+        {
+            self.activations.pop_front();
+        }
+        if self.activations.len() < max as usize {
+            return None;
+        }
+        let oldest = self.activations.front()?;
+        Some(window.saturating_sub(now.saturating_duration_since(*oldest)))
+    }
+
+    /// Warn loudly that the rate limit holds back this timer for `wait`
+    fn warn_held_back(&self, wait: Duration) {
+        let (max, window) = self.rate_limit.unwrap_or_default();
+        let reason = format!(
+            "It went off {max} times within {window:?}, which looks like a loop. It's held back for {wait:?}."
+        );
+        error!(
+            "!!! Holding back timer {:?}: {}",
+            self.name.as_deref().unwrap_or(""),
+            reason
+        );
+        if self.notify_on_failure {
+            notify::failure(&self.failure_summary(), &reason);
+        }
+    }
+
+    /// Dim the backlight when this timer activates
+    #[cfg(feature = "backlight")]
+    pub fn set_dim(&mut self, dim: Option<Dim>) {
+        self.dim = dim;
+    }
+
+    /// Restore the backlight, if this timer dimmed it
+    fn restore_backlight(&self) {
+        #[cfg(feature = "backlight")]
+        {
+            if let Some(ref dim) = self.dim {
+                dim.restore();
+            }
+        }
+    }
+
+    /// Whether the process with this ID is the last activation command
+    pub fn is_activation(&self, pid: u32) -> bool {
+        self.inner.get().activation_pid == Some(pid)
+    }
+
+    /// The activation command failed to run, or exited unsuccessfully.
+    /// Returns an error if xidlehook should exit because of it, see
+    /// `Failures`.
+    pub fn activation_failed(&self, reason: &str) -> Result<()> {
+        if self.notify_on_failure {
+            notify::failure(&self.failure_summary(), reason);
+        }
+        match self.failures {
+            Some(ref failures) => failures.failure(self),
+            None => Ok(()),
+        }
+    }
+
+    /// The activation command exited unsuccessfully. Like
+    /// `activation_failed`, but this also does what `set_on_failure`
+    /// asks for, which the inner timer already did if the command
+    /// couldn't be run.
+    pub fn activation_exited(&self, reason: &str) -> Result<()> {
+        self.activation_failed(reason)?;
+        match self.on_failure {
+            None | Some(FailureAction::Ignore) => Ok(()),
+            Some(FailureAction::Fallback(ref fallback)) => {
+                warn!("Activation command failed, running fallback: {reason}");
+                command(fallback).spawn()?;
+                Ok(())
+            },
+            Some(FailureAction::Exit) => Err(format!("activation command failed: {reason}").into()),
+        }
+    }
+
+    /// The activation command exited successfully
+    pub fn activation_succeeded(&self) {
+        if let Some(ref failures) = self.failures {
+            failures.success();
+        }
+    }
+
+    fn failure_summary(&self) -> String {
+        match self.name {
+            Some(ref name) => format!("xidlehook: timer '{name}' failed"),
+            None => String::from("xidlehook: a timer failed"),
+        }
+    }
+
+    /// Override the name, which defaults to the activation command
+    pub fn set_name(&mut self, name: String) {
+        self.name = Some(name);
     }
 
     // There's no reason to not have `set_time` here as well, it just
     // isn't available in the API yet.
 
+    /// Whether this timer is defined just like `other`, such that one
+    /// can stand in for the other when the timers are reloaded. Every
+    /// setting counts, but not what happened at runtime, such as being
+    /// disabled over the socket.
+    pub fn same_as(&self, other: &Self) -> bool {
+        #[cfg(feature = "backlight")]
+        {
+            if self.dim != other.dim {
+                return false;
+            }
+        }
+        #[cfg(feature = "notify")]
+        {
+            if self.notifications != other.notifications {
+                return false;
+            }
+        }
+        let (inner, other_inner) = (self.inner.get(), other.inner.get());
+        inner.range == other_inner.range
+            // A random duration was most likely drawn differently
+            && (inner.range.is_some() || inner.time == other_inner.time)
+            && inner.timeout == other_inner.timeout
+            && inner.dry_run == other_inner.dry_run
+            && self.inner.session() == other.inner.session()
+            && self.inner.at() == other.inner.at()
+            && self.name == other.name
+            && self.added == other.added
+            && self.on_failure == other.on_failure
+            && self.notify_on_failure == other.notify_on_failure
+            && self.no_cancel == other.no_cancel
+            && self.wait == other.wait
+            && self.reset_on_exit == other.reset_on_exit
+            && self.rate_limit == other.rate_limit
+            && self.outputs == other.outputs
+            && self.signal == other.signal
+            && self.activation == other.activation
+            && self.abortion == other.abortion
+            && self.deactivation == other.deactivation
+    }
+
     pub fn get_time(&self) -> Duration {
-        self.inner.time
+        self.inner.get().time
     }
 
     pub fn activation(&self) -> &[String] {
@@ -80,49 +701,211 @@ impl CmdTimer {
 
     /// Propagate my fields to the inner timer
     fn sync(&mut self) {
-        self.inner.activation = self
-            .activation
-            .as_ref()
-            .map(|parts| (parts, Command::new(&parts[0])))
-            .map(|(parts, mut cmd)| {
-                cmd.args(&parts[1..]);
-                cmd
-            });
-        self.inner.abortion = self
-            .abortion
-            .as_ref()
-            .map(|parts| (parts, Command::new(&parts[0])))
-            .map(|(parts, mut cmd)| {
-                cmd.args(&parts[1..]);
-                cmd
-            });
-        self.inner.deactivation = self
-            .deactivation
-            .as_ref()
-            .map(|parts| (parts, Command::new(&parts[0])))
-            .map(|(parts, mut cmd)| {
-                cmd.args(&parts[1..]);
-                cmd
-            });
+        self.inner.get_mut().activation = self.activation.as_deref().map(command);
+        self.inner.get_mut().abortion = self.abortion.as_deref().map(command);
+        self.inner.get_mut().deactivation = self.deactivation.as_deref().map(command);
     }
 }
 impl Timer for CmdTimer {
     fn time_left(&mut self, idle_time: Duration) -> Result<Option<Duration>> {
-        self.inner.time_left(idle_time)
+        let left = self.inner.timer().time_left(idle_time)?;
+        let Some(wait) = self.rate_limited() else {
+            self.held_back = false;
+            return Ok(left);
+        };
+        // Only warn once it actually keeps the timer from activating
+        if left.is_none() && !self.held_back {
+            self.held_back = true;
+            self.warn_held_back(wait);
+        }
+        Ok(left.max(Some(wait)))
     }
     fn abort_urgency(&self) -> Option<Duration> {
-        self.inner.abort_urgency()
+        // Undimming the screen should feel instant
+        #[cfg(feature = "backlight")]
+        {
+            if self.dim.is_some() {
+                return Some(Duration::from_millis(100));
+            }
+        }
+        if self.no_cancel {
+            return None;
+        }
+        self.inner.get().abort_urgency()
+    }
+    fn reset(&mut self) {
+        self.inner.timer().reset();
+    }
+    fn prepare(&mut self, timer: TimerInfo) {
+        self.index = Some(timer.index);
+        self.reached = timer.reached;
     }
     fn activate(&mut self) -> Result<()> {
-        self.inner.activate()
+        if self.rate_limit.is_some() {
+            self.activations.push_back(Instant::now());
+        }
+        self.record(EventKind::Activated);
+        #[cfg(feature = "backlight")]
+        {
+            if let Some(dim) = self.dim.as_ref().filter(|_| !self.inner.get().dry_run) {
+                dim.dim();
+            }
+        }
+        if let Some(ref signal) = self.signal {
+            if self.inner.get().dry_run {
+                info!("Would send {signal}");
+            } else {
+                signal.send();
+            }
+        }
+        let env = self.env("activation");
+        if let Some(ref mut command) = self.inner.get_mut().activation {
+            command.envs(env);
+        }
+        let result = self.inner.timer().activate();
+        if self.activation.is_some()
+            && self.inner.get().activation_pid.is_none()
+            && !self.inner.get().dry_run
+        {
+            let reason = match result {
+                Err(ref err) => format!("Couldn't run the command: {err}"),
+                Ok(()) => String::from("Couldn't run the command"),
+            };
+            self.activation_failed(&reason)?;
+        }
+        result
     }
     fn abort(&mut self) -> Result<()> {
-        self.inner.abort()
+        self.record(EventKind::Aborted);
+        // This isn't a command, so it's done even with `no_cancel`
+        self.restore_backlight();
+        if self.no_cancel {
+            return Ok(());
+        }
+        let env = self.env("abortion");
+        if let Some(ref mut command) = self.inner.get_mut().abortion {
+            command.envs(env);
+        }
+        self.inner.timer().abort()
     }
     fn deactivate(&mut self) -> Result<()> {
-        self.inner.deactivate()
+        self.record(EventKind::Deactivated);
+        self.restore_backlight();
+        if self.no_cancel {
+            return Ok(());
+        }
+        let env = self.env("deactivation");
+        if let Some(ref mut command) = self.inner.get_mut().deactivation {
+            command.envs(env);
+        }
+        self.inner.timer().deactivate()
     }
     fn disabled(&mut self) -> bool {
-        self.inner.disabled()
+        self.get_disabled()
+            || self.paused
+            || self.outputs.as_ref().is_some_and(OutputCondition::skip)
+    }
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    fn take_warnings(&mut self) -> Vec<Error> {
+        self.inner.timer().take_warnings()
+    }
+    fn module(&mut self) -> Option<&mut dyn Module> {
+        self.inner.timer().module()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use xidlehook_core::{TimerState, Xidlehook};
+
+    #[test]
+    fn rate_limit() {
+        let hour = Duration::from_secs(3600);
+        let mut timer = CmdTimer::from_parts(Duration::from_secs(1), vec![], vec![], vec![]);
+        timer.set_rate_limit(2, hour);
+
+        for _ in 0..2 {
+            assert_eq!(timer.time_left(hour).unwrap(), None);
+            timer.activate().unwrap();
+        }
+
+        // Held back, but not disabled
+        let wait = timer.time_left(hour).unwrap().unwrap();
+        assert!(wait > Duration::from_secs(3590) && wait <= hour);
+        assert!(!timer.get_disabled());
+    }
+
+    #[test]
+    fn paused() {
+        let mut timer = CmdTimer::from_parts(Duration::from_secs(1), vec![], vec![], vec![]);
+        timer.set_paused(true);
+        assert!(timer.disabled());
+        assert!(!timer.get_disabled());
+
+        // Resuming leaves a timer that's disabled on its own disabled
+        timer.set_disabled(true);
+        timer.set_paused(false);
+        assert!(timer.disabled());
+        timer.set_disabled(false);
+        assert!(!timer.disabled());
+    }
+
+    #[test]
+    fn same_as() {
+        let timer =
+            || CmdTimer::from_parts(Duration::from_secs(1), vec!["true".into()], vec![], vec![]);
+        assert!(timer().same_as(&timer()));
+
+        let mut other = timer();
+        other.set_wait(true);
+        assert!(!timer().same_as(&other));
+
+        let mut other = timer();
+        other.set_session(true);
+        assert!(!timer().same_as(&other));
+
+        // What happened at runtime doesn't count
+        let mut other = timer();
+        other.set_disabled(true);
+        assert!(timer().same_as(&other));
+        other.set_paused(true);
+        assert!(timer().same_as(&other));
+    }
+
+    #[test]
+    fn reload_changed_flag() {
+        let timer = || CmdTimer::from_parts(Duration::from_secs(1), vec![], vec![], vec![]);
+        let mut xidlehook = Xidlehook::new(vec![timer(), timer()]);
+        xidlehook.step(Duration::from_secs(1)).unwrap();
+        assert_eq!(xidlehook.timer_state(0), TimerState::Active);
+
+        // Nothing changed, so the chain carries on
+        assert!(xidlehook
+            .replace_timers(vec![timer(), timer()], CmdTimer::same_as)
+            .unwrap());
+        assert_eq!(xidlehook.timer_state(0), TimerState::Active);
+
+        // A flag of the timer that went off changed, so it starts over with the new timer
+        let mut changed = timer();
+        changed.set_wait(true);
+        assert!(!xidlehook
+            .replace_timers(vec![changed, timer()], CmdTimer::same_as)
+            .unwrap());
+        assert!(xidlehook.timers()[0].get_wait());
+    }
+
+    #[test]
+    fn on_failure_when_exited() {
+        let mut timer = CmdTimer::from_parts(Duration::from_secs(1), vec![], vec![], vec![]);
+        // Only a failure to run the command exits by default
+        assert!(timer.activation_exited("exit code 1").is_ok());
+        timer.set_on_failure(Some(FailureAction::Ignore));
+        assert!(timer.activation_exited("exit code 1").is_ok());
+        timer.set_on_failure(Some(FailureAction::Exit));
+        assert!(timer.activation_exited("exit code 1").is_err());
     }
 }